@@ -15,3 +15,28 @@ Benchee.run(%{
     Soy.Native.db_batch(db, batch)
   end,
 })
+
+# Large values: fetch copies pinned bytes once, multi_get and snapshot
+# reads hand values >= 64KiB to the BEAM without copying.
+blob_keys = for i <- 1..16, do: "blob:#{i}"
+blob_sizes = [{"64KiB", 64 * 1024}, {"1MiB", 1024 * 1024}, {"8MiB", 8 * 1024 * 1024}]
+
+for {label, size} <- blob_sizes do
+  blob_db = Soy.Native.path_open_db(tmp_dir(), %Soy.OpenOpts{})
+  blob = :crypto.strong_rand_bytes(size)
+  Enum.each(blob_keys, fn k -> :ok = Soy.Native.db_put(blob_db, k, blob) end)
+  ss = Soy.Native.db_snapshot(blob_db)
+
+  Benchee.run(
+    %{
+      "fetch_#{label}" => fn -> Enum.map(blob_keys, fn k -> Soy.Native.db_fetch(blob_db, k) end) end,
+      "multi_get_#{label}" => fn -> Soy.Native.db_multi_get(blob_db, blob_keys) end,
+      "ss_multi_get_#{label}" => fn -> Soy.Native.ss_multi_get(ss, blob_keys) end,
+      "iter_#{label}" => fn ->
+        it = Soy.Native.db_iter(blob_db)
+        Enum.map(blob_keys, fn _ -> Soy.Native.iter_seek(it, :next) end)
+      end
+    },
+    memory_time: 1
+  )
+end
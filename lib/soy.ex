@@ -13,6 +13,8 @@ defmodule Soy do
     DB.repair(path)
   end
 
+  def close({DB, _} = db), do: DB.close(db)
+
   def path({DB, _} = db), do: DB.path(db)
 
   @doc """
@@ -101,6 +103,7 @@ defmodule Soy do
     case impl.fetch_cf(store, col_fam, key) do
       {:ok, got} -> got
       :error -> default
+      {:error, reason} -> raise ArgumentError, "cannot get #{inspect(key)}: #{inspect(reason)}"
     end
   end
 
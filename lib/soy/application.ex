@@ -7,9 +7,11 @@ defmodule Soy.Application do
 
   @impl true
   def start(_type, _args) do
+    :ok = Soy.Dirty.load()
+
     children = [
-      # Starts a worker by calling: Soy.Worker.start_link(arg)
-      # {Soy.Worker, arg}
+      Soy.LogForwarder,
+      Soy.ChangeMonitor
     ]
 
     # See https://hexdocs.pm/elixir/Supervisor.html
@@ -0,0 +1,13 @@
+defmodule Soy.CfExport do
+  @moduledoc """
+  The result of `Soy.DBCol.export/2`: the `dir` holding the SST files
+  written by the export, the column family `name` they were exported from,
+  the total `num_entries` and the `Soy.SstFileInfo` of every written file.
+  """
+  defstruct [
+    :name,
+    :dir,
+    :num_entries,
+    :files
+  ]
+end
@@ -0,0 +1,12 @@
+defmodule Soy.CfMetadata do
+  @moduledoc """
+  The result of `Soy.DBCol.metadata/1`: the total `size` and `file_count` of
+  the column family `name` and a `Soy.LevelMetadata` for each of its levels.
+  """
+  defstruct [
+    :name,
+    :size,
+    :file_count,
+    :levels
+  ]
+end
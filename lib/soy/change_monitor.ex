@@ -0,0 +1,40 @@
+defmodule Soy.ChangeMonitor do
+  @moduledoc false
+
+  # Ends the subscriptions of `Soy.DB.subscribe_changes/3` when their
+  # process exits. Started with the application; only subscription ids are
+  # kept, so the monitor does not keep any db alive.
+
+  use GenServer
+
+  alias Soy.Native
+
+  def start_link(_) do
+    GenServer.start_link(__MODULE__, nil, name: __MODULE__)
+  end
+
+  def monitor(pid, id), do: GenServer.call(__MODULE__, {:monitor, pid, id})
+
+  def unsubscribe(id), do: GenServer.call(__MODULE__, {:unsubscribe, id})
+
+  @impl true
+  def init(nil), do: {:ok, %{}}
+
+  @impl true
+  def handle_call({:monitor, pid, id}, _from, by_id) do
+    {:reply, :ok, Map.put(by_id, id, Process.monitor(pid))}
+  end
+
+  def handle_call({:unsubscribe, id}, _from, by_id) do
+    {ref, by_id} = Map.pop(by_id, id)
+    if ref, do: Process.demonitor(ref, [:flush])
+    {:reply, Native.changes_unsubscribe(id), by_id}
+  end
+
+  @impl true
+  def handle_info({:DOWN, ref, :process, _, _}, by_id) do
+    ids = for {id, ^ref} <- by_id, do: id
+    Enum.each(ids, &Native.changes_unsubscribe/1)
+    {:noreply, Map.drop(by_id, ids)}
+  end
+end
@@ -0,0 +1,134 @@
+defmodule Soy.Checkpoint do
+  @moduledoc """
+  Copies a db to another process, e.g. to bootstrap a replica on another
+  node, by streaming the files of a checkpoint as messages.
+
+  The sender creates the checkpoint with `stream/3`. The receiving process
+  reassembles it with `receive_into/3` into a dir that can be opened with
+  `Soy.open/2`. The receiver sets the pace, so a large db is never queued
+  up in a mailbox.
+  """
+
+  alias Soy.{DB, Native}
+
+  @chunk_size 1024 * 1024
+  @window 8
+  @timeout 60_000
+
+  @doc """
+  Creates a checkpoint of the `db` and sends its files to `pid` in chunks
+  of at most `chunk_size:` bytes (1 MiB by default). Returns `{:ok, id}`
+  where the `id` tags every message of the stream:
+
+    * `{:soy_checkpoint, id, {:chunk, name, offset, crc, data}}`
+    * `{:soy_checkpoint, id, {:done, [{name, size, crc}]}}`
+    * `{:soy_checkpoint, id, {:error, reason}}`
+
+  The `id` is the pid of the local process that reads and sends the
+  chunks. The receiver acks every chunk with `{:soy_checkpoint_ack, id}`
+  sent to it, as `receive_into/3` does, and at most `window:` chunks
+  (8 by default) are sent ahead of the acks. Streaming stops when `pid`
+  exits, or with `{:error, :timeout}` when no ack arrives within
+  `timeout:` milliseconds (60 seconds by default).
+
+  The checkpoint is created next to the db dir and removed once it was
+  sent or streaming stopped.
+  """
+  def stream(db, pid, opts \\ []) when is_pid(pid) do
+    n = :erlang.unique_integer([:positive])
+    chunk_size = Keyword.get(opts, :chunk_size, @chunk_size)
+    window = Keyword.get(opts, :window, @window)
+    timeout = Keyword.get(opts, :timeout, @timeout)
+
+    case Native.db_stream_checkpoint(DB.to_ref(db), n, chunk_size) do
+      stream when is_reference(stream) ->
+        sender = spawn(fn -> send_stream(stream, pid, window, timeout) end)
+        {:ok, sender}
+
+      {:error, _} = err ->
+        err
+    end
+  end
+
+  defp send_stream(stream, pid, window, timeout) do
+    ref = Process.monitor(pid)
+    send_chunks(stream, pid, ref, window, timeout)
+  end
+
+  # the stream resource, and with it the checkpoint, goes away with this
+  # process
+  defp send_chunks(stream, pid, ref, 0, timeout) do
+    id = self()
+
+    receive do
+      {:soy_checkpoint_ack, ^id} -> send_chunks(stream, pid, ref, 1, timeout)
+      {:DOWN, ^ref, :process, _, _} -> :ok
+    after
+      timeout -> send(pid, {:soy_checkpoint, id, {:error, :timeout}})
+    end
+  end
+
+  defp send_chunks(stream, pid, ref, credit, timeout) do
+    id = self()
+
+    receive do
+      {:soy_checkpoint_ack, ^id} -> send_chunks(stream, pid, ref, credit + 1, timeout)
+      {:DOWN, ^ref, :process, _, _} -> :ok
+    after
+      0 ->
+        case Native.checkpoint_stream_next(stream) do
+          {:chunk, _, _, _, _} = chunk ->
+            send(pid, {:soy_checkpoint, id, chunk})
+            send_chunks(stream, pid, ref, credit - 1, timeout)
+
+          {:done, _} = done ->
+            send(pid, {:soy_checkpoint, id, done})
+
+          {:error, _} = err ->
+            send(pid, {:soy_checkpoint, id, err})
+        end
+    end
+  end
+
+  @doc """
+  Receives the stream with `id` into `dir`, checking the crc of every chunk
+  and of every file once the stream is done. Every chunk is acked once it
+  was written. Returns `{:ok, dir}`, or an error if a chunk is corrupt or
+  no message arrives within `timeout` milliseconds.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> :ok = Soy.put(db, "k1", "v1")
+      iex> {:ok, id} = Soy.Checkpoint.stream(db, self())
+      iex> dir = Path.join(tmp_dir(), "replica")
+      iex> {:ok, ^dir} = Soy.Checkpoint.receive_into(dir, id)
+      iex> replica = Soy.open(dir)
+      iex> Soy.get(replica, "k1")
+      "v1"
+  """
+  def receive_into(dir, id, timeout \\ 60_000) do
+    receive do
+      {:soy_checkpoint, ^id, {:chunk, name, offset, crc, data}} ->
+        case Native.path_write_checkpoint_chunk(dir, name, offset, crc, data) do
+          :ok ->
+            send(id, {:soy_checkpoint_ack, id})
+            receive_into(dir, id, timeout)
+
+          {:error, _} = err ->
+            err
+        end
+
+      {:soy_checkpoint, ^id, {:done, files}} ->
+        case Native.path_finish_checkpoint(dir, files) do
+          :ok -> {:ok, dir}
+          {:error, _} = err -> err
+        end
+
+      {:soy_checkpoint, ^id, {:error, _} = err} ->
+        err
+    after
+      timeout -> {:error, :timeout}
+    end
+  end
+end
@@ -1,5 +1,5 @@
 defmodule Soy.DB do
-  alias Soy.{DB, Iter, Native, OpenOpts, Snapshot, DBCol}
+  alias Soy.{ChangeMonitor, DB, Dirty, IngestOpts, Iter, Native, OpenOpts, Snapshot, DBCol}
 
   @doc """
   Opens a db at the given path with the given options list or
@@ -25,13 +25,40 @@ defmodule Soy.DB do
   def open(path, options \\ [])
 
   def open(path, opts) when is_list(opts) do
-    open(path, struct!(OpenOpts, opts))
+    {load_latest?, opts} = Keyword.pop(opts, :load_latest_options, false)
+    open(path, struct!(base_open_opts(path, load_latest?), opts))
   end
 
   def open(path, %OpenOpts{} = open_config) do
+    open_config = OpenOpts.validate!(open_config)
+
+    open_config = %OpenOpts{
+      open_config
+      | event_listener: whereis(open_config.event_listener),
+        log_listener: whereis(open_config.log_listener)
+    }
+
     {DB, Native.path_open_db(path, open_config)}
   end
 
+  defp base_open_opts(path, true) do
+    case OpenOpts.load_latest(path) do
+      {:ok, loaded} -> OpenOpts.from_persisted(loaded)
+      {:error, _} -> %OpenOpts{}
+    end
+  end
+
+  defp base_open_opts(_path, false), do: %OpenOpts{}
+
+  defp whereis(name) when is_atom(name) and name != nil do
+    case Process.whereis(name) do
+      nil -> raise ArgumentError, "no process registered as #{inspect(name)}"
+      pid -> pid
+    end
+  end
+
+  defp whereis(pid_or_nil), do: pid_or_nil
+
   @doc """
   Returns the reference of a tagged db or a reference itself.
   """
@@ -80,7 +107,7 @@ defmodule Soy.DB do
       iex> db = Soy.open(path)
       iex> :ok = Soy.put(db, "hello", "world")
       iex> "world" = Soy.get(db, "hello")
-      iex> magically_drop_db_from_scope()
+      iex> :ok = Soy.close(db)
       iex> :ok = Soy.destroy(path)
       iex> db = Soy.open(path)
       iex> Soy.get(db, "hello")
@@ -96,8 +123,8 @@ defmodule Soy.DB do
 
   ## Examples
       iex> path = tmp_dir()
-      iex> _db = Soy.open(path)
-      iex> magically_drop_db_from_scope()
+      iex> db = Soy.open(path)
+      iex> :ok = Soy.close(db)
       iex> :ok = Soy.repair(path)
       iex> {DB, db} = Soy.open(path)
       iex> is_reference(db)
@@ -108,6 +135,34 @@ defmodule Soy.DB do
     Native.path_repair(path)
   end
 
+  @doc """
+  Closes the db.
+
+  Flushes the memtables, waits for background work to stop and releases
+  the db's lock file so the path can be reopened, repaired or destroyed
+  right away. Every snapshot, column family and iterator of the db is
+  released too; using the db or any of them afterwards returns
+  `{:error, :closed}`.
+
+  ## Examples
+
+      iex> path = tmp_dir()
+      iex> db = Soy.open(path)
+      iex> :ok = Soy.put(db, "hello", "world")
+      iex> :ok = DB.close(db)
+      iex> DB.close(db)
+      {:error, :closed}
+      iex> Soy.put(db, "hello", "again")
+      {:error, :closed}
+      iex> db = Soy.open(path)
+      iex> Soy.get(db, "hello")
+      "world"
+
+  """
+  def close(db) do
+    Native.db_close(to_ref(db))
+  end
+
   @doc """
   The path of the db.
 
@@ -123,6 +178,100 @@ defmodule Soy.DB do
     Native.db_path(to_ref(db))
   end
 
+  @doc """
+  Returns the value of a RocksDB property of the default column family as
+  an integer or a string, or `nil` for an unknown property.
+
+  See `Soy.DBCol.property/2` for other column families.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> :ok = Soy.put(db, "hello", "world")
+      iex> DB.property(db, "rocksdb.estimate-num-keys")
+      1
+
+  """
+  def property(db, prop) do
+    Native.db_get_property(to_ref(db), prop)
+  end
+
+  @doc """
+  Returns the value of a map-valued RocksDB property of the default
+  column family, such as `"rocksdb.cfstats"` or
+  `"rocksdb.aggregated-table-properties"`, parsed into a map.
+
+  Compaction stats tables are found under `"compaction_stats"` keyed by
+  row (`"L0"`, `"Sum"`, ...) and column (`"Files"`, `"W-Amp"`, ...).
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> DB.property_map(db, "rocksdb.aggregated-table-properties")["# entries"]
+      0
+
+  """
+  def property_map(db, prop) do
+    Native.db_get_property_map(to_ref(db), prop)
+  end
+
+  @doc """
+  Changes mutable column family options of the default column family on
+  the open db, given as a map or keyword list of RocksDB option names
+  (`write_buffer_size`, `disable_auto_compactions`,
+  `level0_slowdown_writes_trigger`, `level0_stop_writes_trigger`, ...) to
+  values. The options are applied all at once or not at all.
+
+  Returns `{:error, {reason, name}}` for the offending option that comes
+  first by name, where `reason` is `:unknown_option`, `:immutable_option`
+  (it can only be set through `Soy.OpenOpts`), `:not_a_cf_option` or
+  `:invalid_option_value`. Db options such as `max_background_jobs` are
+  `:not_a_cf_option`s: the RocksDB C API soy is built on cannot change db
+  options of an open db, so they have to be set through `Soy.OpenOpts`.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> DB.set_options(db, write_buffer_size: 8 * 1024 * 1024, disable_auto_compactions: false)
+      :ok
+      iex> DB.set_options(db, %{"num_levels" => 4})
+      {:error, {:immutable_option, "num_levels"}}
+      iex> DB.set_options(db, max_background_jobs: 4)
+      {:error, {:not_a_cf_option, "max_background_jobs"}}
+      iex> DB.set_options(db, write_buffer_size: "big")
+      {:error, {:invalid_option_value, "write_buffer_size"}}
+
+  """
+  def set_options(db, opts) do
+    Native.db_set_options(to_ref(db), Map.new(opts, fn {k, v} -> {to_string(k), v} end))
+  end
+
+  @doc """
+  Returns the approximate size in bytes and a rough estimate of the number
+  of keys of each `{start, limit}` key range (limit exclusive) of the default
+  column family as `%{size: bytes, keys: count}`.
+
+  Unflushed memtable contents are excluded from both: only SST files are
+  measured, as the RocksDB C API cannot size a key range of the memtables,
+  so writes count once they are flushed.
+
+  The key count is not a count of the range. It is
+  `"rocksdb.estimate-num-keys"` scaled by the range's share of the live SST
+  bytes, so it is off for ranges with unusually large or small values and
+  counts overwritten and deleted keys that are not yet compacted away.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> :ok = Soy.put(db, "a", "1")
+      iex> DB.approximate_sizes(db, [{"a", "b"}, {"x", "z"}])
+      [%{keys: 0, size: 0}, %{keys: 0, size: 0}]
+
+  """
+  def approximate_sizes(db, ranges) do
+    Native.db_get_approximate_sizes(to_ref(db), ranges)
+  end
+
   @doc """
   Returns the live files of the database.
 
@@ -134,6 +283,62 @@ defmodule Soy.DB do
     Native.db_live_files(to_ref(db))
   end
 
+  @doc """
+  Deletes the SST files of the default column family that lie entirely
+  within `from..to` (both inclusive) without writing tombstones. Level 0
+  files are never deleted.
+
+  This reclaims space quickly but is not a delete: keys in files that only
+  partly overlap the range, and keys in memtables, remain. Snapshots and
+  iterators may no longer see the deleted data.
+  """
+  def delete_files_in_range(db, from, to) do
+    Native.db_delete_files_in_range(to_ref(db), from, to)
+  end
+
+  @doc """
+  Returns the `Soy.Statistics` collected by a db opened with
+  `enable_statistics: true`, or `nil` when statistics are not enabled.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir(), enable_statistics: true)
+      iex> :ok = Soy.put(db, "hello", "world")
+      iex> "world" = Soy.get(db, "hello")
+      iex> stats = DB.statistics(db)
+      iex> stats.tickers["rocksdb.number.keys.written"]
+      1
+      iex> stats.histograms["rocksdb.db.get.micros"].count
+      1
+
+      iex> db = Soy.open(tmp_dir())
+      iex> DB.statistics(db)
+      nil
+
+  """
+  def statistics(db) do
+    Native.db_statistics(to_ref(db))
+  end
+
+  @doc """
+  Resets the statistics of the db.
+
+  Tickers and the `count` and `sum` of histograms restart from zero.
+  Histogram percentiles keep covering everything since the db was opened.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir(), enable_statistics: true)
+      iex> :ok = Soy.put(db, "hello", "world")
+      iex> :ok = DB.reset_statistics(db)
+      iex> DB.statistics(db).tickers["rocksdb.number.keys.written"]
+      0
+
+  """
+  def reset_statistics(db) do
+    Native.db_reset_statistics(to_ref(db))
+  end
+
   @doc """
   Stores a value in the DB.
 
@@ -194,6 +399,9 @@ defmodule Soy.DB do
   Returns `default` (the default of `default` is `nil`) for a missing key and `binary` for
   a found key. The `default` can be overridden with get/3.
 
+  Raises an `ArgumentError` if the db is closed; `fetch/2` returns the
+  error instead.
+
   ## Examples
 
   For a missing key:
@@ -217,6 +425,7 @@ defmodule Soy.DB do
     case fetch(db, key) do
       {:ok, got} -> got
       :error -> default
+      {:error, reason} -> raise ArgumentError, "cannot get #{inspect(key)}: #{inspect(reason)}"
     end
   end
 
@@ -292,14 +501,183 @@ defmodule Soy.DB do
 
   """
   def batch(db, ops) when is_list(ops) do
-    Native.db_batch(to_ref(db), ops)
+    if Dirty.dirty?(:batch, ops) do
+      Native.db_batch_dirty(to_ref(db), ops)
+    else
+      Native.db_batch(to_ref(db), ops)
+    end
+  end
+
+  @doc """
+  Subscribes a process to the writes to keys starting with `prefix` and
+  returns `{:ok, id}`. After every successful put, delete, merge or batch
+  the process receives a `{:soy_change, cf, op, key, value}` message per
+  matching write, where `op` is `:put`, `:delete` or `:merge` and `value`
+  is `nil` for deletes. Batches only hold puts and deletes.
+
+  Only writes made through this db are reported; writes replicated with
+  `Soy.Wal.apply_batch/3` or ingested from files are not. Messages about
+  writes from different processes can arrive in another order than the
+  writes were applied in.
+
+  The subscription ends with `unsubscribe_changes/2` or when the process
+  exits, which a process started with the application monitors.
+
+  Options:
+
+    * `:cf` - (default: `"default"`) the name of the column family.
+    * `:pid` - (default: `self()`) the local process to notify.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> {:ok, _id} = DB.subscribe_changes(db, "user:")
+      iex> :ok = Soy.put(db, "user:1", "bill")
+      iex> :ok = Soy.put(db, "post:1", "hello")
+      iex> :ok = Soy.delete(db, "user:1")
+      iex> receive do: ({:soy_change, _, _, _, _} = msg -> msg)
+      {:soy_change, "default", :put, "user:1", "bill"}
+      iex> receive do: ({:soy_change, _, _, _, _} = msg -> msg)
+      {:soy_change, "default", :delete, "user:1", nil}
+
+  """
+  def subscribe_changes(db, prefix, opts \\ []) when is_binary(prefix) do
+    cf = Keyword.get(opts, :cf, "default")
+    pid = Keyword.get(opts, :pid, self())
+    id = :erlang.unique_integer([:positive])
+
+    case Native.db_subscribe_changes(to_ref(db), pid, id, cf, prefix) do
+      :ok ->
+        :ok = ChangeMonitor.monitor(pid, id)
+        {:ok, id}
+
+      {:error, _} = err ->
+        err
+    end
+  end
+
+  @doc """
+  Ends a subscription of `subscribe_changes/3`.
+  """
+  def unsubscribe_changes(_db, id) do
+    ChangeMonitor.unsubscribe(id)
+  end
+
+  @doc """
+  Returns the number of subscriptions of `subscribe_changes/3` to the db.
+  """
+  def count_change_subscriptions(db) do
+    Native.db_count_change_subscriptions(to_ref(db))
+  end
+
+  @doc """
+  Ingests SST files written with `Soy.SstWriter` into the default column
+  family of the db.
+
+  The ingestion is atomic: either all of the files are added or none are.
+  See `Soy.IngestOpts` for the options.
+  """
+  def ingest(db, paths, opts \\ []) when is_list(paths) do
+    Native.db_ingest_external_file(to_ref(db), paths, IngestOpts.new(opts))
   end
 
   @doc """
   Gets multiple keys from the db.
   """
   def multi_get(db, keys) do
-    Native.db_multi_get(to_ref(db), keys)
+    if Dirty.dirty?(:multi_get, keys) do
+      Native.db_multi_get_dirty(to_ref(db), keys)
+    else
+      Native.db_multi_get(to_ref(db), keys)
+    end
+  end
+
+  @doc """
+  The sequence number of the last write to the `db`.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> DB.latest_sequence_number(db)
+      0
+      iex> :ok = Soy.put(db, "k1", "v1")
+      iex> DB.latest_sequence_number(db)
+      1
+
+  """
+  def latest_sequence_number(db) do
+    Native.db_latest_sequence_number(to_ref(db))
+  end
+
+  @doc """
+  Returns the sequence number of the oldest write the WAL still holds, or
+  the next sequence number when it holds none.
+
+  `Soy.Wal.updates_since/2` from an earlier sequence number misses writes,
+  so a consumer that fell behind it has to start over from a snapshot.
+  How long WAL files are kept is set with the `:set_wal_ttl_seconds` and
+  `:set_wal_size_limit_mb` options of `Soy.OpenOpts`.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> DB.oldest_available_sequence_number(db)
+      1
+      iex> :ok = Soy.put(db, "k1", "v1")
+      iex> DB.oldest_available_sequence_number(db)
+      1
+
+  """
+  def oldest_available_sequence_number(db) do
+    Native.db_oldest_available_sequence_number(to_ref(db))
+  end
+
+  @doc """
+  Dumps the `db` as of now into a file at `path`. See
+  `Soy.Snapshot.dump/3` for the options.
+  """
+  def dump(db, path, opts \\ []) do
+    case snapshot(db) do
+      {Snapshot, _} = ss ->
+        dumped = Snapshot.dump(ss, path, opts)
+        :ok = Snapshot.release(ss)
+        dumped
+
+      {:error, _} = err ->
+        err
+    end
+  end
+
+  @doc """
+  Restores a dump written by `dump/3` or `Soy.Snapshot.dump/3` into the
+  `db`. Column families missing from the `db` are created with the
+  `cf_opts:` (see `Soy.OpenOpts`). Entries that already exist are
+  overwritten.
+
+  Every block of the dump is checked against its checksum before it is
+  written. With `mode: :batch`, the default, each block is written as one
+  batch. With `mode: :ingest` the entries of each column family are written
+  into SST files and ingested, which is faster for large dumps.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> {:ok, cf} = DBCol.create_new(db, "feet")
+      iex> :ok = DBCol.put(cf, "k1", "v1")
+      iex> path = Path.join(tmp_dir(), "db.dump")
+      iex> {:ok, _} = DB.dump(db, path)
+      iex> other = Soy.open(tmp_dir())
+      iex> {:ok, info} = DB.load_dump(other, path, mode: :ingest)
+      iex> info.column_families
+      ["default", "feet"]
+      iex> {:ok, cf} = DBCol.open(other, "feet")
+      iex> DBCol.get(cf, "k1")
+      "v1"
+  """
+  def load_dump(db, path, opts \\ []) do
+    mode = Keyword.get(opts, :mode, :batch)
+    cf_opts = OpenOpts.new(Keyword.get(opts, :cf_opts, []))
+    Native.db_load_dump(to_ref(db), path, mode, cf_opts)
   end
 
   @doc """
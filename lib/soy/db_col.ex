@@ -3,7 +3,7 @@ defmodule Soy.DBCol do
   For dealing with a column family.
   """
 
-  alias Soy.{DBCol, DB, Native, OpenOpts}
+  alias Soy.{CfExport, DBCol, DB, Dirty, IngestOpts, Native, OpenOpts}
 
   @doc """
   Creates a column family for with `name` and `opts` in the `db`
@@ -18,6 +18,67 @@ defmodule Soy.DBCol do
     end
   end
 
+  @doc """
+  Exports every entry of the column family, as of the moment the export
+  starts, into SST files in `dir`. The `dir` must not exist yet, and is
+  removed again if the export fails so it can be retried.
+
+  RocksDB 6.28 has no `ExportColumnFamily` in its C API, so this is a
+  logical export: every entry is read and written into new SST files
+  with default options, which takes time and space in proportion to the
+  data instead of hard linking the column family's files. The files carry
+  no prefix extractor or merge operator, so pass those to `import/5` as
+  `opts`. The `files` describe the new SST files, not the column family's.
+
+  Returns `{:ok, %Soy.CfExport{}}` which can be given to `import/5` on any
+  db, e.g. after copying the `dir` to another node.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> {:ok, cf} = DBCol.create_new(db, "tenant")
+      iex> :ok = DBCol.put(cf, "k1", "v1")
+      iex> :ok = DBCol.put(cf, "k2", "v2")
+      iex> dir = Path.join(tmp_dir(), "export")
+      iex> {:ok, export} = DBCol.export(cf, dir)
+      iex> {export.name, export.num_entries, length(export.files)}
+      {"tenant", 2, 1}
+      iex> other = Soy.open(tmp_dir())
+      iex> {:ok, imported} = DBCol.import(other, "tenant", export)
+      iex> DBCol.get(imported, "k2")
+      "v2"
+
+  """
+  def export(cf, dir) do
+    Native.db_cf_export(to_ref(cf), dir)
+  end
+
+  @doc """
+  Creates the column family `name` in the `db` from an export made with
+  `export/2`, given as a `Soy.CfExport` or the export dir.
+
+  The column family is created with `opts` (see `Soy.OpenOpts`) and the
+  files are ingested with `ingest_opts` (see `Soy.IngestOpts`); pass
+  `set_move_files: true` to move the files instead of copying them. If
+  the ingestion fails the column family is dropped again.
+  """
+  def import(db, name, export_or_dir, opts \\ [], ingest_opts \\ [])
+
+  def import(db, name, %CfExport{dir: dir}, opts, ingest_opts) do
+    import(db, name, dir, opts, ingest_opts)
+  end
+
+  def import(db, name, dir, opts, ingest_opts) when is_binary(dir) do
+    db_ref = DB.to_ref(db)
+    open_opts = OpenOpts.new(opts)
+    ingest_opts = IngestOpts.new(ingest_opts)
+
+    case Native.db_import_cf(db_ref, name, dir, open_opts, ingest_opts) do
+      cf_ref when is_reference(cf_ref) -> {:ok, {DBCol, cf_ref}}
+      {:error, _} = err -> err
+    end
+  end
+
   @doc """
   Builds a tuple with the `db` and `name`.
   """
@@ -50,6 +111,9 @@ defmodule Soy.DBCol do
   @doc """
   Drops a column family with `name` from the `db`.
 
+  Every handle, snapshot column family and iterator of the dropped column
+  family returns `{:error, {:column_family_dropped, name}}` from then on.
+
   # WARNING - this causes data loss of the column family for the specified `name`
   """
   def destroy(cf) do
@@ -77,11 +141,15 @@ defmodule Soy.DBCol do
   @doc """
   Gets the binary value of `key` in the `db` at the column family
   with `name`.
+
+  Raises an `ArgumentError` if the column family was dropped or its db
+  closed; `fetch/2` returns the error instead.
   """
   def get(cf, key, default \\ nil) do
     case fetch(cf, key) do
       {:ok, got} -> got
       :error -> default
+      {:error, reason} -> raise ArgumentError, "cannot get #{inspect(key)}: #{inspect(reason)}"
     end
   end
 
@@ -100,13 +168,127 @@ defmodule Soy.DBCol do
     Native.db_cf_delete(to_ref(cf), key)
   end
 
+  @doc """
+  Counts the entries of the column family with keys in the range
+  `from` (inclusive) to `to` (exclusive) without copying any values
+  into the BEAM. A `nil` bound leaves that side of the range open.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> {:ok, cf} = DBCol.create_new(db, "counted")
+      iex> for k <- ["a", "b", "c", "d"], do: :ok = DBCol.put(cf, k, "v")
+      iex> DBCol.count_range(cf)
+      4
+      iex> DBCol.count_range(cf, "b", "d")
+      2
+      iex> DBCol.count_range(cf, "c", nil)
+      2
+  """
+  def count_range(cf, from \\ nil, to \\ nil) do
+    Native.db_cf_count_range(to_ref(cf), from, to)
+  end
+
+  @doc """
+  Ingests SST files written with `Soy.SstWriter` into the column family.
+
+  See `Soy.DB.ingest/3`.
+
+  ## Examples
+
+      iex> path = Path.join(tmp_dir(), "fam.sst")
+      iex> {:ok, w} = Soy.SstWriter.open(path)
+      iex> 1 = Soy.SstWriter.add(w, [{:put, "k", "v"}])
+      iex> {:ok, _info} = Soy.SstWriter.finish(w)
+      iex> db = Soy.open(tmp_dir())
+      iex> {:ok, cf} = DBCol.create_new(db, "fam")
+      iex> :ok = DBCol.ingest(cf, [path], set_move_files: true)
+      iex> {DBCol.get(cf, "k"), Soy.get(db, "k")}
+      {"v", nil}
+  """
+  def ingest(cf, paths, opts \\ []) when is_list(paths) do
+    Native.db_cf_ingest_external_file(to_ref(cf), paths, IngestOpts.new(opts))
+  end
+
+  @doc """
+  Returns the value of a RocksDB property of the column family as an
+  integer or a string, or `nil` for an unknown property.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> {:ok, cf} = DBCol.create_new(db, "fam")
+      iex> :ok = DBCol.put(cf, "k", "v")
+      iex> DBCol.property(cf, "rocksdb.num-entries-active-mem-table")
+      1
+      iex> Soy.DB.property(db, "rocksdb.num-entries-active-mem-table")
+      0
+
+  """
+  def property(cf, prop) do
+    Native.db_cf_get_property(to_ref(cf), prop)
+  end
+
+  @doc """
+  Returns the value of a map-valued RocksDB property of the column family
+  parsed into a map. See `Soy.DB.property_map/2`.
+  """
+  def property_map(cf, prop) do
+    Native.db_cf_get_property_map(to_ref(cf), prop)
+  end
+
+  @doc """
+  Changes mutable options of the column family on the open db. See
+  `Soy.DB.set_options/2`.
+  """
+  def set_options(cf, opts) do
+    Native.db_cf_set_options(to_ref(cf), Map.new(opts, fn {k, v} -> {to_string(k), v} end))
+  end
+
+  @doc """
+  Returns the `Soy.CfMetadata` of the column family: its levels and, per
+  level, its SST files with their key and seqno ranges.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> {:ok, cf} = DBCol.create_new(db, "fam")
+      iex> :ok = DBCol.put(cf, "a", "1")
+      iex> :ok = Soy.Native.db_cf_flush(DBCol.to_ref(cf))
+      iex> %Soy.CfMetadata{file_count: 1, levels: [level0 | _]} = DBCol.metadata(cf)
+      iex> [%Soy.SstFileMetadata{smallest_key: "a", largest_key: "a"}] = level0.files
+      iex> level0.level
+      0
+
+  """
+  def metadata(cf) do
+    Native.db_cf_metadata(to_ref(cf))
+  end
+
+  @doc """
+  Deletes the SST files of the column family that lie entirely within
+  `from..to`. See `Soy.DB.delete_files_in_range/3`.
+  """
+  def delete_files_in_range(cf, from, to) do
+    Native.db_cf_delete_files_in_range(to_ref(cf), from, to)
+  end
+
+  @doc """
+  Returns the approximate size and a rough estimate of the number of keys
+  of each `{start, limit}` key range of the column family, excluding
+  unflushed memtable contents. See `Soy.DB.approximate_sizes/2`.
+  """
+  def approximate_sizes(cf, ranges) do
+    Native.db_cf_get_approximate_sizes(to_ref(cf), ranges)
+  end
+
   @doc """
   Gets binary value or nil for a list of {cf, key} pairs.
   """
   def multi_get(cf, keys) do
     ref = to_ref(cf)
     pairs = Enum.map(keys, fn k -> {ref, k} end)
-    Native.db_cf_multi_get(pairs)
+    do_multi_get(pairs)
   end
 
   @doc """
@@ -118,6 +300,14 @@ defmodule Soy.DBCol do
       {{DBCol, ref}, key} -> {ref, key}
       {ref, key} when is_reference(ref) -> {ref, key}
     end)
-    |> Native.db_cf_multi_get()
+    |> do_multi_get()
+  end
+
+  defp do_multi_get(pairs) do
+    if Dirty.dirty?(:multi_get, pairs) do
+      Native.db_cf_multi_get_dirty(pairs)
+    else
+      Native.db_cf_multi_get(pairs)
+    end
   end
 end
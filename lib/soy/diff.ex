@@ -0,0 +1,87 @@
+defmodule Soy.Diff do
+  @moduledoc """
+  Compares the keys of two snapshots, or of a snapshot and the db, in
+  chunks.
+
+  The entries of a diff are in key order:
+
+    * `{:added, key, value}` - the key is only in the new side.
+    * `{:removed, key, value}` - the key is only in the old side.
+    * `{:changed, key, old_value, new_value}`
+  """
+
+  alias Soy.{DB, Diff, Native, Snapshot}
+
+  @doc """
+  Starts a diff from `old` to `new`, each a snapshot or a db. A db is read
+  as of the call.
+
+  Options:
+
+    * `:cf` - (default: `"default"`) the name of the column family.
+    * `:from` - (default: nil) the first key to compare.
+    * `:to` - (default: nil) the key to stop before.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> :ok = Soy.put(db, "a", "1")
+      iex> :ok = Soy.put(db, "b", "2")
+      iex> ss = Soy.snapshot(db)
+      iex> :ok = Soy.put(db, "b", "3")
+      iex> :ok = Soy.delete(db, "a")
+      iex> :ok = Soy.put(db, "c", "4")
+      iex> diff = Diff.new(ss, db)
+      iex> Diff.next(diff, 2)
+      [{:removed, "a", "1"}, {:changed, "b", "2", "3"}]
+      iex> Diff.next(diff, 2)
+      [{:added, "c", "4"}]
+      iex> Diff.next(diff, 2)
+      nil
+  """
+  def new(old, new, opts \\ []) do
+    cf = Keyword.get(opts, :cf)
+    from = Keyword.get(opts, :from)
+    to = Keyword.get(opts, :to)
+    wrap(Native.ss_diff(side_ref(old), side_ref(new), cf, from, to))
+  end
+
+  @doc """
+  Returns up to `max` entries, or `nil` once the diff is done. A chunk may
+  be empty when a long run of equal keys was skipped.
+  """
+  def next(diff, max \\ 1000) when is_integer(max) and max > 0 do
+    Native.diff_next(to_ref(diff), max)
+  end
+
+  @doc """
+  Returns a stream of the entries of a diff from `old` to `new`. Takes the
+  options of `new/3`.
+  """
+  def stream(old, new, opts \\ []) do
+    Stream.resource(
+      fn ->
+        case new(old, new, opts) do
+          {:error, reason} -> raise ArgumentError, "cannot diff: #{inspect(reason)}"
+          diff -> diff
+        end
+      end,
+      fn diff ->
+        case next(diff) do
+          nil -> {:halt, diff}
+          {:error, reason} -> raise ArgumentError, "cannot diff: #{inspect(reason)}"
+          entries -> {entries, diff}
+        end
+      end,
+      fn _ -> :ok end
+    )
+  end
+
+  def to_ref({Diff, ref}) when is_reference(ref), do: ref
+
+  defp side_ref({Snapshot, _} = ss), do: Snapshot.to_ref(ss)
+  defp side_ref({DB, _} = db), do: DB.to_ref(db)
+
+  defp wrap(ref) when is_reference(ref), do: {Diff, ref}
+  defp wrap({:error, _} = err), do: err
+end
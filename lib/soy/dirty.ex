@@ -0,0 +1,73 @@
+defmodule Soy.Dirty do
+  @moduledoc """
+  Size-aware dispatching of NIF calls onto dirty IO schedulers.
+
+  Small batches and multi-gets run on the normal schedulers. Once the
+  number of ops (for `:batch`) or keys (for `:multi_get`) reaches the
+  configured threshold, or the keys and values add up to `:bytes`, the
+  `_dirty` variant of the NIF is called instead, so large calls cannot
+  block a normal scheduler for more than a millisecond.
+
+  Thresholds are read from the application env when `:soy` starts:
+
+      config :soy, :dirty_thresholds,
+        batch: 1_000,
+        multi_get: 500,
+        bytes: 1_048_576
+
+  ## Examples
+
+      iex> Soy.Dirty.dirty?(:batch, 1)
+      false
+      iex> Soy.Dirty.dirty?(:batch, 1_000_000)
+      true
+      iex> Soy.Dirty.dirty?(:batch, [{:put, "k", "v"}])
+      false
+      iex> Soy.Dirty.dirty?(:batch, [{:put, "k", :binary.copy("v", 1_048_576)}])
+      true
+  """
+
+  @defaults [batch: 1_000, multi_get: 500, bytes: 1_048_576]
+
+  @key {__MODULE__, :thresholds}
+
+  @doc """
+  Loads the thresholds from the `:soy` application env.
+  """
+  def load do
+    thresholds =
+      @defaults
+      |> Keyword.merge(Application.get_env(:soy, :dirty_thresholds, []))
+      |> Map.new()
+
+    :persistent_term.put(@key, thresholds)
+  end
+
+  @doc """
+  The current thresholds.
+  """
+  def thresholds do
+    :persistent_term.get(@key, Map.new(@defaults))
+  end
+
+  @doc """
+  Returns `true` when an `op` of the given `size` should run on a dirty
+  IO scheduler.
+
+  Given the ops or keys themselves, their count and the summed byte size
+  of their keys and values are checked.
+  """
+  def dirty?(op, size) when is_integer(size) do
+    size >= Map.fetch!(thresholds(), op)
+  end
+
+  def dirty?(op, items) when is_list(items) do
+    dirty?(op, length(items)) or payload_size(items) >= Map.fetch!(thresholds(), :bytes)
+  end
+
+  # the binaries of ops and keys; column family refs add nothing
+  defp payload_size(bin) when is_binary(bin), do: byte_size(bin)
+  defp payload_size(list) when is_list(list), do: Enum.reduce(list, 0, &(payload_size(&1) + &2))
+  defp payload_size(tuple) when is_tuple(tuple), do: payload_size(Tuple.to_list(tuple))
+  defp payload_size(_), do: 0
+end
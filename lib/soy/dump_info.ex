@@ -0,0 +1,14 @@
+defmodule Soy.DumpInfo do
+  @moduledoc """
+  Describes a dump written by `Soy.Snapshot.dump/3` or restored by
+  `Soy.DB.load_dump/3`: the `path` of the dump file, the `sequence_number`
+  of the snapshot it was taken from, the total `num_entries` and the names
+  of the `column_families` it holds.
+  """
+  defstruct [
+    :path,
+    :sequence_number,
+    :num_entries,
+    :column_families
+  ]
+end
@@ -0,0 +1,19 @@
+defmodule Soy.FifoCompactOpts do
+  @moduledoc """
+  A struct matching RocksDB CompactionOptionsFIFO, used for
+  `:set_fifo_compaction_options` of `Soy.OpenOpts`.
+
+    * `:set_max_table_files_size` (default: 1GiB) - The total size of the
+      SST files at which the oldest files are deleted.
+
+  """
+  defstruct set_max_table_files_size: nil
+
+  def new(opts) when is_list(opts) do
+    struct!(__MODULE__, opts)
+  end
+
+  def new(%__MODULE__{} = opts) do
+    opts
+  end
+end
@@ -0,0 +1,33 @@
+defmodule Soy.IngestOpts do
+  @moduledoc """
+  A struct matching RocksDB IngestExternalFileOptions.
+
+  Options left as `nil` use the RocksDB defaults.
+
+    * `:set_move_files` (default: `false`) - If `true`, the files are moved
+      (hard linked) into the db instead of copied.
+    * `:set_snapshot_consistency` (default: `true`) - If `true`, snapshots
+      taken before the ingestion do not see the ingested keys.
+    * `:set_allow_global_seqno` (default: `true`) - If `false`, ingestion
+      fails when the files overlap keys already in the db.
+    * `:set_allow_blocking_flush` (default: `true`) - If `false`, ingestion
+      fails when the memtable overlaps the files and needs a flush first.
+    * `:set_ingest_behind` (default: `false`) - If `true`, the files are
+      ingested into the bottommost level and existing keys win over the
+      ingested ones. Requires a db opened with `allow_ingest_behind`.
+
+  """
+  defstruct set_move_files: nil,
+            set_snapshot_consistency: nil,
+            set_allow_global_seqno: nil,
+            set_allow_blocking_flush: nil,
+            set_ingest_behind: nil
+
+  def new(opts) when is_list(opts) do
+    struct!(__MODULE__, opts)
+  end
+
+  def new(%__MODULE__{} = ingest_opts) do
+    ingest_opts
+  end
+end
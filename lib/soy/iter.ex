@@ -33,10 +33,13 @@ defmodule Soy.Iter do
       nil
   """
   def new(store)
-  def new({DB, db}), do: {Iter, Native.db_iter(db)}
-  def new({Snapshot, ss}), do: {Iter, Native.ss_iter(ss)}
-  def new({SnapshotCol, ss}), do: {Iter, Native.ss_cf_iter(ss)}
-  def new({DBCol, cf}), do: {Iter, Native.db_cf_iter(cf)}
+  def new({DB, db}), do: wrap(Native.db_iter(db))
+  def new({Snapshot, ss}), do: wrap(Native.ss_iter(ss))
+  def new({SnapshotCol, ss}), do: wrap(Native.ss_cf_iter(ss))
+  def new({DBCol, cf}), do: wrap(Native.db_cf_iter(cf))
+
+  defp wrap(it_ref) when is_reference(it_ref), do: {Iter, it_ref}
+  defp wrap({:error, _} = err), do: err
 
   # def new(store, cf_name) do
   #   case store do
@@ -49,7 +52,32 @@ defmodule Soy.Iter do
 
   # def prefix({DBCol, {db, name}}, prefix), do: new(db, {:prefix, name, prefix})
 
-  def seek(it, kind), do: Soy.Native.iter_seek(to_ref(it), kind)
+  @doc """
+  Moves the iter according to `kind` and returns the entry at the new
+  position in the shape given by `mode`:
+
+    * `:key_value` (default) - `{key, value}`
+    * `:key` - `key` only; the value is never copied into the BEAM
+    * `:key_size` - `{key, value_byte_size}`
+
+  Returns `nil` when the iter becomes invalid.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> 2 = Soy.batch(db, [{:put, "k1", "v1"}, {:put, "k2", "value2"}])
+      iex> it = Iter.new(db)
+      iex> Iter.seek(it, :next, :key)
+      "k1"
+      iex> Iter.seek(it, :next, :key_size)
+      {"k2", 6}
+      iex> Iter.seek(it, :next, :key)
+      nil
+  """
+  def seek(it, kind, mode \\ :key_value)
+  def seek(it, kind, :key_value), do: Soy.Native.iter_seek(to_ref(it), kind)
+  def seek(it, kind, :key), do: Soy.Native.iter_seek_key(to_ref(it), kind)
+  def seek(it, kind, :key_size), do: Soy.Native.iter_seek_key_size(to_ref(it), kind)
 
   def first(it), do: seek(it, :first)
 
@@ -71,6 +99,21 @@ defmodule Soy.Iter do
 
   def valid?(it), do: Soy.Native.iter_valid(to_ref(it))
 
+  @doc """
+  Frees the iterator right away instead of when it is garbage collected.
+  Later use of the iterator returns `{:error, :released}`.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> it = Iter.new(db)
+      iex> Iter.close(it)
+      :ok
+      iex> Iter.next(it)
+      {:error, :released}
+  """
+  def close(it), do: Soy.Native.iter_close(to_ref(it))
+
   def to_ref({Iter, ref}) when is_reference(ref), do: ref
   def to_ref(ref) when is_reference(ref), do: ref
 end
@@ -0,0 +1,10 @@
+defmodule Soy.LevelMetadata do
+  @moduledoc """
+  The total `size` of a `level` and the `Soy.SstFileMetadata` of its files.
+  """
+  defstruct [
+    :level,
+    :size,
+    :files
+  ]
+end
@@ -0,0 +1,49 @@
+defmodule Soy.LogForwarder do
+  @moduledoc """
+  Routes the RocksDB info log into `Logger`.
+
+  Started with the application under its module name, so a db opened with
+  `log_listener: Soy.LogForwarder` has its log entries logged with the db
+  path in the `:rocksdb` metadata. RocksDB levels map to `Logger` levels as
+  `:debug` -> `:debug`, `:info` and `:header` -> `:info`, `:warn` ->
+  `:warning` and `:error` and `:fatal` -> `:error`.
+  """
+
+  use GenServer
+
+  require Logger
+
+  def start_link(_) do
+    GenServer.start_link(__MODULE__, nil, name: __MODULE__)
+  end
+
+  @impl true
+  def init(nil), do: {:ok, nil}
+
+  @impl true
+  def handle_info({:soy_log, path, level, message}, state) do
+    Logger.log(logger_level(level), message, rocksdb: path)
+    {:noreply, state}
+  end
+
+  def handle_info(_, state), do: {:noreply, state}
+
+  @doc """
+  The `Logger` level of a RocksDB info log level.
+
+  ## Examples
+
+      iex> Soy.LogForwarder.logger_level(:warn)
+      :warning
+
+      iex> Soy.LogForwarder.logger_level(:header)
+      :info
+
+  """
+  def logger_level(:debug), do: :debug
+  def logger_level(:info), do: :info
+  def logger_level(:header), do: :info
+  def logger_level(:warn), do: :warning
+  def logger_level(:error), do: :error
+  def logger_level(:fatal), do: :error
+end
@@ -5,11 +5,16 @@ defmodule Soy.Native do
 
   # path ops
   def path_open_db(_path, _options), do: err()
+  def path_load_latest_options(_path), do: err()
   def path_destroy(_path), do: err()
   def path_repair(_path), do: err()
   def path_list_cf(_path), do: err()
 
   def db_checkpoint(_db, _checkpoint_path), do: err()
+  def db_stream_checkpoint(_db, _id, _chunk_size), do: err()
+  def checkpoint_stream_next(_stream), do: err()
+  def path_write_checkpoint_chunk(_dir, _name, _offset, _crc, _data), do: err()
+  def path_finish_checkpoint(_dir, _files), do: err()
 
   # flush/sync to disk
   def db_flush(_db), do: err()
@@ -17,12 +22,14 @@ defmodule Soy.Native do
 
   # metadata
   def db_path(_db), do: err()
+  def db_close(_db), do: err()
   def db_live_files(_db), do: err()
 
   # db reads
   def db_fetch(_db, _key), do: err()
 
   def db_multi_get(_db, _keys), do: err()
+  def db_multi_get_dirty(_db, _keys), do: err()
   def db_multi_get_cf(_db, _cf_and_key_pairs), do: err()
   def db_key_may_exist(_db, _key), do: err()
 
@@ -33,11 +40,20 @@ defmodule Soy.Native do
   def db_delete(_db, _key), do: err()
   def db_put(_db, _key, _val), do: err()
   def db_batch(_db, _ops_list), do: err()
+  def db_batch_dirty(_db, _ops_list), do: err()
+  def db_subscribe_changes(_db, _pid, _id, _cf_name, _prefix), do: err()
+  def changes_unsubscribe(_id), do: err()
+  def db_count_change_subscriptions(_db), do: err()
+
+  # ingestion
+  def db_ingest_external_file(_db, _paths, _ingest_opts), do: err()
+  def db_cf_ingest_external_file(_cf, _paths, _ingest_opts), do: err()
 
   # cf create/drop
   def db_create_new_cf(_db, _col_name, _open_cfg), do: err()
   def db_open_existing_cf(_db, _col_name), do: err()
   def db_drop_cf(_db, _cf), do: err()
+  def db_import_cf(_db, _col_name, _dir, _open_cfg, _ingest_opts), do: err()
 
   # iteration for both db and cf based on itermode
   def db_iter(_db), do: err()
@@ -45,8 +61,11 @@ defmodule Soy.Native do
   def ss_iter(_ss), do: err()
 
   def iter_seek(_db_iter, _seek), do: err()
+  def iter_seek_key(_db_iter, _seek), do: err()
+  def iter_seek_key_size(_db_iter, _seek), do: err()
 
   def iter_valid(_db_iter), do: err()
+  def iter_close(_it), do: err()
   def iter_key(_it), do: err()
   def iter_value(_it), do: err()
   def iter_key_value(_it), do: err()
@@ -55,7 +74,27 @@ defmodule Soy.Native do
   def db_snapshot(_db), do: err()
   def ss_fetch(_ss, _key), do: err()
   def ss_multi_get(_ss, _keys), do: err()
+  def ss_multi_get_dirty(_ss, _keys), do: err()
   def ss_open_ss_cf(_ss, _cf_name), do: err()
+  def ss_release(_ss), do: err()
+  def ss_diff(_old, _new, _cf_name, _from, _to), do: err()
+  def diff_next(_diff, _max), do: err()
+  def ss_sequence_number(_ss), do: err()
+  def ss_created_at(_ss), do: err()
+  def ss_dump(_ss, _path, _cfs), do: err()
+  def db_load_dump(_db, _path, _mode, _open_opts), do: err()
+
+  # wal
+  def db_latest_sequence_number(_db), do: err()
+  def db_oldest_available_sequence_number(_db), do: err()
+  def db_get_updates_since(_db, _since), do: err()
+  def wal_iter_next(_wal_iter), do: err()
+  def wal_iter_next_batch(_wal_iter), do: err()
+  def wal_iter_take(_wal_iter, _n), do: err()
+  def wal_iter_subscribe(_wal_iter, _pid, _id, _interval_ms), do: err()
+  def wal_iter_next_binary(_wal_iter), do: err()
+  def db_apply_batch_binary(_db, _batch, _primary_cf_ids), do: err()
+  def db_wal_cf_ids(_db), do: err()
 
   # write opts
   def write_opts_default, do: err()
@@ -65,8 +104,22 @@ defmodule Soy.Native do
 
   # properties
   def db_get_property(_db, _prop), do: err()
+  def db_get_property_map(_db, _prop), do: err()
+  def db_cf_get_property(_cf, _prop), do: err()
+  def db_cf_get_property_map(_cf, _prop), do: err()
+  def db_get_approximate_sizes(_db, _ranges), do: err()
+  def db_cf_get_approximate_sizes(_cf, _ranges), do: err()
+  def db_cf_metadata(_cf), do: err()
+  def db_set_options(_db, _opts), do: err()
+  def db_cf_set_options(_cf, _opts), do: err()
+  def db_delete_files_in_range(_db, _from, _to), do: err()
+  def db_cf_delete_files_in_range(_cf, _from, _to), do: err()
   def db_list_properties(_db), do: err()
 
+  # statistics
+  def db_statistics(_db), do: err()
+  def db_reset_statistics(_db), do: err()
+
   # db col fam ops
   def db_cf_delete(_cf, _key), do: err()
   def db_cf_put(_cf, _key, _value), do: err()
@@ -77,15 +130,24 @@ defmodule Soy.Native do
   def db_cf_name(_cf), do: err()
   def db_cf_into_db(_cf), do: err()
   def db_cf_iter(_cf), do: err()
+  def db_cf_count_range(_cf, _from, _to), do: err()
+  def db_cf_export(_cf, _dir), do: err()
   def db_cf_flush(_cf), do: err()
   def db_cf_multi_get(_cf_key_pairs), do: err()
+  def db_cf_multi_get_dirty(_cf_key_pairs), do: err()
 
   # snapshot cf ops
   def ss_cf_multi_get(_ss_cf_and_key_pairs), do: err()
+  def ss_cf_multi_get_dirty(_ss_cf_and_key_pairs), do: err()
   def ss_cf_fetch(_ss_cf, _key), do: err()
   def ss_cf_iter(_ss_cf), do: err()
   def ss_cf_name(_ss_cf), do: err()
   def ss_cf_into_ss(_ss_cf), do: err()
 
+  # sst writer
+  def sst_writer_open(_path, _open_opts), do: err()
+  def sst_writer_add(_writer, _ops), do: err()
+  def sst_writer_finish(_writer), do: err()
+
   def resource_kind(_resource), do: err()
 end
@@ -13,6 +13,18 @@ defmodule Soy.OpenOpts do
       such that total of `total_threads` is used. Good value for `total_threads`
       is the number of cores. You almost definitely want to increase this value
       if your system is bottlenecked by RocksDB.
+    * `:set_max_background_jobs` - (default: nil) The maximum number of
+      concurrent flushes and compactions. RocksDB defaults to `2`.
+    * `:set_paranoid_checks` - (default: nil) If `true`, RocksDB checks data
+      aggressively and stops the db on the first corruption it finds.
+    * `:set_atomic_flush` - (default: nil) If `true`, the memtables of all
+      column families are flushed together.
+    * `:set_delete_obsolete_files_period_micros` - (default: nil) How often
+      obsolete files are deleted. Files going out of scope during compaction
+      are always deleted.
+    * `:set_ratelimiter` - (default: nil) A `{rate_bytes_per_sec,
+      refill_period_us, fairness}` tuple limiting the write rate of flushes
+      and compactions, for example `{10_485_760, 100_000, 10}`.
     * `:optimize_for_point_lookup` - makes get and put operations faster by
       creating a bloolfilter and setting the index type to kHashSearch.
     * `:optimize_level_style_compaction` - sets many buffer sizes inside RockDB.
@@ -22,14 +34,116 @@ defmodule Soy.OpenOpts do
       All data will be in level 0 without any automatic compaction. It’s
       recommended to manually call `compact_range(db, nil, nil)` before reading
       from the database, because otherwise the read can be very slow.
+    * `:enable_statistics` - (default: nil) If `true`, RocksDB collects
+      tickers and histograms that can be read with `Soy.DB.statistics/1`.
+      Collecting statistics costs 5-10% of throughput. The stats level
+      cannot be chosen: the C API of RocksDB 6.28 has no setter for it, so
+      RocksDB's default level (`kExceptDetailedTimers`) is used.
+    * `:event_listener` - (default: nil) A pid that is sent
+      `{:soy_event, path, event, info}` messages about the background work
+      of the db. See "Events" below.
+    * `:event_poll_interval_ms` - (default: 250) How often the db is checked
+      for events.
+    * `:event_table_files` - (default: false) If `true`, table file events
+      are sent too. Finding them lists the live files on every poll.
+
+  ### WAL Options
+
+    * `:set_wal_dir` - (default: nil) A directory for the WAL files instead of
+      the db directory.
+    * `:set_wal_ttl_seconds` - (default: nil) How long archived WAL files are
+      kept.
+    * `:set_wal_size_limit_mb` - (default: nil) The total size archived WAL
+      files are kept up to.
+    * `:set_max_total_wal_size` - (default: nil) The WAL size at which the
+      column families backed by the oldest WAL file are flushed.
+
+  Without a TTL or size limit RocksDB deletes WAL files once their writes
+  are flushed, after which `Soy.Wal` can no longer read them. With either
+  set, flushed WAL files are archived and deleted once they are older than
+  the TTL or exceed the size limit. `Soy.DB.oldest_available_sequence_number/1` tells how far
+  back the WAL can still be read.
+
+  ### Compaction Options
+
+    * `:set_compaction_style` - (default: `:universal`) One of `:level`,
+      `:universal` or `:fifo`.
+    * `:set_level_compaction_dynamic_level_bytes` - (default: nil) If `true`,
+      level sizes are picked dynamically from the size of the last level.
+    * `:set_max_bytes_for_level_base` - (default: nil) The total size of
+      level 1.
+    * `:set_max_bytes_for_level_multiplier` - (default: nil) How much larger
+      each level is than the one above.
+    * `:set_universal_compaction_options` - (default: nil) A
+      `Soy.UniversalCompactOpts` or keyword list of its fields.
+    * `:set_fifo_compaction_options` - (default: nil) A `Soy.FifoCompactOpts`
+      or keyword list of its fields.
+
+  ### Logging Options
+
+    * `:set_log_level` - (default: nil) The lowest level RocksDB writes to its
+      info log. One of `:debug`, `:info`, `:warn`, `:error`, `:fatal` or
+      `:header`. RocksDB defaults to `:info`.
+    * `:set_max_log_file_size` - (default: nil) The size in bytes at which the
+      info log is rolled. RocksDB defaults to `0`, one log per open.
+    * `:set_keep_log_file_num` - (default: nil) The number of rolled info logs
+      to keep. RocksDB defaults to `1000`.
+    * `:set_db_log_dir` - (default: nil) A directory for the info log instead
+      of the db directory. The file is named after the absolute db path.
+    * `:log_listener` - (default: nil) A pid or registered name that is sent
+      `{:soy_log, path, level, message}` messages for the entries of the info
+      log, starting with the ones written while opening. Pass
+      `Soy.LogForwarder` to route them into `Logger`.
+
+  ### Column Families
+
+    * `:column_families` - (default: nil) A map of column family names to
+      `Soy.OpenOpts` for the column families that exist when the db is
+      opened. The `"default"` column family uses the db options unless it
+      has an entry; others without an entry use RocksDB defaults.
+
+  ### Persisted Options
+
+  RocksDB writes the options a db runs with to an `OPTIONS-NNNNNN` file.
+  `load_latest/1` reads the latest one back and opening with
+  `load_latest_options: true` starts from those options, overridden by the
+  other options given:
+
+      Soy.open(path, load_latest_options: true, set_max_open_files: 512)
+
+  Only options that `Soy.OpenOpts` supports are loaded. Merge operators,
+  the rate limiter and `:increase_parallelism` are not persisted and have
+  to be given again.
+
+  ### Validation
+
+  `validate/1` checks the type of every option that is set. `Soy.DB.open/2`
+  raises an `ArgumentError` for invalid options.
+
+  ### Events
+
+  The db is only polled when an `:event_listener` is given. The events are
+  derived from the db's properties and, with `:event_table_files`, its live
+  files, so they are approximate and can be missed: a flush or compaction
+  that starts and finishes between two polls only shows up through its
+  table file events, if at all. The `event` and `info` are:
+
+    * `:flush_begin` / `:flush_completed` - `%{running: count}`
+    * `:compaction_begin` / `:compaction_completed` - `%{running: count}`
+    * `:stall_conditions_changed` - `%{prev: condition, cur: condition,
+      delayed_write_rate: bytes_per_sec}` where a condition is `:normal`,
+      `:delayed` or `:stopped`
+    * `:table_file_created` / `:table_file_deleted` - a `Soy.LiveFile`,
+      only with `:event_table_files`
+    * `:background_error` - `%{count: total_background_errors}`
+
+  Polling stops when the db is closed or garbage collected.
 
   """
 
   # TODO: support more options
   # TODO: match RocksDB documented defaults
 
-  # enable_statistics
-  # get_statistics
   # set_access_hint_on_compaction_start
 
   # set_advise_random_on_open
@@ -38,7 +152,6 @@ defmodule Soy.OpenOpts do
   # set_allow_mmap_writes
   # set_allow_os_buffer
   # set_arena_block_size
-  # set_atomic_flush
   # set_block_based_table_factory
   # set_bloom_locality
   # set_bottommost_compression_options
@@ -54,45 +167,34 @@ defmodule Soy.OpenOpts do
   # set_compression_per_level
   # set_compression_type
   # set_cuckoo_table_factory
-  # set_db_log_dir
   # set_db_paths
   # set_db_write_buffer_size
-  # set_delete_obsolete_files_period_micros
   # set_disable_auto_compactions
   # set_dump_malloc_stats
   # set_enable_pipelined_write
   # set_enable_write_thread_adaptive_yield
   # set_env
   # set_error_if_exists
-  # set_fifo_compaction_options
   # set_hard_pending_compaction_bytes_limit
   # set_inplace_update_locks
   # set_inplace_update_support
   # set_is_fd_close_on_exec
-  # set_keep_log_file_num
-  # set_level_compaction_dynamic_level_bytes
   # set_level_zero_file_num_compaction_trigger
   # set_level_zero_slowdown_writes_trigger
   # set_level_zero_stop_writes_trigger
   # set_log_file_time_to_roll
-  # set_log_level
   # set_manifest_preallocation_size
   # set_manual_wal_flush
   # set_max_background_compactions
   # set_max_background_flushes
-  # set_max_background_jobs
-  # set_max_bytes_for_level_base
-  # set_max_bytes_for_level_multiplier
   # set_max_bytes_for_level_multiplier_additional
   # set_max_compaction_bytes
   # set_max_file_opening_threads
-  # set_max_log_file_size
   # set_max_manifest_file_size
   # set_max_open_files
   # set_max_sequential_skip_in_iterations
   # set_max_subcompactions
   # set_max_successive_merges
-  # set_max_total_wal_size
   # set_max_write_buffer_number
   # set_max_write_buffer_size_to_maintain
   # set_memtable_factory
@@ -106,10 +208,8 @@ defmodule Soy.OpenOpts do
   # set_min_write_buffer_number_to_merge
   # set_num_levels
   # set_optimize_filters_for_hits
-  # set_paranoid_checks
   # set_plain_table_factory
   # set_prefix_extractor
-  # set_ratelimiter
   # set_recycle_log_file_num
   # set_report_bg_io_stats
   # set_row_cache
@@ -121,17 +221,13 @@ defmodule Soy.OpenOpts do
   # set_table_cache_num_shard_bits
   # set_target_file_size_base
   # set_target_file_size_multiplier
-  # set_universal_compaction_options
   # set_unordered_write
   # set_use_adaptive_mutex
   # set_use_direct_io_for_flush_and_compaction
   # set_use_direct_reads
   # set_use_fsync
   # set_wal_bytes_per_sync
-  # set_wal_dir
   # set_wal_recovery_mode
-  # set_wal_size_limit_mb
-  # set_wal_ttl_seconds
   # set_writable_file_max_buffer_size
   # set_write_buffer_size
   # set_zstd_max_train_bytes
@@ -153,12 +249,316 @@ defmodule Soy.OpenOpts do
             set_disable_auto_compactions: true,
             set_compaction_style: :universal,
             prefix_length: nil,
+            enable_statistics: nil,
+            event_listener: nil,
+            event_poll_interval_ms: nil,
+            event_table_files: nil,
+            set_log_level: nil,
+            set_max_log_file_size: nil,
+            set_keep_log_file_num: nil,
+            set_db_log_dir: nil,
+            log_listener: nil,
+            column_families: nil,
+            set_max_background_jobs: nil,
+            set_wal_dir: nil,
+            set_wal_ttl_seconds: nil,
+            set_wal_size_limit_mb: nil,
+            set_max_total_wal_size: nil,
+            set_atomic_flush: nil,
+            set_paranoid_checks: nil,
+            set_ratelimiter: nil,
+            set_delete_obsolete_files_period_micros: nil,
+            set_level_compaction_dynamic_level_bytes: nil,
+            set_max_bytes_for_level_base: nil,
+            set_max_bytes_for_level_multiplier: nil,
+            set_universal_compaction_options: nil,
+            set_fifo_compaction_options: nil,
             set_merge_operator_associative: nil
 
   def new(opts) when is_list(opts) do
     struct!(__MODULE__, opts)
   end
 
+  @db_options %{
+    "create_if_missing" => {:create_if_missing, :bool},
+    "create_missing_column_families" => {:create_missing_column_families, :bool},
+    "max_open_files" => {:set_max_open_files, :int},
+    "use_fsync" => {:set_use_fsync, :bool},
+    "bytes_per_sync" => {:set_bytes_per_sync, :int},
+    "table_cache_numshardbits" => {:set_table_cache_num_shard_bits, :int},
+    "info_log_level" => {:set_log_level, :log_level},
+    "max_log_file_size" => {:set_max_log_file_size, :int},
+    "keep_log_file_num" => {:set_keep_log_file_num, :int},
+    "db_log_dir" => {:set_db_log_dir, :string},
+    "max_background_jobs" => {:set_max_background_jobs, :int},
+    "wal_dir" => {:set_wal_dir, :string},
+    "WAL_ttl_seconds" => {:set_wal_ttl_seconds, :int},
+    "WAL_size_limit_MB" => {:set_wal_size_limit_mb, :int},
+    "max_total_wal_size" => {:set_max_total_wal_size, :int},
+    "atomic_flush" => {:set_atomic_flush, :bool},
+    "paranoid_checks" => {:set_paranoid_checks, :bool},
+    "delete_obsolete_files_period_micros" => {:set_delete_obsolete_files_period_micros, :int}
+  }
+
+  @cf_options %{
+    "max_write_buffer_number" => {:set_max_write_buffer_number, :int},
+    "write_buffer_size" => {:set_write_buffer_size, :int},
+    "target_file_size_base" => {:set_target_file_size_base, :int},
+    "min_write_buffer_number_to_merge" => {:set_min_write_buffer_number_to_merge, :int},
+    "level0_stop_writes_trigger" => {:set_level_zero_stop_writes_trigger, :int},
+    "level0_slowdown_writes_trigger" => {:set_level_zero_slowdown_writes_trigger, :int},
+    "disable_auto_compactions" => {:set_disable_auto_compactions, :bool},
+    "compaction_style" => {:set_compaction_style, :compaction_style},
+    "prefix_extractor" => {:prefix_length, :prefix_extractor},
+    "level_compaction_dynamic_level_bytes" => {:set_level_compaction_dynamic_level_bytes, :bool},
+    "max_bytes_for_level_base" => {:set_max_bytes_for_level_base, :int},
+    "max_bytes_for_level_multiplier" => {:set_max_bytes_for_level_multiplier, :float},
+    "compaction_options_universal" => {:set_universal_compaction_options, :universal},
+    "compaction_options_fifo" => {:set_fifo_compaction_options, :fifo}
+  }
+
+  @universal_options %{
+    "size_ratio" => {:set_size_ratio, :c_int},
+    "min_merge_width" => {:set_min_merge_width, :c_int},
+    "max_merge_width" => {:set_max_merge_width, :c_int},
+    "max_size_amplification_percent" => {:set_max_size_amplification_percent, :c_int},
+    "compression_size_percent" => {:set_compression_size_percent, :c_int},
+    "stop_style" => {:set_stop_style, :stop_style}
+  }
+
+  @fifo_options %{
+    "max_table_files_size" => {:set_max_table_files_size, :int}
+  }
+
+  @types [
+    create_if_missing: :boolean,
+    create_missing_column_families: :boolean,
+    increase_parallelism: :pos_integer,
+    set_max_open_files: :integer,
+    set_use_fsync: :boolean,
+    set_bytes_per_sync: :non_neg_integer,
+    optimize_for_point_lookup: :non_neg_integer,
+    set_table_cache_num_shard_bits: :non_neg_integer,
+    set_max_write_buffer_number: :pos_integer,
+    set_write_buffer_size: :pos_integer,
+    set_target_file_size_base: :pos_integer,
+    set_min_write_buffer_number_to_merge: :pos_integer,
+    set_level_zero_stop_writes_trigger: :pos_integer,
+    set_level_zero_slowdown_writes_trigger: :pos_integer,
+    set_disable_auto_compactions: :boolean,
+    set_compaction_style: {:one_of, [:level, :universal, :fifo]},
+    prefix_length: :pos_integer,
+    enable_statistics: :boolean,
+    event_poll_interval_ms: :pos_integer,
+    event_table_files: :boolean,
+    set_log_level: {:one_of, [:debug, :info, :warn, :error, :fatal, :header]},
+    set_max_log_file_size: :non_neg_integer,
+    set_keep_log_file_num: :pos_integer,
+    set_db_log_dir: :string,
+    set_max_background_jobs: :pos_integer,
+    set_wal_dir: :string,
+    set_wal_ttl_seconds: :non_neg_integer,
+    set_wal_size_limit_mb: :non_neg_integer,
+    set_max_total_wal_size: :non_neg_integer,
+    set_atomic_flush: :boolean,
+    set_paranoid_checks: :boolean,
+    set_ratelimiter: :ratelimiter,
+    set_delete_obsolete_files_period_micros: :non_neg_integer,
+    set_level_compaction_dynamic_level_bytes: :boolean,
+    set_max_bytes_for_level_base: :pos_integer,
+    set_max_bytes_for_level_multiplier: :number,
+    set_universal_compaction_options: :universal,
+    set_fifo_compaction_options: :fifo,
+    column_families: :column_families
+  ]
+
+  @doc """
+  Checks the type of every option that is set, turning keyword lists given
+  for the compaction sub-options into their structs.
+
+  Returns `{:ok, open_opts}` or `{:error, {option, value}}` for the first
+  invalid option.
+
+  ## Examples
+
+      iex> {:ok, opts} = OpenOpts.validate(%OpenOpts{set_universal_compaction_options: [set_size_ratio: 2]})
+      iex> opts.set_universal_compaction_options
+      %Soy.UniversalCompactOpts{set_size_ratio: 2}
+
+      iex> OpenOpts.validate(%OpenOpts{set_max_background_jobs: 0})
+      {:error, {:set_max_background_jobs, 0}}
+
+      iex> OpenOpts.validate(%OpenOpts{set_compaction_style: :tiered})
+      {:error, {:set_compaction_style, :tiered}}
+
+  """
+  def validate(%__MODULE__{} = open_opts) do
+    Enum.reduce_while(@types, {:ok, open_opts}, fn {field, type}, {:ok, acc} ->
+      case check(type, Map.fetch!(acc, field)) do
+        {:ok, value} -> {:cont, {:ok, Map.put(acc, field, value)}}
+        :error -> {:halt, {:error, {field, Map.fetch!(acc, field)}}}
+      end
+    end)
+  end
+
+  @doc """
+  Like `validate/1` but raises an `ArgumentError` for invalid options.
+  """
+  def validate!(%__MODULE__{} = open_opts) do
+    case validate(open_opts) do
+      {:ok, open_opts} ->
+        open_opts
+
+      {:error, {field, value}} ->
+        raise ArgumentError, "invalid value for open option #{inspect(field)}: #{inspect(value)}"
+    end
+  end
+
+  defp check(_type, nil), do: {:ok, nil}
+  defp check(:boolean, v) when is_boolean(v), do: {:ok, v}
+  defp check(:integer, v) when is_integer(v), do: {:ok, v}
+  defp check(:pos_integer, v) when is_integer(v) and v > 0, do: {:ok, v}
+  defp check(:non_neg_integer, v) when is_integer(v) and v >= 0, do: {:ok, v}
+  defp check(:number, v) when is_number(v), do: {:ok, v / 1}
+  defp check(:string, v) when is_binary(v), do: {:ok, v}
+  defp check({:one_of, values}, v), do: if(v in values, do: {:ok, v}, else: :error)
+
+  defp check(:ratelimiter, {rate, refill, fairness} = v)
+       when is_integer(rate) and rate > 0 and is_integer(refill) and refill > 0 and
+              is_integer(fairness) and fairness > 0,
+       do: {:ok, v}
+
+  defp check(:universal, v) when is_list(v), do: check(:universal, Soy.UniversalCompactOpts.new(v))
+  defp check(:universal, %Soy.UniversalCompactOpts{} = v), do: {:ok, v}
+  defp check(:fifo, v) when is_list(v), do: check(:fifo, Soy.FifoCompactOpts.new(v))
+  defp check(:fifo, %Soy.FifoCompactOpts{} = v), do: {:ok, v}
+  defp check(:column_families, cfs) when is_map(cfs) do
+    Enum.reduce_while(cfs, {:ok, %{}}, fn {name, cf_opts}, {:ok, acc} ->
+      cf_opts = if is_struct(cf_opts, __MODULE__), do: cf_opts, else: new(cf_opts)
+
+      case validate(cf_opts) do
+        {:ok, cf_opts} -> {:cont, {:ok, Map.put(acc, name, cf_opts)}}
+        {:error, _} -> {:halt, :error}
+      end
+    end)
+  end
+
+  defp check(_type, _v), do: :error
+
+  @doc """
+  Loads the options RocksDB persisted last for the db at `path`.
+
+  Returns `{:ok, %{file: file, db: db_opts, column_families: cf_opts}}`
+  where `db_opts` is a map of the `Soy.OpenOpts` fields of the db options
+  and `cf_opts` maps every column family name to a map of the
+  `Soy.OpenOpts` fields of its options.
+
+  ## Examples
+
+      iex> path = tmp_dir()
+      iex> db = Soy.open(path, set_max_open_files: 100, set_write_buffer_size: 8_388_608)
+      iex> :ok = Soy.DB.close(db)
+      iex> {:ok, loaded} = OpenOpts.load_latest(path)
+      iex> loaded.db.set_max_open_files
+      100
+      iex> loaded.column_families["default"].set_write_buffer_size
+      8_388_608
+
+  """
+  def load_latest(path) do
+    case Soy.Native.path_load_latest_options(path) do
+      {:ok, %{file: file, db: db, column_families: cfs}} ->
+        # RocksDB persists the db dir as the WAL dir when none was given
+        db = if db["wal_dir"] in [path, Path.expand(path)], do: Map.delete(db, "wal_dir"), else: db
+
+        {:ok,
+         %{
+           file: file,
+           db: convert(db, @db_options),
+           column_families: Map.new(cfs, fn {name, cf} -> {name, convert(cf, @cf_options)} end)
+         }}
+
+      {:error, _} = err ->
+        err
+    end
+  end
+
+  @doc """
+  Builds `Soy.OpenOpts` from options loaded with `load_latest/1`. The db
+  options and the options of the `"default"` column family become the top
+  level fields, the other column families go into `:column_families`.
+  """
+  def from_persisted(%{db: db, column_families: cfs}) do
+    {default, others} = Map.pop(cfs, "default", %{})
+
+    column_families =
+      case Map.new(others, fn {name, cf} -> {name, struct!(__MODULE__, cf)} end) do
+        empty when map_size(empty) == 0 -> nil
+        column_families -> column_families
+      end
+
+    __MODULE__
+    |> struct!(Map.merge(db, default))
+    |> Map.put(:column_families, column_families)
+  end
+
+  defp convert(raw, known) do
+    for {name, value} <- raw, Map.has_key?(known, name), into: %{} do
+      {field, type} = Map.fetch!(known, name)
+      {field, parse(type, value)}
+    end
+  end
+
+  defp parse(:bool, value), do: value == "true"
+  defp parse(:int, value), do: String.to_integer(value)
+  defp parse(:float, value), do: value |> Float.parse() |> elem(0)
+
+  # UINT_MAX and other values beyond a C int mean "unlimited" and are left
+  # to the RocksDB default
+  defp parse(:c_int, value) do
+    case String.to_integer(value) do
+      int when int in -2_147_483_648..2_147_483_647 -> int
+      _ -> nil
+    end
+  end
+  defp parse(:string, ""), do: nil
+  defp parse(:string, value), do: value
+  defp parse(:log_level, "DEBUG_LEVEL"), do: :debug
+  defp parse(:log_level, "INFO_LEVEL"), do: :info
+  defp parse(:log_level, "WARN_LEVEL"), do: :warn
+  defp parse(:log_level, "ERROR_LEVEL"), do: :error
+  defp parse(:log_level, "FATAL_LEVEL"), do: :fatal
+  defp parse(:log_level, "HEADER_LEVEL"), do: :header
+  defp parse(:compaction_style, "kCompactionStyleLevel"), do: :level
+  defp parse(:compaction_style, "kCompactionStyleUniversal"), do: :universal
+  defp parse(:compaction_style, "kCompactionStyleFIFO"), do: :fifo
+  defp parse(:compaction_style, _), do: nil
+  defp parse(:prefix_extractor, "rocksdb.FixedPrefix." <> len), do: String.to_integer(len)
+  defp parse(:prefix_extractor, _), do: nil
+  defp parse(:stop_style, "kCompactionStopStyleSimilarSize"), do: :similar
+  defp parse(:stop_style, "kCompactionStopStyleTotalSize"), do: :total
+  defp parse(:stop_style, _), do: nil
+
+  defp parse(:universal, value) do
+    struct!(Soy.UniversalCompactOpts, convert(parse_nested(value), @universal_options))
+  end
+
+  defp parse(:fifo, value) do
+    struct!(Soy.FifoCompactOpts, convert(parse_nested(value), @fifo_options))
+  end
+
+  # `{key=value;key=value;}`
+  defp parse_nested(value) do
+    value
+    |> String.trim_leading("{")
+    |> String.trim_trailing("}")
+    |> String.split(";", trim: true)
+    |> Map.new(fn pair ->
+      [key, value] = String.split(pair, "=", parts: 2)
+      {key, value}
+    end)
+  end
+
   def new(%__MODULE__{} = open_opts) do
     open_opts
   end
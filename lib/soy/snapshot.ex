@@ -1,14 +1,100 @@
 defmodule Soy.Snapshot do
-  alias Soy.{Iter, DB, Native, Snapshot}
+  alias Soy.{Iter, DB, Dirty, Native, Snapshot}
 
-  def new(db), do: {Snapshot, Native.db_snapshot(DB.to_ref(db))}
+  def new(db) do
+    case Native.db_snapshot(DB.to_ref(db)) do
+      ss_ref when is_reference(ss_ref) -> {Snapshot, ss_ref}
+      {:error, _} = err -> err
+    end
+  end
 
   def to_ref({Snapshot, ss_ref}) when is_reference(ss_ref), do: ss_ref
   def to_ref(ss_ref) when is_reference(ss_ref), do: ss_ref
 
   def fetch(ss, key), do: Native.ss_fetch(to_ref(ss), key)
 
-  def multi_get(ss, keys), do: Native.ss_multi_get(to_ref(ss), keys)
+  def multi_get(ss, keys) do
+    if Dirty.dirty?(:multi_get, keys) do
+      Native.ss_multi_get_dirty(to_ref(ss), keys)
+    else
+      Native.ss_multi_get(to_ref(ss), keys)
+    end
+  end
 
   def iter(ss), do: Iter.new(ss)
+
+  @doc """
+  The sequence number of the last write the snapshot sees. Reading the WAL
+  with `Soy.Wal.updates_since/2` from the snapshot continues right after it.
+
+  RocksDB does not expose the sequence number of a snapshot, so it is read
+  before and after taking the snapshot. If other writes kept landing in
+  between for a few attempts, the number is only a lower bound: the
+  snapshot may also see some writes after it, and reading the WAL from the
+  snapshot can repeat those writes but never misses one.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> :ok = Soy.put(db, "k1", "v1")
+      iex> :ok = Soy.put(db, "k2", "v2")
+      iex> ss = Snapshot.new(db)
+      iex> Snapshot.sequence_number(ss)
+      2
+  """
+  def sequence_number(ss), do: Native.ss_sequence_number(to_ref(ss))
+
+  @doc """
+  Writes the entries of the snapshot into a dump file at `path`, which must
+  not exist yet. The dump can be restored with `Soy.DB.load_dump/3` into a
+  db of any RocksDB version or options.
+
+  Every column family is dumped unless `column_families:` lists the names
+  of some.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> :ok = Soy.put(db, "k1", "v1")
+      iex> ss = Snapshot.new(db)
+      iex> path = Path.join(tmp_dir(), "db.dump")
+      iex> {:ok, info} = Snapshot.dump(ss, path)
+      iex> {info.num_entries, info.column_families}
+      {1, ["default"]}
+  """
+  def dump(ss, path, opts \\ []) do
+    cfs = Keyword.get(opts, :column_families)
+    Native.ss_dump(to_ref(ss), path, cfs)
+  end
+
+  @doc """
+  When the snapshot was taken.
+  """
+  def created_at(ss) do
+    ss
+    |> to_ref()
+    |> Native.ss_created_at()
+    |> DateTime.from_unix!(:millisecond)
+  end
+
+  @doc """
+  Releases the snapshot and the iterators reading it right away instead of
+  when the snapshot is garbage collected. A snapshot keeps RocksDB from
+  discarding the entries it can see, so long-lived processes should release
+  snapshots they are done with.
+
+  Later use of the snapshot, its column families or its iterators returns
+  `{:error, :released}`.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> :ok = Soy.put(db, "k1", "v1")
+      iex> ss = Snapshot.new(db)
+      iex> Snapshot.release(ss)
+      :ok
+      iex> Snapshot.fetch(ss, "k1")
+      {:error, :released}
+  """
+  def release(ss), do: Native.ss_release(to_ref(ss))
 end
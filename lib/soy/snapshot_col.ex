@@ -3,7 +3,7 @@ defmodule Soy.SnapshotCol do
   For dealing with a column family.
   """
 
-  alias Soy.{Iter, Snapshot, SnapshotCol, Native}
+  alias Soy.{Dirty, Iter, Snapshot, SnapshotCol, Native}
 
   def new(ss, name) when is_binary(name) do
     ss
@@ -36,11 +36,15 @@ defmodule Soy.SnapshotCol do
   @doc """
   Gets the binary value of `key` in the `db` at the column family
   with `name`.
+
+  Raises an `ArgumentError` if the snapshot was released or its db closed;
+  `fetch/2` returns the error instead.
   """
   def get(cf, key, default \\ nil) do
     case fetch(cf, key) do
       {:ok, got} -> got
       :error -> default
+      {:error, reason} -> raise ArgumentError, "cannot get #{inspect(key)}: #{inspect(reason)}"
     end
   end
 
@@ -58,7 +62,7 @@ defmodule Soy.SnapshotCol do
   def multi_get(ss_cf, keys) do
     ss_cf_ref = to_ref(ss_cf)
     pairs = Enum.map(keys, fn k when is_binary(k) -> {ss_cf_ref, k} end)
-    Native.ss_cf_multi_get(pairs)
+    do_multi_get(pairs)
   end
 
   @doc """
@@ -66,7 +70,15 @@ defmodule Soy.SnapshotCol do
   """
   def multi_get(pairs) do
     pairs = Enum.map(pairs, fn {ss_cf, k} -> {to_ref(ss_cf), k} end)
-    Native.ss_cf_multi_get(pairs)
+    do_multi_get(pairs)
+  end
+
+  defp do_multi_get(pairs) do
+    if Dirty.dirty?(:multi_get, pairs) do
+      Native.ss_cf_multi_get_dirty(pairs)
+    else
+      Native.ss_cf_multi_get(pairs)
+    end
   end
 
   def iter(ss_cf) do
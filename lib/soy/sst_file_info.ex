@@ -0,0 +1,9 @@
+defmodule Soy.SstFileInfo do
+  defstruct [
+    :path,
+    :file_size,
+    :num_entries,
+    :smallest_key,
+    :largest_key
+  ]
+end
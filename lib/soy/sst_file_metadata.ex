@@ -0,0 +1,17 @@
+defmodule Soy.SstFileMetadata do
+  @moduledoc """
+  An SST file of a `Soy.LevelMetadata`. `being_compacted` is always `nil` as
+  RocksDB does not expose it to Soy.
+  """
+  defstruct [
+    :name,
+    :size,
+    :smallest_key,
+    :largest_key,
+    :smallest_seqno,
+    :largest_seqno,
+    :num_entries,
+    :num_deletions,
+    :being_compacted
+  ]
+end
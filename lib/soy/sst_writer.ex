@@ -0,0 +1,70 @@
+defmodule Soy.SstWriter do
+  @moduledoc """
+  Writes sorted entries into an SST file that can be ingested into a db
+  or column family with `Soy.DB.ingest/3` or `Soy.DBCol.ingest/3`.
+
+  Keys must be added in strictly ascending order. The `Soy.OpenOpts`
+  given to `open/2` should match the options of the db the file will be
+  ingested into (in particular its merge operator).
+
+  ## Examples
+
+      iex> path = Path.join(tmp_dir(), "data.sst")
+      iex> {:ok, w} = SstWriter.open(path)
+      iex> 2 = SstWriter.add(w, [{:put, "a", "1"}, {:put, "b", "2"}])
+      iex> :ok = SstWriter.delete(w, "c")
+      iex> {:ok, info} = SstWriter.finish(w)
+      iex> {info.num_entries, info.smallest_key, info.largest_key}
+      {3, "a", "c"}
+      iex> db = Soy.open(tmp_dir())
+      iex> :ok = Soy.DB.ingest(db, [path])
+      iex> Soy.get(db, "b")
+      "2"
+
+  """
+
+  alias Soy.{Native, OpenOpts, SstWriter}
+
+  @doc """
+  Creates the SST file at `path`.
+  """
+  def open(path, opts \\ []) do
+    case Native.sst_writer_open(path, OpenOpts.new(opts)) do
+      ref when is_reference(ref) -> {:ok, {SstWriter, ref}}
+      {:error, _} = err -> err
+    end
+  end
+
+  @doc """
+  Adds a list of `{:put, key, value}`, `{:merge, key, value}` and
+  `{:delete, key}` entries to the file and returns how many were added.
+  """
+  def add(writer, entries) do
+    Native.sst_writer_add(to_ref(writer), entries)
+  end
+
+  def put(writer, key, val), do: add_one(writer, {:put, key, val})
+
+  def merge(writer, key, val), do: add_one(writer, {:merge, key, val})
+
+  def delete(writer, key), do: add_one(writer, {:delete, key})
+
+  defp add_one(writer, entry) do
+    case add(writer, [entry]) do
+      1 -> :ok
+      {:error, _} = err -> err
+    end
+  end
+
+  @doc """
+  Finishes the file and returns its `Soy.SstFileInfo`.
+
+  The writer cannot be used afterwards.
+  """
+  def finish(writer) do
+    Native.sst_writer_finish(to_ref(writer))
+  end
+
+  def to_ref({SstWriter, ref}) when is_reference(ref), do: ref
+  def to_ref(ref) when is_reference(ref), do: ref
+end
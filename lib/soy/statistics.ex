@@ -0,0 +1,16 @@
+defmodule Soy.Statistics do
+  @moduledoc """
+  RocksDB statistics parsed into maps.
+
+    * `:tickers` - a map of ticker name to its count, e.g.
+      `"rocksdb.block.cache.hit"`, `"rocksdb.bytes.written"`,
+      `"rocksdb.compact.write.bytes"` or `"rocksdb.stall.micros"`.
+    * `:histograms` - a map of histogram name to a map with the
+      `:p50`, `:p95`, `:p99` and `:p100` percentiles and the `:count` and
+      `:sum` of the samples, e.g. `"rocksdb.db.get.micros"`,
+      `"rocksdb.db.write.micros"` or `"rocksdb.db.seek.micros"`.
+
+  """
+  defstruct tickers: %{},
+            histograms: %{}
+end
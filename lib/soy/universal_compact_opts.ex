@@ -0,0 +1,37 @@
+defmodule Soy.UniversalCompactOpts do
+  @moduledoc """
+  A struct matching RocksDB CompactionOptionsUniversal, used for
+  `:set_universal_compaction_options` of `Soy.OpenOpts`.
+
+  Options left as `nil` use the RocksDB defaults.
+
+    * `:set_size_ratio` (default: `1`) - The percentage by which a file may
+      be larger than the sum of the smaller files it is compacted with.
+    * `:set_min_merge_width` (default: `2`) - The minimum number of files in
+      a single compaction run.
+    * `:set_max_merge_width` (default: unlimited) - The maximum number of
+      files in a single compaction run.
+    * `:set_max_size_amplification_percent` (default: `200`) - The extra
+      space, as a percentage of the data size, that triggers a full
+      compaction.
+    * `:set_compression_size_percent` (default: `-1`) - The percentage of the
+      data that is compressed, or `-1` to compress everything as configured.
+    * `:set_stop_style` (default: `:total`) - How files are picked for a
+      compaction run, `:similar` or `:total` size.
+
+  """
+  defstruct set_size_ratio: nil,
+            set_min_merge_width: nil,
+            set_max_merge_width: nil,
+            set_max_size_amplification_percent: nil,
+            set_compression_size_percent: nil,
+            set_stop_style: nil
+
+  def new(opts) when is_list(opts) do
+    struct!(__MODULE__, opts)
+  end
+
+  def new(%__MODULE__{} = opts) do
+    opts
+  end
+end
@@ -0,0 +1,172 @@
+defmodule Soy.Wal do
+  @moduledoc """
+  Reads the batches of writes recorded in the write-ahead log.
+
+  Every batch is a `{sequence_number, writes}` tuple where the writes are:
+
+    * `{:put, cf, key, value}`
+    * `{:merge, cf, key, value}`
+    * `{:delete, cf, key}`
+    * `{:single_delete, cf, key}`
+    * `{:delete_range, cf, from, to}`
+
+  `cf` is the name of the column family that was written.
+
+  A follower db can replicate a primary by applying the raw batches of
+  `next_binary/1` with `apply_batch/3`.
+  """
+
+  alias Soy.{DB, Native, Snapshot, Wal}
+
+  @doc """
+  Reads the WAL of the `db` starting at the batch containing the sequence
+  number `since`, or right after the last write seen by a snapshot.
+
+  Starting from a snapshot allows copying a db with a snapshot scan and then
+  following its WAL without missing a write.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> :ok = Soy.put(db, "k1", "v1")
+      iex> ss = Soy.snapshot(db)
+      iex> :ok = Soy.put(db, "k2", "v2")
+      iex> wal = Wal.updates_since(db, ss)
+      iex> Wal.next(wal)
+      {2, [{:put, "default", "k2", "v2"}]}
+      iex> Wal.next(wal)
+      nil
+  """
+  def updates_since(db, {Snapshot, _} = ss) do
+    wrap(Native.db_get_updates_since(DB.to_ref(db), Snapshot.to_ref(ss)))
+  end
+
+  def updates_since(db, since) when is_integer(since) and since >= 0 do
+    wrap(Native.db_get_updates_since(DB.to_ref(db), since))
+  end
+
+  @doc """
+  Returns the next batch, or `nil` when there is none yet. The iterator
+  continues where it left off once more is written.
+  """
+  def next(wal), do: Native.wal_iter_next_batch(to_ref(wal))
+
+  @doc """
+  Returns up to `n` batches at once, fewer when caught up.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> for k <- ["a", "b", "c"], do: :ok = Soy.put(db, k, k)
+      iex> wal = Wal.updates_since(db, 1)
+      iex> Wal.take(wal, 2)
+      [{1, [{:put, "default", "a", "a"}]}, {2, [{:put, "default", "b", "b"}]}]
+      iex> Wal.take(wal, 2)
+      [{3, [{:put, "default", "c", "c"}]}]
+      iex> Wal.take(wal, 2)
+      []
+  """
+  def take(wal, n) when is_integer(n) and n >= 0, do: Native.wal_iter_take(to_ref(wal), n)
+
+  @doc """
+  Asks for a `{:soy_wal, id, latest_seq}` message once there is a batch
+  the iterator did not return yet, and returns `{:ok, id}`.
+
+  The message is sent once; after `next/1` or `take/2` catch up, the next
+  write notifies again. A consumer can therefore read until caught up and
+  then block in `receive` until the message arrives. Subscribing again
+  replaces the earlier subscription.
+
+  Options:
+
+    * `:pid` - (default: `self()`) the local process to notify.
+    * `:interval` - (default: `50`) how often the latest sequence number is
+      checked, in milliseconds.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> wal = Wal.updates_since(db, 1)
+      iex> {:ok, id} = Wal.subscribe(wal)
+      iex> :ok = Soy.put(db, "k1", "v1")
+      iex> receive do
+      ...>   {:soy_wal, ^id, _latest_seq} -> Wal.take(wal, 10)
+      ...> end
+      [{1, [{:put, "default", "k1", "v1"}]}]
+  """
+  def subscribe(wal, opts \\ []) do
+    pid = Keyword.get(opts, :pid, self())
+    interval = Keyword.get(opts, :interval, 50)
+    id = :erlang.unique_integer([:positive])
+
+    case Native.wal_iter_subscribe(to_ref(wal), pid, id, interval) do
+      :ok -> {:ok, id}
+      {:error, _} = err -> err
+    end
+  end
+
+  @doc """
+  Like `next/1`, but returns the batch as the binary `WriteBatch` RocksDB
+  logged. The binary can be sent to another node and written to a follower
+  with `apply_batch/3`.
+  """
+  def next_binary(wal), do: Native.wal_iter_next_binary(to_ref(wal))
+
+  @doc """
+  Returns the ids the WAL of the `db` gives its column families, by name.
+
+  A batch of `next_binary/1` refers to column families by these ids, so
+  `apply_batch/3` needs the ids of the primary.
+
+  ## Examples
+
+      iex> db = Soy.open(tmp_dir())
+      iex> {:ok, _} = Soy.DBCol.create_new(db, "feet")
+      iex> Wal.cf_ids(db)
+      %{"default" => 0, "feet" => 1}
+  """
+  def cf_ids(db), do: Native.db_wal_cf_ids(DB.to_ref(db))
+
+  @doc """
+  Writes a binary batch of `next_binary/1` to the follower `db` atomically
+  and returns the latest sequence number of the follower afterwards.
+  `primary_cf_ids` are the `cf_ids/1` of the primary.
+
+  The batch must continue right after the latest sequence number of the
+  follower, so that both dbs stay at the same sequence numbers. This holds
+  for a follower that started as a checkpoint of the primary (see
+  `Soy.Checkpoint`), or as an empty db, and is written by nothing else.
+  Otherwise `{:error, {:out_of_order, seq}}` is returned with the sequence
+  number the next batch has to start at. Writes the primary makes without
+  the WAL, such as ingested files, are not replicated.
+
+  The column families the batch writes to must have the same ids in the
+  follower as in the primary, which holds when they were created in the
+  same order. Otherwise `{:error, {:column_family_mismatch, id}}` is
+  returned with the id of the first one that differs, and nothing is
+  written.
+
+  ## Examples
+
+      iex> primary = Soy.open(tmp_dir())
+      iex> follower = Soy.open(tmp_dir())
+      iex> :ok = Soy.put(primary, "k1", "v1")
+      iex> wal = Wal.updates_since(primary, 1)
+      iex> {1, batch} = Wal.next_binary(wal)
+      iex> cf_ids = Wal.cf_ids(primary)
+      iex> Wal.apply_batch(follower, batch, cf_ids)
+      1
+      iex> Soy.get(follower, "k1")
+      "v1"
+      iex> Wal.apply_batch(follower, batch, cf_ids)
+      {:error, {:out_of_order, 2}}
+  """
+  def apply_batch(db, batch, primary_cf_ids) when is_binary(batch) and is_map(primary_cf_ids) do
+    Native.db_apply_batch_binary(DB.to_ref(db), batch, primary_cf_ids)
+  end
+
+  def to_ref({Wal, ref}) when is_reference(ref), do: ref
+
+  defp wrap(ref) when is_reference(ref), do: {Wal, ref}
+  defp wrap({:error, _} = err), do: err
+end
@@ -0,0 +1,94 @@
+use crate::db_col_fam::CfHandle;
+use crate::Error;
+use librocksdb_sys as ffi;
+use rocksdb::{properties as props, AsColumnFamilyRef, DBAccess, DB as RocksDb};
+use rustler::{Binary, NifMap};
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+/// The approximate on-disk size and rough number of keys of a `[start, limit)`
+/// key range.
+#[derive(Debug, NifMap)]
+pub struct RangeSize {
+    size: u64,
+    keys: u64,
+}
+
+/// Measures `ranges` of `cf` (or the default column family).
+///
+/// The C API has no per-range memtable estimate, so only SST files are
+/// sized and unflushed writes are not counted.
+/// The key counts are only rough estimates: `ESTIMATE_NUM_KEYS` scaled by
+/// the range's share of the live SST bytes, which assumes keys are of
+/// roughly even size.
+pub fn range_sizes(
+    db: &RocksDb,
+    cf: Option<&CfHandle>,
+    ranges: &[(Binary, Binary)],
+) -> Result<Vec<RangeSize>, Error> {
+    let sizes = approximate_sizes(db, cf, ranges)?;
+    let int_prop = |prop: &str| {
+        let value = match cf {
+            Some(cf) => db.property_int_value_cf(cf, prop),
+            None => db.property_int_value(prop),
+        };
+        value.map(|v| v.unwrap_or(0))
+    };
+    let num_keys = int_prop(props::ESTIMATE_NUM_KEYS)?;
+    let live_size = int_prop(props::LIVE_SST_FILES_SIZE)?;
+    Ok(sizes
+        .into_iter()
+        .map(|size| {
+            let keys = match live_size {
+                0 => 0,
+                _ => (num_keys as u128 * size.min(live_size) as u128 / live_size as u128) as u64,
+            };
+            RangeSize { size, keys }
+        })
+        .collect())
+}
+
+fn approximate_sizes(
+    db: &RocksDb,
+    cf: Option<&CfHandle>,
+    ranges: &[(Binary, Binary)],
+) -> Result<Vec<u64>, Error> {
+    let starts: Vec<*const c_char> = ranges.iter().map(|r| r.0.as_ptr() as _).collect();
+    let start_lens: Vec<usize> = ranges.iter().map(|r| r.0.len()).collect();
+    let limits: Vec<*const c_char> = ranges.iter().map(|r| r.1.as_ptr() as _).collect();
+    let limit_lens: Vec<usize> = ranges.iter().map(|r| r.1.len()).collect();
+    let mut sizes = vec![0u64; ranges.len()];
+    let mut err: *mut c_char = ptr::null_mut();
+    unsafe {
+        match cf {
+            Some(cf) => ffi::rocksdb_approximate_sizes_cf(
+                db.inner(),
+                cf.inner(),
+                ranges.len() as i32,
+                starts.as_ptr(),
+                start_lens.as_ptr(),
+                limits.as_ptr(),
+                limit_lens.as_ptr(),
+                sizes.as_mut_ptr(),
+                &mut err,
+            ),
+            None => ffi::rocksdb_approximate_sizes(
+                db.inner(),
+                ranges.len() as i32,
+                starts.as_ptr(),
+                start_lens.as_ptr(),
+                limits.as_ptr(),
+                limit_lens.as_ptr(),
+                sizes.as_mut_ptr(),
+                &mut err,
+            ),
+        }
+        if !err.is_null() {
+            let msg = CStr::from_ptr(err).to_string_lossy().into_owned();
+            ffi::rocksdb_free(err as *mut c_void);
+            return Err(Error::RocksDbError(msg));
+        }
+    }
+    Ok(sizes)
+}
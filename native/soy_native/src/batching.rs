@@ -15,6 +15,20 @@ impl PutOp {
     }
 }
 
+#[derive(NifRecord)]
+#[tag = "merge"]
+pub struct MergeOp(Bin, Bin);
+
+impl MergeOp {
+    pub fn key(&self) -> &[u8] {
+        &self.0.as_bytes()
+    }
+
+    pub fn val(&self) -> &[u8] {
+        &self.1.as_bytes()
+    }
+}
+
 #[derive(NifRecord)]
 #[tag = "put_cf"]
 pub struct PutCfOp(SoyDbColFam, Bin, Bin);
@@ -1,4 +1,6 @@
-use rustler::{Binary, Decoder, Encoder, Env, Error as NifError, NifResult, OwnedBinary, Term};
+use rustler::{
+    Binary, Decoder, Encoder, Env, Error as NifError, NifResult, OwnedBinary, ResourceArc, Term,
+};
 use std::ops::Deref;
 use std::str;
 
@@ -6,10 +8,6 @@ use std::str;
 pub struct Bin(Vec<u8>);
 
 impl Bin {
-    pub fn from_vec(v: Vec<u8>) -> Bin {
-        Bin(v)
-    }
-
     // pub fn from_str(s: &str) -> Bin {
     //     Bin(s.as_bytes().to_vec())
     // }
@@ -19,6 +17,12 @@ impl Bin {
     }
 }
 
+impl From<Vec<u8>> for Bin {
+    fn from(bytes: Vec<u8>) -> Bin {
+        Bin(bytes)
+    }
+}
+
 impl<'a> Decoder<'a> for Bin {
     fn decode(term: Term<'a>) -> NifResult<Self> {
         if let Ok(bytes) = term.decode::<Binary>() {
@@ -46,6 +50,24 @@ pub fn new_binary<'a>(val: &[u8], env: Env<'a>) -> Binary<'a> {
     Binary::from_owned(value, env)
 }
 
+/// Values at least this large are handed to the BEAM as resource-backed
+/// binaries instead of being copied into a new `OwnedBinary`.
+pub const ZERO_COPY_MIN_SIZE: usize = 64 * 1024;
+
+/// Owns the bytes behind a resource-backed binary. The bytes are freed
+/// when the last BEAM term referencing the binary is garbage collected.
+pub struct VecResource(Vec<u8>);
+
+/// Turns a `Vec<u8>` returned by RocksDB into a binary. Large values are
+/// not copied; the vec is moved into a `VecResource` and the binary
+/// points directly at its heap allocation.
+pub fn vec_into_binary<'a>(val: Vec<u8>, env: Env<'a>) -> Binary<'a> {
+    if val.len() < ZERO_COPY_MIN_SIZE {
+        return new_binary(&val[..], env);
+    }
+    ResourceArc::new(VecResource(val)).make_binary(env, |r| &r.0[..])
+}
+
 pub struct BinStr<'a>(Binary<'a>);
 
 impl<'a> Decoder<'a> for BinStr<'a> {
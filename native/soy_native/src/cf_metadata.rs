@@ -0,0 +1,135 @@
+use crate::db_col_fam::CfHandle;
+use crate::{Bin, Error};
+use rocksdb::{properties as props, DB as RocksDb};
+use rustler::NifStruct;
+use std::collections::HashMap;
+
+#[derive(Debug, NifStruct)]
+#[module = "Soy.CfMetadata"]
+pub struct SoyCfMetadata {
+    name: String,
+    size: u64,
+    file_count: usize,
+    levels: Vec<SoyLevelMetadata>,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Soy.LevelMetadata"]
+pub struct SoyLevelMetadata {
+    level: i32,
+    size: u64,
+    files: Vec<SoySstFileMetadata>,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Soy.SstFileMetadata"]
+pub struct SoySstFileMetadata {
+    name: String,
+    size: u64,
+    smallest_key: Option<Bin>,
+    largest_key: Option<Bin>,
+    smallest_seqno: Option<u64>,
+    largest_seqno: Option<u64>,
+    num_entries: u64,
+    num_deletions: u64,
+    // not exposed by the C API
+    being_compacted: Option<bool>,
+}
+
+/// The levels of the current version and the `(smallest, largest)` seqnos
+/// of its files by file number, parsed from the `rocksdb.sstables` property:
+///
+/// ```text
+/// --- level 0 --- version# 2 ---
+///  7:1024[1 .. 3]['61' seq:1, type:1 .. '63' seq:3, type:1](0)
+/// ```
+fn parse_sstables(text: &str) -> (Vec<i32>, HashMap<u64, (u64, u64)>) {
+    let mut levels = Vec::new();
+    let mut seqnos = HashMap::new();
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix("--- level ") {
+            if let Some(Ok(level)) = rest.split(' ').next().map(str::parse) {
+                levels.push(level);
+            }
+            continue;
+        }
+        let parsed = (|| {
+            let (number, rest) = line.trim_start().split_once(':')?;
+            let (_size, rest) = rest.split_once('[')?;
+            let (range, _) = rest.split_once(']')?;
+            let (smallest, largest) = range.split_once(" .. ")?;
+            Some((
+                number.parse().ok()?,
+                (smallest.parse().ok()?, largest.parse().ok()?),
+            ))
+        })();
+        if let Some((number, range)) = parsed {
+            seqnos.insert(number, range);
+        }
+    }
+    (levels, seqnos)
+}
+
+/// The file number of a live file name such as `/000007.sst`.
+fn file_number(name: &str) -> Option<u64> {
+    name.trim_start_matches('/')
+        .trim_end_matches(".sst")
+        .parse()
+        .ok()
+}
+
+/// Groups the live files of the column family `name` by level.
+///
+/// The C API has no column family metadata, so it is built from the live
+/// files and the seqnos of the `rocksdb.sstables` property.
+pub fn cf_metadata(db: &RocksDb, cf: &CfHandle, name: &str) -> Result<SoyCfMetadata, Error> {
+    let sstables = db
+        .property_value_cf(cf, props::SSTABLES)?
+        .unwrap_or_default();
+    let (level_nums, seqnos) = parse_sstables(&sstables);
+    let mut levels: Vec<SoyLevelMetadata> = level_nums
+        .into_iter()
+        .map(|level| SoyLevelMetadata {
+            level,
+            size: 0,
+            files: Vec::new(),
+        })
+        .collect();
+    for lf in db.live_files()? {
+        if lf.column_family_name != name {
+            continue;
+        }
+        let seqno_range = file_number(&lf.name).and_then(|n| seqnos.get(&n).copied());
+        let file = SoySstFileMetadata {
+            size: lf.size as u64,
+            smallest_key: lf.start_key.map(Bin::from),
+            largest_key: lf.end_key.map(Bin::from),
+            smallest_seqno: seqno_range.map(|r| r.0),
+            largest_seqno: seqno_range.map(|r| r.1),
+            num_entries: lf.num_entries,
+            num_deletions: lf.num_deletions,
+            being_compacted: None,
+            name: lf.name,
+        };
+        let level = match levels.iter_mut().find(|l| l.level == lf.level) {
+            Some(level) => level,
+            None => {
+                levels.push(SoyLevelMetadata {
+                    level: lf.level,
+                    size: 0,
+                    files: Vec::new(),
+                });
+                levels.last_mut().unwrap()
+            }
+        };
+        level.size += file.size;
+        level.files.push(file);
+    }
+    levels.sort_by_key(|l| l.level);
+    Ok(SoyCfMetadata {
+        name: name.to_string(),
+        size: levels.iter().map(|l| l.size).sum(),
+        file_count: levels.iter().map(|l| l.files.len()).sum(),
+        levels,
+    })
+}
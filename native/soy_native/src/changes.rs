@@ -0,0 +1,125 @@
+use crate::{atoms, new_binary};
+use rustler::{Atom, Encoder, Env, LocalPid};
+use std::sync::{Arc, Mutex, RwLock, Weak};
+
+// the registries of all dbs by subscription id, so that a subscription can
+// be removed without holding on to its db
+static BY_ID: Mutex<Vec<(u64, Weak<Subscriptions>)>> = Mutex::new(Vec::new());
+
+/// A successful write to report to the pids subscribed to its key.
+pub struct Change<'a> {
+    cf: &'a str,
+    op: Atom,
+    key: &'a [u8],
+    value: Option<&'a [u8]>,
+}
+
+impl<'a> Change<'a> {
+    pub fn put(cf: &'a str, key: &'a [u8], value: &'a [u8]) -> Change<'a> {
+        Change {
+            cf,
+            op: atoms::put(),
+            key,
+            value: Some(value),
+        }
+    }
+
+    pub fn merge(cf: &'a str, key: &'a [u8], value: &'a [u8]) -> Change<'a> {
+        Change {
+            cf,
+            op: atoms::merge(),
+            key,
+            value: Some(value),
+        }
+    }
+
+    pub fn delete(cf: &'a str, key: &'a [u8]) -> Change<'a> {
+        Change {
+            cf,
+            op: atoms::delete(),
+            key,
+            value: None,
+        }
+    }
+}
+
+struct Subscription {
+    id: u64,
+    pid: LocalPid,
+    cf: String,
+    prefix: Vec<u8>,
+}
+
+impl Subscription {
+    fn matches(&self, change: &Change) -> bool {
+        self.cf == change.cf && change.key.starts_with(&self.prefix)
+    }
+}
+
+/// The pids subscribed to the writes under a key prefix of a column
+/// family.
+///
+/// The C API has no hook into the write path, so only writes made through
+/// the NIFs of this db are reported, by the NIF that made them.
+#[derive(Default)]
+pub struct Subscriptions(RwLock<Vec<Subscription>>);
+
+impl Subscriptions {
+    /// Subscribes `pid` under an `id` that is unique across dbs.
+    pub fn subscribe(self: &Arc<Self>, id: u64, pid: LocalPid, cf: &str, prefix: &[u8]) {
+        self.0.write().unwrap().push(Subscription {
+            id,
+            pid,
+            cf: cf.to_string(),
+            prefix: prefix.to_vec(),
+        });
+        let mut by_id = BY_ID.lock().unwrap();
+        by_id.retain(|(_, subs)| subs.strong_count() > 0);
+        by_id.push((id, Arc::downgrade(self)));
+    }
+
+    /// Removes the subscription `id` from whichever db it belongs to.
+    pub fn unsubscribe(id: u64) {
+        let subs = {
+            let mut by_id = BY_ID.lock().unwrap();
+            let pos = by_id.iter().position(|(sub_id, _)| *sub_id == id);
+            pos.and_then(|pos| by_id.swap_remove(pos).1.upgrade())
+        };
+        if let Some(subs) = subs {
+            subs.0.write().unwrap().retain(|sub| sub.id != id)
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.read().unwrap().len()
+    }
+
+    pub fn clear(&self) {
+        self.0.write().unwrap().clear()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.read().unwrap().is_empty()
+    }
+
+    /// Sends `{:soy_change, cf, op, key, value}` for every change to the
+    /// pids subscribed to its key. `value` is `nil` for deletes.
+    pub fn notify(&self, env: Env, changes: &[Change]) {
+        let subs = self.0.read().unwrap();
+        if subs.is_empty() {
+            return;
+        }
+        for change in changes {
+            for sub in subs.iter().filter(|sub| sub.matches(change)) {
+                let msg = (
+                    atoms::soy_change(),
+                    change.cf,
+                    change.op,
+                    new_binary(change.key, env),
+                    change.value.map(|v| new_binary(v, env)),
+                );
+                env.send(&sub.pid, msg.encode(env));
+            }
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use crate::crc::{crc32, Crc32};
+use crate::{atoms, new_binary, Error, SoyDb};
+use rocksdb::checkpoint::Checkpoint;
+use rustler::{Encoder, Env, Term};
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// A part of a checkpoint stream: a chunk of a file, or the list of all
+/// files as `(name, size, crc)` once every chunk was read.
+pub enum StreamItem {
+    Chunk {
+        name: String,
+        offset: u64,
+        crc: u32,
+        data: Vec<u8>,
+    },
+    Done(Vec<(String, u64, u32)>),
+}
+
+impl Encoder for StreamItem {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        match self {
+            StreamItem::Chunk {
+                name,
+                offset,
+                crc,
+                data,
+            } => (atoms::chunk(), name, offset, crc, new_binary(data, env)).encode(env),
+            StreamItem::Done(files) => (atoms::done(), files).encode(env),
+        }
+    }
+}
+
+struct OpenFile {
+    name: String,
+    file: File,
+    offset: u64,
+    crc: Crc32,
+}
+
+struct StreamState {
+    // the files left to read, last one first
+    names: Vec<String>,
+    current: Option<OpenFile>,
+    files: Vec<(String, u64, u32)>,
+    done: bool,
+}
+
+/// A checkpoint of a db created next to it, read a chunk at a time so the
+/// receiver sets the pace.
+///
+/// The checkpoint is removed once the stream is done or dropped.
+pub struct CheckpointStream {
+    dir: PathBuf,
+    chunk_size: usize,
+    state: Mutex<StreamState>,
+}
+
+impl CheckpointStream {
+    pub fn new(db: &SoyDb, id: u64, chunk_size: usize) -> Result<CheckpointStream, Error> {
+        let rdb = db.rocks_db_ref()?;
+        let db_name = rdb.path().file_name().unwrap_or_default().to_string_lossy();
+        let dir = rdb
+            .path()
+            .with_file_name(format!("{}.checkpoint-{}", db_name, id));
+        Checkpoint::new(&rdb)?.create_checkpoint(&dir)?;
+        drop(rdb);
+        let stream = CheckpointStream {
+            dir,
+            chunk_size: chunk_size.max(1),
+            state: Mutex::new(StreamState {
+                names: Vec::new(),
+                current: None,
+                files: Vec::new(),
+                done: false,
+            }),
+        };
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&stream.dir)? {
+            names.push(entry?.file_name().to_string_lossy().into_owned());
+        }
+        names.sort_by(|a, b| b.cmp(a));
+        stream.state.lock().unwrap().names = names;
+        Ok(stream)
+    }
+
+    /// Reads the next chunk, or returns the files once all chunks were
+    /// read. Returns `None` after that.
+    pub fn next(&self) -> Result<Option<StreamItem>, Error> {
+        let mut guard = self.state.lock().unwrap();
+        let state = &mut *guard;
+        if state.done {
+            return Ok(None);
+        }
+        loop {
+            if state.current.is_none() {
+                let name = match state.names.pop() {
+                    Some(name) => name,
+                    None => {
+                        state.done = true;
+                        let _ = fs::remove_dir_all(&self.dir);
+                        return Ok(Some(StreamItem::Done(state.files.clone())));
+                    }
+                };
+                state.current = Some(OpenFile {
+                    file: File::open(self.dir.join(&name))?,
+                    name,
+                    offset: 0,
+                    crc: Crc32::default(),
+                });
+            }
+            let current = state.current.as_mut().unwrap();
+            let mut data = vec![0u8; self.chunk_size];
+            let len = current.file.read(&mut data)?;
+            if len == 0 {
+                let file = (current.name.clone(), current.offset, current.crc.sum());
+                state.files.push(file);
+                state.current = None;
+                continue;
+            }
+            data.truncate(len);
+            current.crc.update(&data);
+            let offset = current.offset;
+            current.offset += len as u64;
+            return Ok(Some(StreamItem::Chunk {
+                name: current.name.clone(),
+                offset,
+                crc: crc32(&data),
+                data,
+            }));
+        }
+    }
+}
+
+impl Drop for CheckpointStream {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Checkpoint files are flat, so names must not leave the dir.
+fn file_path(dir: &str, name: &str) -> Result<PathBuf, Error> {
+    if name.is_empty() || name == "." || name == ".." || name.contains(&['/', '\\'][..]) {
+        return Err(Error::InvalidCheckpoint(format!(
+            "bad file name {:?}",
+            name
+        )));
+    }
+    Ok(Path::new(dir).join(name))
+}
+
+/// Writes a chunk received from `stream` into `dir` after checking its crc.
+pub fn write_chunk(dir: &str, name: &str, offset: u64, crc: u32, data: &[u8]) -> Result<(), Error> {
+    if crc32(data) != crc {
+        return Err(Error::InvalidCheckpoint(format!(
+            "checksum mismatch in {} at {}",
+            name, offset
+        )));
+    }
+    let path = file_path(dir, name)?;
+    fs::create_dir_all(dir)?;
+    let mut file = OpenOptions::new().create(true).write(true).open(path)?;
+    file.seek(SeekFrom::Start(offset))?;
+    file.write_all(data)?;
+    Ok(())
+}
+
+/// Checks that `dir` holds the `(name, size, crc)` files of a finished
+/// stream and syncs them, after which the dir can be opened as a db.
+pub fn finish(dir: &str, files: &[(String, u64, u32)]) -> Result<(), Error> {
+    fs::create_dir_all(dir)?;
+    for (name, size, crc) in files {
+        let path = file_path(dir, name)?;
+        // empty files are never sent as chunks
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .write(true)
+            .open(&path)?;
+        let mut actual_crc = Crc32::default();
+        let mut actual_size = 0u64;
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let len = file.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            actual_crc.update(&buf[..len]);
+            actual_size += len as u64;
+        }
+        if actual_size != *size || actual_crc.sum() != *crc {
+            return Err(Error::InvalidCheckpoint(format!("{} is incomplete", name)));
+        }
+        file.sync_all()?;
+    }
+    File::open(dir)?.sync_all()?;
+    Ok(())
+}
@@ -1,35 +1,70 @@
+use crate::release::Slot;
+use crate::soy_db::DbRef;
 use crate::{Error, SoyDb};
-use rocksdb::{ColumnFamilyRef, DB as RocksDb};
+use librocksdb_sys as ffi;
+use rocksdb::{AsColumnFamilyRef, ColumnFamilyRef, DB as RocksDb};
 use rustler::ResourceArc;
+use std::sync::Arc;
 
 pub type SoyDbColFam = ResourceArc<DbColFamResource>;
 
+/// A `ColumnFamilyRef` with its lifetime extended to `'static`.
+///
+/// Dropping a handle after its db is closed or its column family is dropped
+/// is a use-after-free in RocksDB, so handles live in a `Slot` that is
+/// registered with the db. Clones taken out of the slot must only be used,
+/// and dropped, while the db is locked.
+#[derive(Clone)]
+pub struct CfHandle(ColumnFamilyRef<'static>);
+
+unsafe impl Send for CfHandle {}
+unsafe impl Sync for CfHandle {}
+
+impl CfHandle {
+    pub fn open(db: &SoyDb, rdb: &DbRef, name: &str) -> Result<Arc<Slot<CfHandle>>, Error> {
+        let cf_ref = get_cf_handle(rdb, name)?;
+        let slot = Slot::new(CfHandle(unsafe { extend_lifetime_cf(cf_ref) }));
+        db.register_cf(name, &slot);
+        Ok(slot)
+    }
+}
+
+impl AsColumnFamilyRef for CfHandle {
+    fn inner(&self) -> *mut ffi::rocksdb_column_family_handle_t {
+        self.0.inner()
+    }
+}
+
 pub struct DbColFamResource {
     db: SoyDb,
     cf_name: String,
-    cf_ref: ColumnFamilyRef<'static>,
+    cf_ref: Arc<Slot<CfHandle>>,
 }
 
-unsafe impl Send for DbColFamResource {}
-unsafe impl Sync for DbColFamResource {}
-
 impl DbColFamResource {
     pub fn new(db: &SoyDb, name: &str) -> Result<DbColFamResource, Error> {
-        let db_ref = db.rocks_db_ref();
-        let cf_ref = get_cf_handle(db_ref, name)?;
+        let rdb = db.rocks_db_ref()?;
+        let cf_ref = CfHandle::open(db, &rdb, name)?;
         Ok(DbColFamResource {
             db: db.clone(),
             cf_name: name.to_owned(),
-            cf_ref: unsafe { extend_lifetime_cf(cf_ref) },
+            cf_ref,
         })
     }
 
-    pub fn handle<'a>(&'a self) -> &'a ColumnFamilyRef<'a> {
-        unsafe { unextend_lifetime_cf(&self.cf_ref) }
+    pub fn handle(&self) -> Result<CfHandle, Error> {
+        self.cf_ref.read().map(|h| h.clone())
+    }
+
+    pub fn rocks_db_ref(&self) -> Result<DbRef, Error> {
+        self.db.rocks_db_ref()
     }
 
-    pub fn rocks_db_ref(&self) -> &RocksDb {
-        &self.db.rocks_db_ref()
+    /// Read-locks the db and takes the cf handle.
+    pub fn lock(&self) -> Result<(DbRef, CfHandle), Error> {
+        let rdb = self.rocks_db_ref()?;
+        let handle = self.handle()?;
+        Ok((rdb, handle))
     }
 
     pub fn soy_db(&self) -> &SoyDb {
@@ -54,7 +89,3 @@ pub(crate) fn get_cf_handle<'a>(
 unsafe fn extend_lifetime_cf<'b>(s: ColumnFamilyRef<'b>) -> ColumnFamilyRef<'static> {
     std::mem::transmute::<ColumnFamilyRef<'b>, ColumnFamilyRef<'static>>(s)
 }
-
-unsafe fn unextend_lifetime_cf<'b>(r: &ColumnFamilyRef<'static>) -> &'b ColumnFamilyRef<'b> {
-    std::mem::transmute::<&ColumnFamilyRef<'static>, &ColumnFamilyRef<'b>>(r)
-}
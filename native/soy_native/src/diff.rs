@@ -0,0 +1,170 @@
+use crate::iteration::{IterLocker, IterResource, SafeIter};
+use crate::{
+    atoms, new_binary, Bin, DbColFamResource, Error, SoyDb, SoyIter, SoySnapshot, SsColFamResource,
+};
+use rustler::{Encoder, Env, NifUntaggedEnum, ResourceArc, Term};
+use std::cmp::Ordering;
+use std::sync::Mutex;
+
+// keys visited per call, so that long runs of equal keys return early
+const MAX_STEPS: usize = 10_000;
+
+/// What a diff compares: a snapshot, or the db as of the diff's creation.
+#[derive(NifUntaggedEnum)]
+pub enum DiffSide {
+    Snapshot(SoySnapshot),
+    Db(SoyDb),
+}
+
+impl DiffSide {
+    fn iter(self, cf: Option<&str>) -> Result<SoyIter, Error> {
+        match (self, cf) {
+            (DiffSide::Snapshot(ss), None) => IterResource::from_ss(ss),
+            (DiffSide::Snapshot(ss), Some(name)) => {
+                let ss_cf = SsColFamResource::new(&ss, name)?;
+                IterResource::from_ss_cf(ResourceArc::new(ss_cf))
+            }
+            (DiffSide::Db(db), None) => IterResource::from_db(db),
+            (DiffSide::Db(db), Some(name)) => {
+                let db_cf = DbColFamResource::new(&db, name)?;
+                IterResource::from_db_cf(ResourceArc::new(db_cf))
+            }
+        }
+    }
+}
+
+pub enum DiffEntry {
+    Added(Vec<u8>, Vec<u8>),
+    Removed(Vec<u8>, Vec<u8>),
+    Changed(Vec<u8>, Vec<u8>, Vec<u8>),
+}
+
+impl Encoder for DiffEntry {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let bin = |b: &[u8]| new_binary(b, env);
+        match self {
+            DiffEntry::Added(key, value) => (atoms::added(), bin(key), bin(value)).encode(env),
+            DiffEntry::Removed(key, value) => (atoms::removed(), bin(key), bin(value)).encode(env),
+            DiffEntry::Changed(key, old, new) => {
+                (atoms::changed(), bin(key), bin(old), bin(new)).encode(env)
+            }
+        }
+    }
+}
+
+enum Step {
+    Old,
+    New,
+    Both,
+}
+
+/// Walks two iterators over the same key range in lockstep and returns
+/// the keys that were added, removed or changed from the old to the new
+/// side, a chunk at a time.
+pub struct SnapshotDiff {
+    old: SoyIter,
+    new: SoyIter,
+    from: Option<Bin>,
+    to: Option<Bin>,
+    started: Mutex<bool>,
+}
+
+impl SnapshotDiff {
+    /// Diffs the keys in `[from, to)` of the column family `cf`, or of the
+    /// default one.
+    pub fn new(
+        old: DiffSide,
+        new: DiffSide,
+        cf: Option<&str>,
+        from: Option<Bin>,
+        to: Option<Bin>,
+    ) -> Result<SnapshotDiff, Error> {
+        Ok(SnapshotDiff {
+            old: old.iter(cf)?,
+            new: new.iter(cf)?,
+            from,
+            to,
+            started: Mutex::new(false),
+        })
+    }
+
+    /// Returns up to `max` entries in key order, or `None` once the diff is
+    /// done. A chunk can be empty when many equal keys were skipped.
+    pub fn next_chunk(&self, max: usize) -> Result<Option<Vec<DiffEntry>>, Error> {
+        let mut started = self.started.lock().unwrap();
+        let mut old = self.old.lock().write()?;
+        let mut new = self.new.lock().write()?;
+        if !*started {
+            self.seek_start(&mut old);
+            self.seek_start(&mut new);
+            *started = true;
+        }
+        let mut entries = Vec::new();
+        let mut steps = 0;
+        while entries.len() < max && steps < MAX_STEPS {
+            steps += 1;
+            let step = match (self.bounded(&old)?, self.bounded(&new)?) {
+                (None, None) if entries.is_empty() => return Ok(None),
+                (None, None) => break,
+                (Some((k, v)), None) => {
+                    entries.push(DiffEntry::Removed(k.to_vec(), v.to_vec()));
+                    Step::Old
+                }
+                (None, Some((k, v))) => {
+                    entries.push(DiffEntry::Added(k.to_vec(), v.to_vec()));
+                    Step::New
+                }
+                (Some((ok, ov)), Some((nk, nv))) => match ok.cmp(nk) {
+                    Ordering::Less => {
+                        entries.push(DiffEntry::Removed(ok.to_vec(), ov.to_vec()));
+                        Step::Old
+                    }
+                    Ordering::Greater => {
+                        entries.push(DiffEntry::Added(nk.to_vec(), nv.to_vec()));
+                        Step::New
+                    }
+                    Ordering::Equal => {
+                        if ov != nv {
+                            let entry = DiffEntry::Changed(ok.to_vec(), ov.to_vec(), nv.to_vec());
+                            entries.push(entry);
+                        }
+                        Step::Both
+                    }
+                },
+            };
+            match step {
+                Step::Old => old.next(),
+                Step::New => new.next(),
+                Step::Both => {
+                    old.next();
+                    new.next();
+                }
+            }
+        }
+        Ok(Some(entries))
+    }
+
+    fn seek_start(&self, it: &mut SafeIter) {
+        match &self.from {
+            Some(from) => it.seek(from.as_bytes()),
+            None => it.seek_to_first(),
+        }
+    }
+
+    /// The current key and value of `it` unless it is past the range. An
+    /// iterator that stopped on an error returns the error, so a failed
+    /// read never looks like the end of the diff.
+    fn bounded<'a>(&self, it: &'a SafeIter) -> Result<Option<(&'a [u8], &'a [u8])>, Error> {
+        let (key, value) = match it.key_value() {
+            Some(key_value) => key_value,
+            None => {
+                it.status()?;
+                return Ok(None);
+            }
+        };
+        match &self.to {
+            Some(to) if key >= to.as_bytes() => Ok(None),
+            _ => Ok(Some((key, value))),
+        }
+    }
+}
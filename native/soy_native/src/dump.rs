@@ -0,0 +1,436 @@
+//! A dump holds the entries of a snapshot independent of the RocksDB
+//! version and options that wrote them:
+//!
+//! ```text
+//! header: "SOYDUMP\0" version:u32
+//! block:  len:u32 crc32:u32 payload[len]
+//! ```
+//!
+//! The payload of a block is a run of records, with lengths as varints:
+//!
+//! ```text
+//! 0x01 name_len name                   the column family of what follows
+//! 0x02 key_len key value_len value     an entry
+//! 0x03 seq:u64 num_entries:u64         the end, in the last block
+//! ```
+//!
+//! Integers are little-endian and checksums cover the payload only.
+
+use crate::crc::crc32;
+use crate::sst_writer::SstFile;
+use crate::{Error, SoyDb, SoySnapshot};
+use rocksdb::{
+    IngestExternalFileOptions, Options, WriteBatch, DB as RocksDb, DEFAULT_COLUMN_FAMILY_NAME,
+};
+use rustler::{NifStruct, NifUnitEnum};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::PathBuf;
+
+const MAGIC: &[u8; 8] = b"SOYDUMP\0";
+const VERSION: u32 = 1;
+/// Blocks are written out once their payload reaches this size.
+const BLOCK_SIZE: usize = 1024 * 1024;
+/// Ingested sst files are rolled over once they reach this size.
+const LOAD_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+const TAG_CF: u8 = 0x01;
+const TAG_ENTRY: u8 = 0x02;
+const TAG_END: u8 = 0x03;
+
+#[derive(Debug, NifStruct)]
+#[module = "Soy.DumpInfo"]
+pub struct SoyDumpInfo {
+    path: String,
+    sequence_number: u64,
+    num_entries: u64,
+    column_families: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, NifUnitEnum)]
+pub enum LoadMode {
+    Batch,
+    Ingest,
+}
+
+fn invalid(msg: &str) -> Error {
+    Error::InvalidDump(msg.to_string())
+}
+
+fn put_varint(buf: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        buf.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    buf.push(n as u8);
+}
+
+fn put_slice(buf: &mut Vec<u8>, s: &[u8]) {
+    put_varint(buf, s.len());
+    buf.extend_from_slice(s);
+}
+
+struct DumpWriter {
+    out: BufWriter<File>,
+    block: Vec<u8>,
+}
+
+impl DumpWriter {
+    fn create(path: &str) -> Result<DumpWriter, Error> {
+        let file = OpenOptions::new().write(true).create_new(true).open(path)?;
+        let mut out = BufWriter::new(file);
+        out.write_all(MAGIC)?;
+        out.write_all(&VERSION.to_le_bytes())?;
+        Ok(DumpWriter {
+            out,
+            block: Vec::with_capacity(BLOCK_SIZE),
+        })
+    }
+
+    fn cf(&mut self, name: &str) -> Result<(), Error> {
+        self.block.push(TAG_CF);
+        put_slice(&mut self.block, name.as_bytes());
+        self.maybe_flush()
+    }
+
+    fn entry(&mut self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        self.block.push(TAG_ENTRY);
+        put_slice(&mut self.block, key);
+        put_slice(&mut self.block, value);
+        self.maybe_flush()
+    }
+
+    fn maybe_flush(&mut self) -> Result<(), Error> {
+        if self.block.len() >= BLOCK_SIZE {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> Result<(), Error> {
+        self.out
+            .write_all(&(self.block.len() as u32).to_le_bytes())?;
+        self.out.write_all(&crc32(&self.block).to_le_bytes())?;
+        self.out.write_all(&self.block)?;
+        self.block.clear();
+        Ok(())
+    }
+
+    fn finish(mut self, seq: u64, num_entries: u64) -> Result<(), Error> {
+        self.block.push(TAG_END);
+        self.block.extend_from_slice(&seq.to_le_bytes());
+        self.block.extend_from_slice(&num_entries.to_le_bytes());
+        self.flush_block()?;
+        self.out.flush()?;
+        self.out.get_ref().sync_all()?;
+        Ok(())
+    }
+}
+
+/// Writes the entries of the snapshot into a dump at `path`, which must
+/// not exist yet. Dumps every column family unless `cfs` names some.
+pub fn dump(ss: &SoySnapshot, path: &str, cfs: Option<Vec<String>>) -> Result<SoyDumpInfo, Error> {
+    let rdb = ss.soy_db().rocks_db_ref()?;
+    let rss = ss.rocks_ss_ref()?;
+    let names = match cfs {
+        Some(names) => names,
+        None => RocksDb::list_cf(&Options::default(), rdb.path())?,
+    };
+    let mut writer = DumpWriter::create(path)?;
+    let written = (|| {
+        let mut num_entries = 0;
+        for name in names.iter() {
+            let mut it = match &name[..] {
+                DEFAULT_COLUMN_FAMILY_NAME => rss.raw_iterator(),
+                _ => {
+                    let cf = rdb
+                        .cf_handle(name)
+                        .ok_or_else(|| Error::ColumnFamilyDoesNotExist(name.to_string()))?;
+                    rss.raw_iterator_cf(&cf)
+                }
+            };
+            writer.cf(name)?;
+            it.seek_to_first();
+            while let (Some(key), Some(value)) = (it.key(), it.value()) {
+                writer.entry(key, value)?;
+                num_entries += 1;
+                it.next();
+            }
+            it.status()?;
+        }
+        writer.finish(ss.sequence_number(), num_entries)?;
+        Ok(num_entries)
+    })();
+    let num_entries = match written {
+        Ok(num_entries) => num_entries,
+        Err(e) => {
+            let _ = fs::remove_file(path);
+            return Err(e);
+        }
+    };
+    Ok(SoyDumpInfo {
+        path: path.to_string(),
+        sequence_number: ss.sequence_number(),
+        num_entries,
+        column_families: names,
+    })
+}
+
+enum Record<'a> {
+    Cf(&'a str),
+    Entry(&'a [u8], &'a [u8]),
+    End(u64, u64),
+}
+
+struct BlockReader<'a>(&'a [u8]);
+
+impl<'a> BlockReader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        if self.0.len() < len {
+            return Err(invalid("truncated record"));
+        }
+        let (taken, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Ok(taken)
+    }
+
+    fn varint(&mut self) -> Result<usize, Error> {
+        let mut n = 0usize;
+        for shift in (0..64).step_by(7) {
+            let b = self.take(1)?[0];
+            n |= ((b & 0x7f) as usize) << shift;
+            if b & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(invalid("bad varint"))
+    }
+
+    fn slice(&mut self) -> Result<&'a [u8], Error> {
+        let len = self.varint()?;
+        self.take(len)
+    }
+
+    fn u64(&mut self) -> Result<u64, Error> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn record(&mut self) -> Result<Option<Record<'a>>, Error> {
+        let tag = match self.0.first() {
+            Some(tag) => *tag,
+            None => return Ok(None),
+        };
+        self.0 = &self.0[1..];
+        let record = match tag {
+            TAG_CF => {
+                let name = std::str::from_utf8(self.slice()?)
+                    .map_err(|_| invalid("column family name is not utf-8"))?;
+                Record::Cf(name)
+            }
+            TAG_ENTRY => Record::Entry(self.slice()?, self.slice()?),
+            TAG_END => Record::End(self.u64()?, self.u64()?),
+            _ => return Err(invalid("unknown record")),
+        };
+        Ok(Some(record))
+    }
+}
+
+/// Reads the next block into `block`. Returns `false` at the end of the file.
+fn read_block(input: &mut impl Read, block: &mut Vec<u8>) -> Result<bool, Error> {
+    let mut header = [0u8; 8];
+    match input.read_exact(&mut header) {
+        Ok(()) => (),
+        Err(e) if e.kind() == ErrorKind::UnexpectedEof => return Ok(false),
+        Err(e) => return Err(e.into()),
+    }
+    let len = u32::from_le_bytes([header[0], header[1], header[2], header[3]]) as usize;
+    let crc = u32::from_le_bytes([header[4], header[5], header[6], header[7]]);
+    block.resize(len, 0);
+    input
+        .read_exact(block)
+        .map_err(|_| invalid("truncated block"))?;
+    if crc32(block) != crc {
+        return Err(invalid("checksum mismatch"));
+    }
+    Ok(true)
+}
+
+/// Where the entries of a column family are loaded into.
+enum Sink {
+    Batch(WriteBatch),
+    Ingest {
+        dir: PathBuf,
+        files: Vec<PathBuf>,
+        file: Option<SstFile>,
+    },
+}
+
+struct Loader<'a> {
+    db: &'a SoyDb,
+    rdb: &'a RocksDb,
+    opts: &'a Options,
+    mode: LoadMode,
+    tmp_dir: PathBuf,
+    num_cfs: usize,
+    cf: Option<String>,
+    sink: Option<Sink>,
+}
+
+impl Loader<'_> {
+    /// Switches to the column family `name`, creating it if needed.
+    fn start_cf(&mut self, name: &str) -> Result<(), Error> {
+        self.finish_cf()?;
+        if name != DEFAULT_COLUMN_FAMILY_NAME && self.rdb.cf_handle(name).is_none() {
+            self.db.create_cf(self.rdb, name, self.opts)?;
+        }
+        self.cf = Some(name.to_string());
+        self.num_cfs += 1;
+        self.sink = Some(match self.mode {
+            LoadMode::Batch => Sink::Batch(WriteBatch::default()),
+            LoadMode::Ingest => Sink::Ingest {
+                dir: self.tmp_dir.join(format!("{:06}", self.num_cfs)),
+                files: Vec::new(),
+                file: None,
+            },
+        });
+        Ok(())
+    }
+
+    fn entry(&mut self, key: &[u8], value: &[u8]) -> Result<(), Error> {
+        let cf = self
+            .cf
+            .as_deref()
+            .ok_or_else(|| invalid("entry before column family"))?;
+        match self.sink.as_mut().unwrap() {
+            Sink::Batch(batch) => match cf {
+                DEFAULT_COLUMN_FAMILY_NAME => batch.put(key, value),
+                _ => batch.put_cf(&self.rdb.cf_handle(cf).unwrap(), key, value),
+            },
+            Sink::Ingest { dir, files, file } => {
+                if file.is_none() {
+                    fs::create_dir_all(&dir)?;
+                    let path = dir.join(format!("{:06}.sst", files.len() + 1));
+                    let path_str = path.to_string_lossy().into_owned();
+                    *file = Some(SstFile::open(&path_str, self.opts.clone())?);
+                    files.push(path);
+                }
+                let sst = file.as_mut().unwrap();
+                sst.put(key, value)?;
+                if sst.file_size() >= LOAD_FILE_SIZE {
+                    let _ = file.take().unwrap().finish()?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes what was collected so far; batches are written per block.
+    fn flush(&mut self) -> Result<(), Error> {
+        if let Some(Sink::Batch(batch)) = self.sink.as_mut() {
+            if !batch.is_empty() {
+                self.rdb.write(std::mem::take(batch))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish_cf(&mut self) -> Result<(), Error> {
+        self.flush()?;
+        let (name, dir, files, file) = match (self.cf.take(), self.sink.take()) {
+            (Some(name), Some(Sink::Ingest { dir, files, file })) => (name, dir, files, file),
+            _ => return Ok(()),
+        };
+        if let Some(file) = file {
+            let _ = file.finish()?;
+        }
+        if !files.is_empty() {
+            let mut ingest_opts = IngestExternalFileOptions::default();
+            ingest_opts.set_move_files(true);
+            match &name[..] {
+                DEFAULT_COLUMN_FAMILY_NAME => {
+                    self.rdb.ingest_external_file_opts(&ingest_opts, files)?
+                }
+                _ => {
+                    let cf = self.rdb.cf_handle(&name).unwrap();
+                    self.rdb
+                        .ingest_external_file_cf_opts(&cf, &ingest_opts, files)?
+                }
+            }
+        }
+        let _ = fs::remove_dir_all(dir);
+        Ok(())
+    }
+}
+
+/// Restores a dump into `db`, creating missing column families with
+/// `opts`. Entries already in the db are overwritten.
+///
+/// In batch mode each block of the dump is written as one batch after its
+/// checksum was verified. In ingest mode the entries of every column family
+/// are written into sst files that are ingested once the column family is
+/// complete.
+pub fn load(db: &SoyDb, path: &str, mode: LoadMode, opts: &Options) -> Result<SoyDumpInfo, Error> {
+    let mut input = BufReader::new(File::open(path)?);
+    let mut header = [0u8; 12];
+    input
+        .read_exact(&mut header)
+        .map_err(|_| invalid("not a dump"))?;
+    if &header[..8] != MAGIC {
+        return Err(invalid("not a dump"));
+    }
+    if header[8..] != VERSION.to_le_bytes() {
+        return Err(invalid("unsupported version"));
+    }
+
+    let rdb = db.rocks_db_ref()?;
+    let tmp_dir = PathBuf::from(format!("{}.load", path));
+    let mut loader = Loader {
+        db,
+        rdb: &rdb,
+        opts,
+        mode,
+        tmp_dir: tmp_dir.clone(),
+        num_cfs: 0,
+        cf: None,
+        sink: None,
+    };
+    let loaded = (|| {
+        let mut block = Vec::new();
+        let mut column_families = Vec::new();
+        let mut num_entries = 0;
+        while read_block(&mut input, &mut block)? {
+            let mut reader = BlockReader(&block);
+            while let Some(record) = reader.record()? {
+                match record {
+                    Record::Cf(name) => {
+                        loader.start_cf(name)?;
+                        column_families.push(name.to_string());
+                    }
+                    Record::Entry(key, value) => {
+                        loader.entry(key, value)?;
+                        num_entries += 1;
+                    }
+                    Record::End(seq, expected) => {
+                        if expected != num_entries {
+                            return Err(invalid("entry count mismatch"));
+                        }
+                        loader.finish_cf()?;
+                        return Ok((seq, num_entries, column_families));
+                    }
+                }
+            }
+            loader.flush()?;
+        }
+        Err(invalid("truncated dump"))
+    })();
+    let _ = fs::remove_dir_all(&tmp_dir);
+    let (sequence_number, num_entries, column_families) = loaded?;
+    Ok(SoyDumpInfo {
+        path: path.to_string(),
+        sequence_number,
+        num_entries,
+        column_families,
+    })
+}
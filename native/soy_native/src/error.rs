@@ -1,27 +1,99 @@
+use crate::atoms;
 use rustler::{Encoder, Env, Error as RustlerError, Term};
 use thiserror::Error as ThisError;
 
-#[derive(ThisError, Debug)]
+#[derive(ThisError, Debug, Clone)]
 pub enum Error {
     #[error("column family does not exist: {}", _0)]
     ColumnFamilyDoesNotExist(String),
 
     #[error("failed to create wal iterator: {}", _0)]
     WalIteratorCreationError(String),
+
+    #[error("db is closed")]
+    Closed,
+
+    #[error("resource was released")]
+    Released,
+
+    #[error("column family was dropped: {}", _0)]
+    ColumnFamilyDropped(String),
+
+    #[error("batch out of order, expected sequence number {}", _0)]
+    OutOfOrderBatch(u64),
+
+    #[error("column family {} of the batch differs from the primary's", _0)]
+    ColumnFamilyMismatch(u32),
+
+    #[error("sst writer is already finished")]
+    SstWriterFinished,
+
+    #[error("export directory already exists: {}", _0)]
+    ExportDirExists(String),
+
+    #[error("{}", _0)]
+    Io(String),
+
+    #[error("invalid dump: {}", _0)]
+    InvalidDump(String),
+
+    #[error("invalid checkpoint: {}", _0)]
+    InvalidCheckpoint(String),
+
+    #[error("no OPTIONS file found in: {}", _0)]
+    OptionsFileNotFound(String),
+
+    #[error("unknown option: {}", _0)]
+    UnknownOption(String),
+
+    #[error("option cannot be changed on an open db: {}", _0)]
+    ImmutableOption(String),
+
+    #[error("invalid value for option: {}", _0)]
+    InvalidOptionValue(String),
+
+    #[error("not a column family option: {}", _0)]
+    NotCfOption(String),
+
+    #[error("{}", _0)]
+    RocksDbError(String),
     // #[error("wal iterator was invalid")]
     // WalIteratorInvalid,
     // #[error("column name \"default\" is a reserved name")]
     // NameDefaultIsReserved,
 }
 
+impl From<rocksdb::Error> for Error {
+    fn from(e: rocksdb::Error) -> Error {
+        Error::RocksDbError(e.into_string())
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(e: std::io::Error) -> Error {
+        Error::Io(e.to_string())
+    }
+}
+
 impl Encoder for Error {
     fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
-        format!("{}", self).encode(env)
+        match self {
+            Error::Closed => atoms::closed().encode(env),
+            Error::Released => atoms::released().encode(env),
+            Error::ColumnFamilyDropped(name) => (atoms::column_family_dropped(), name).encode(env),
+            Error::OutOfOrderBatch(seq) => (atoms::out_of_order(), seq).encode(env),
+            Error::ColumnFamilyMismatch(id) => (atoms::column_family_mismatch(), id).encode(env),
+            Error::UnknownOption(name) => (atoms::unknown_option(), name).encode(env),
+            Error::ImmutableOption(name) => (atoms::immutable_option(), name).encode(env),
+            Error::InvalidOptionValue(name) => (atoms::invalid_option_value(), name).encode(env),
+            Error::NotCfOption(name) => (atoms::not_a_cf_option(), name).encode(env),
+            _ => format!("{}", self).encode(env),
+        }
     }
 }
 
 impl From<Error> for RustlerError {
     fn from(e: Error) -> RustlerError {
-        RustlerError::Term(Box::new(format!("{}", e)))
+        RustlerError::Term(Box::new(e))
     }
 }
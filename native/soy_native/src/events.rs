@@ -0,0 +1,225 @@
+//! Events about the background work of a db.
+//!
+//! The RocksDB C API has no event listeners, so the events are derived by
+//! polling properties and, when asked for, the live files. They are
+//! approximate and can be missed: what starts and ends between two polls
+//! goes unseen, and only the net change between polls is reported.
+
+use crate::atoms;
+use crate::live_file::SoyLiveFile;
+use rocksdb::{properties as props, DB as RocksDb};
+use rustler::env::OwnedEnv;
+use rustler::{Decoder, Encoder, Env, LocalPid, NifMap, NifResult, NifUnitEnum, Term};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{RwLock, Weak};
+use std::thread;
+use std::time::Duration;
+
+pub const DEFAULT_POLL_INTERVAL_MS: u64 = 250;
+
+/// How the events of a db are polled, as given in the open opts.
+pub struct Polling {
+    pub listener: Listener,
+    pub interval: Duration,
+    // listing the live files on every poll is costly with many files
+    pub table_files: bool,
+}
+
+/// A pid given as a listener in the open opts.
+#[derive(Clone, Copy)]
+pub struct Listener(pub LocalPid);
+
+impl fmt::Debug for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Listener")
+    }
+}
+
+impl<'a> Decoder<'a> for Listener {
+    fn decode(term: Term<'a>) -> NifResult<Self> {
+        Ok(Listener(term.decode()?))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, NifUnitEnum)]
+pub enum StallCondition {
+    Normal,
+    Delayed,
+    Stopped,
+}
+
+#[derive(NifMap)]
+pub struct RunningInfo {
+    running: u64,
+}
+
+#[derive(NifMap)]
+pub struct StallInfo {
+    prev: StallCondition,
+    cur: StallCondition,
+    delayed_write_rate: u64,
+}
+
+#[derive(NifMap)]
+pub struct BackgroundErrorInfo {
+    count: u64,
+}
+
+pub enum Event {
+    FlushBegin(RunningInfo),
+    FlushCompleted(RunningInfo),
+    CompactionBegin(RunningInfo),
+    CompactionCompleted(RunningInfo),
+    StallConditionsChanged(StallInfo),
+    TableFileCreated(SoyLiveFile),
+    TableFileDeleted(SoyLiveFile),
+    BackgroundError(BackgroundErrorInfo),
+}
+
+impl Encoder for Event {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        match self {
+            Event::FlushBegin(info) => (atoms::flush_begin(), info).encode(env),
+            Event::FlushCompleted(info) => (atoms::flush_completed(), info).encode(env),
+            Event::CompactionBegin(info) => (atoms::compaction_begin(), info).encode(env),
+            Event::CompactionCompleted(info) => (atoms::compaction_completed(), info).encode(env),
+            Event::StallConditionsChanged(info) => {
+                (atoms::stall_conditions_changed(), info).encode(env)
+            }
+            Event::TableFileCreated(info) => (atoms::table_file_created(), info).encode(env),
+            Event::TableFileDeleted(info) => (atoms::table_file_deleted(), info).encode(env),
+            Event::BackgroundError(info) => (atoms::background_error(), info).encode(env),
+        }
+    }
+}
+
+/// What the db looked like at the last poll.
+struct PollState {
+    running_flushes: u64,
+    running_compactions: u64,
+    stall: StallCondition,
+    background_errors: u64,
+    files: HashMap<String, SoyLiveFile>,
+}
+
+impl PollState {
+    fn read(rdb: &RocksDb, table_files: bool) -> PollState {
+        let int_prop = |prop: &str| rdb.property_int_value(prop).ok().flatten().unwrap_or(0);
+        let stall = if int_prop(props::IS_WRITE_STOPPED) > 0 {
+            StallCondition::Stopped
+        } else if int_prop(props::ACTUAL_DELAYED_WRITE_RATE) > 0 {
+            StallCondition::Delayed
+        } else {
+            StallCondition::Normal
+        };
+        let files = match table_files {
+            true => rdb
+                .live_files()
+                .unwrap_or_default()
+                .into_iter()
+                .map(|lf| (lf.name.clone(), SoyLiveFile::from(lf)))
+                .collect(),
+            false => HashMap::new(),
+        };
+        PollState {
+            running_flushes: int_prop(props::NUM_RUNNING_FLUSHES),
+            running_compactions: int_prop(props::NUM_RUNNING_COMPACTIONS),
+            stall,
+            background_errors: int_prop(props::BACKGROUND_ERRORS),
+            files,
+        }
+    }
+
+    /// The events that explain how the db got from `prev` to `self`.
+    fn events_since(&self, prev: &mut PollState, rdb: &RocksDb) -> Vec<Event> {
+        let mut events = Vec::new();
+        let running = |running| RunningInfo { running };
+        for _ in prev.running_flushes..self.running_flushes {
+            events.push(Event::FlushBegin(running(self.running_flushes)));
+        }
+        for _ in self.running_flushes..prev.running_flushes {
+            events.push(Event::FlushCompleted(running(self.running_flushes)));
+        }
+        for _ in prev.running_compactions..self.running_compactions {
+            events.push(Event::CompactionBegin(running(self.running_compactions)));
+        }
+        for _ in self.running_compactions..prev.running_compactions {
+            events.push(Event::CompactionCompleted(running(
+                self.running_compactions,
+            )));
+        }
+        if self.stall != prev.stall {
+            let delayed_write_rate = rdb
+                .property_int_value(props::ACTUAL_DELAYED_WRITE_RATE)
+                .ok()
+                .flatten()
+                .unwrap_or(0);
+            events.push(Event::StallConditionsChanged(StallInfo {
+                prev: prev.stall,
+                cur: self.stall,
+                delayed_write_rate,
+            }));
+        }
+        if self.background_errors > prev.background_errors {
+            events.push(Event::BackgroundError(BackgroundErrorInfo {
+                count: self.background_errors,
+            }));
+        }
+        for (name, file) in self.files.iter() {
+            if prev.files.remove(name).is_none() {
+                events.push(Event::TableFileCreated(file.clone()));
+            }
+        }
+        for (_, file) in prev.files.drain() {
+            events.push(Event::TableFileDeleted(file));
+        }
+        events
+    }
+}
+
+/// Polls the db every `interval` and sends `{:soy_event, path, event, info}`
+/// messages to `listener` for what changed in between. Table file events
+/// are only sent when `table_files` is set.
+///
+/// Short flushes and compactions that start and finish between two polls
+/// only show up as table file events, if at all.
+///
+/// The poller stops once the db is closed or garbage collected.
+pub fn spawn_poller(rdb: Weak<RwLock<Option<RocksDb>>>, path: String, polling: Polling) {
+    let Polling {
+        listener,
+        interval,
+        table_files,
+    } = polling;
+    thread::spawn(move || {
+        let mut env = OwnedEnv::new();
+        let mut state: Option<PollState> = None;
+        loop {
+            let events = {
+                let rdb = match rdb.upgrade() {
+                    Some(rdb) => rdb,
+                    None => return,
+                };
+                let guard = rdb.read().unwrap();
+                let db = match guard.as_ref() {
+                    Some(db) => db,
+                    None => return,
+                };
+                let cur = PollState::read(db, table_files);
+                let events = match state.as_mut() {
+                    Some(prev) => cur.events_since(prev, db),
+                    None => Vec::new(),
+                };
+                state = Some(cur);
+                events
+            };
+            for event in events.iter() {
+                env.send_and_clear(&listener.0, |env| {
+                    (atoms::soy_event(), path.as_str(), event).encode(env)
+                });
+            }
+            thread::sleep(interval);
+        }
+    });
+}
@@ -0,0 +1,116 @@
+use crate::db_col_fam::get_cf_handle;
+use crate::sst_writer::{SoySstFileInfo, SstFile};
+use crate::{Error, SoyDb, SoyDbColFam};
+use rocksdb::{IngestExternalFileOptions, Options};
+use rustler::NifStruct;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Exported sst files are rolled over once they reach this size.
+const EXPORT_FILE_SIZE: u64 = 64 * 1024 * 1024;
+
+#[derive(Debug, NifStruct)]
+#[module = "Soy.CfExport"]
+pub struct SoyCfExport {
+    pub name: String,
+    pub dir: String,
+    pub num_entries: u64,
+    pub files: Vec<SoySstFileInfo>,
+}
+
+/// Writes every entry of the cf, as of a snapshot taken when the export
+/// starts, into sst files in `dir`. The dir must not exist yet, and is
+/// removed again if the export fails.
+///
+/// `ExportColumnFamily` is not in the C API of RocksDB 6.28, so this is a
+/// logical export: every entry is read and rewritten into new sst files
+/// with default options, rather than hard linking the cf's files. The
+/// files carry no prefix extractor or merge operator of the cf, and the
+/// returned infos describe the new files, not the cf's.
+pub fn export_cf(db_cf: &SoyDbColFam, dir: &str) -> Result<SoyCfExport, Error> {
+    let dir_path = Path::new(dir);
+    if dir_path.exists() {
+        return Err(Error::ExportDirExists(dir.to_string()));
+    }
+    fs::create_dir_all(dir_path)?;
+    let exported = write_export(db_cf, dir_path);
+    if exported.is_err() {
+        let _ = fs::remove_dir_all(dir_path);
+    }
+    let (num_entries, files) = exported?;
+    Ok(SoyCfExport {
+        name: db_cf.name().to_string(),
+        dir: dir.to_string(),
+        num_entries,
+        files,
+    })
+}
+
+fn write_export(db_cf: &SoyDbColFam, dir_path: &Path) -> Result<(u64, Vec<SoySstFileInfo>), Error> {
+    let (rdb, cf) = db_cf.lock()?;
+    let ss = rdb.snapshot();
+    let mut it = ss.raw_iterator_cf(&cf);
+    it.seek_to_first();
+
+    let mut files = Vec::new();
+    let mut file: Option<SstFile> = None;
+    let mut num_entries = 0;
+    while it.valid() {
+        if file.is_none() {
+            let path = dir_path.join(format!("{:06}.sst", files.len() + 1));
+            file = Some(SstFile::open(path_str(&path)?, Options::default())?);
+        }
+        let sst = file.as_mut().unwrap();
+        sst.put(it.key().unwrap(), it.value().unwrap())?;
+        num_entries += 1;
+        if sst.file_size() >= EXPORT_FILE_SIZE {
+            files.push(file.take().unwrap().finish()?);
+        }
+        it.next();
+    }
+    it.status()?;
+    if let Some(sst) = file {
+        files.push(sst.finish()?);
+    }
+    Ok((num_entries, files))
+}
+
+/// Creates the cf `name` and ingests the sst files of an export dir into
+/// it. The cf is dropped again if the ingestion fails.
+pub fn import_cf(
+    db: &SoyDb,
+    name: &str,
+    dir: &str,
+    opts: &Options,
+    ingest_opts: &IngestExternalFileOptions,
+) -> Result<(), Error> {
+    let files = list_sst_files(Path::new(dir))?;
+    let rdb = db.rocks_db_ref()?;
+    db.create_cf(&rdb, name, opts)?;
+    if files.is_empty() {
+        return Ok(());
+    }
+    let ingested = get_cf_handle(&rdb, name)
+        .and_then(|cf| Ok(rdb.ingest_external_file_cf_opts(&cf, ingest_opts, files)?));
+    if ingested.is_err() {
+        let _ = db.update_cfs(|| Ok(rdb.drop_cf(name)?));
+    }
+    ingested
+}
+
+fn list_sst_files(dir: &Path) -> Result<Vec<PathBuf>, Error> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().map_or(false, |ext| ext == "sst") {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn path_str(path: &Path) -> Result<&str, Error> {
+    path.to_str()
+        .ok_or_else(|| Error::Io(format!("invalid path: {:?}", path)))
+}
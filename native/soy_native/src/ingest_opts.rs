@@ -0,0 +1,32 @@
+use rocksdb::IngestExternalFileOptions;
+use rustler::NifStruct;
+
+#[derive(Debug, NifStruct)]
+#[module = "Soy.IngestOpts"]
+pub struct SoyIngestOpts {
+    set_move_files: Option<bool>,
+    set_snapshot_consistency: Option<bool>,
+    set_allow_global_seqno: Option<bool>,
+    set_allow_blocking_flush: Option<bool>,
+    set_ingest_behind: Option<bool>,
+}
+
+macro_rules! set_opt {
+    ($opts:ident, $ingest_opts:ident, $method:ident) => {
+        if let Some(val) = $ingest_opts.$method {
+            $opts.$method(val);
+        }
+    };
+}
+
+impl From<SoyIngestOpts> for IngestExternalFileOptions {
+    fn from(io: SoyIngestOpts) -> IngestExternalFileOptions {
+        let mut opts = IngestExternalFileOptions::default();
+        set_opt!(opts, io, set_move_files);
+        set_opt!(opts, io, set_snapshot_consistency);
+        set_opt!(opts, io, set_allow_global_seqno);
+        set_opt!(opts, io, set_allow_blocking_flush);
+        set_opt!(opts, io, set_ingest_behind);
+        opts
+    }
+}
@@ -1,9 +1,8 @@
-use crate::{atoms, new_binary, Error, SoyDb, SoyDbColFam, SoyIter, SoySnapshot, SoySsColFam};
-use rocksdb::{DBRawIteratorWithThreadMode, DBWALIterator, WriteBatchIterator, DB as RocksDb};
-use rustler::{Encoder, Env, ResourceArc, Term};
-use std::ops::Drop;
-
-use std::sync::RwLock;
+use crate::release::{Release, Slot};
+use crate::{Error, SoyDb, SoyDbColFam, SoyIter, SoySnapshot, SoySsColFam};
+use rocksdb::{DBRawIteratorWithThreadMode, DB as RocksDb};
+use rustler::ResourceArc;
+use std::sync::Arc;
 
 pub type RocksIter<'a> = DBRawIteratorWithThreadMode<'a, RocksDb>;
 
@@ -70,6 +69,11 @@ impl<'a> SafeIter<'a> {
         self.it.valid()
     }
 
+    /// The error that made the iterator invalid, if any.
+    pub fn status(&self) -> Result<(), Error> {
+        self.it.status().map_err(Error::from)
+    }
+
     pub fn key(&self) -> Option<&[u8]> {
         if self.is_seeked && self.valid() {
             self.it.key()
@@ -120,12 +124,33 @@ impl<'a> SafeIter<'a> {
 }
 
 pub trait SafeIteration {
-    fn safe_iter<'a>(&'a self) -> SafeIter<'a>;
+    /// The db the iter borrows from.
+    fn iter_db(&self) -> &SoyDb;
+
+    /// Creates a raw iter with its lifetime extended to `'static`.
+    ///
+    /// `rdb` must be the locked db returned by `iter_db` and the iter must
+    /// be registered with that db before `rdb` is unlocked.
+    unsafe fn raw_iter(&self, rdb: &RocksDb) -> Result<RocksIter<'static>, Error>;
+
+    /// The column family the iter reads, if not the default one.
+    fn cf_name(&self) -> Option<&str> {
+        None
+    }
+
+    /// The snapshot the iter reads, if any.
+    fn iter_snapshot(&self) -> Option<&SoySnapshot> {
+        None
+    }
 }
 
 impl SafeIteration for SoyDb {
-    fn safe_iter<'a>(&'a self) -> SafeIter<'a> {
-        SafeIter::new_unseeked(self.rocks_db_ref().raw_iterator())
+    fn iter_db(&self) -> &SoyDb {
+        self
+    }
+
+    unsafe fn raw_iter(&self, rdb: &RocksDb) -> Result<RocksIter<'static>, Error> {
+        Ok(extend_lifetime_rocks_iter(rdb.raw_iterator()))
     }
 
     // fn safe_iter_cf<'a>(&'a self, name: &'a str) -> SafeIter<'a> {
@@ -136,69 +161,94 @@ impl SafeIteration for SoyDb {
 }
 
 impl SafeIteration for SoyDbColFam {
-    fn safe_iter<'a>(&'a self) -> SafeIter<'a> {
-        let handle = self.handle();
-        let it = self.rocks_db_ref().raw_iterator_cf(handle);
-        SafeIter::new_unseeked(it)
+    fn iter_db(&self) -> &SoyDb {
+        self.soy_db()
+    }
+
+    unsafe fn raw_iter(&self, rdb: &RocksDb) -> Result<RocksIter<'static>, Error> {
+        let handle = self.handle()?;
+        Ok(extend_lifetime_rocks_iter(rdb.raw_iterator_cf(&handle)))
+    }
+
+    fn cf_name(&self) -> Option<&str> {
+        Some(self.name())
     }
 }
 
 impl SafeIteration for SoySnapshot {
-    fn safe_iter<'a>(&'a self) -> SafeIter<'a> {
-        SafeIter::new_unseeked(self.rocks_ss_ref().raw_iterator())
+    fn iter_db(&self) -> &SoyDb {
+        self.soy_db()
+    }
+
+    unsafe fn raw_iter(&self, _rdb: &RocksDb) -> Result<RocksIter<'static>, Error> {
+        let rss = self.rocks_ss_ref()?;
+        Ok(extend_lifetime_rocks_iter(rss.raw_iterator()))
+    }
+
+    fn iter_snapshot(&self) -> Option<&SoySnapshot> {
+        Some(self)
     }
 }
 
 impl SafeIteration for SoySsColFam {
-    fn safe_iter<'a>(&'a self) -> SafeIter<'a> {
-        let handle = self.handle();
-        let it = self.rocks_ss_ref().raw_iterator_cf(handle);
-        SafeIter::new_unseeked(it)
+    fn iter_db(&self) -> &SoyDb {
+        self.soy_snapshot().soy_db()
     }
-}
 
-pub trait IterLocker {
-    fn lock(&self) -> &RwLock<SafeIter<'static>>;
+    unsafe fn raw_iter(&self, _rdb: &RocksDb) -> Result<RocksIter<'static>, Error> {
+        // the db is already locked by the caller
+        let rss = self.rocks_ss_ref()?;
+        let handle = self.handle()?;
+        Ok(extend_lifetime_rocks_iter(rss.raw_iterator_cf(&handle)))
+    }
+
+    fn cf_name(&self) -> Option<&str> {
+        Some(self.name())
+    }
+
+    fn iter_snapshot(&self) -> Option<&SoySnapshot> {
+        Some(self.soy_snapshot())
+    }
 }
 
-unsafe fn extend_lifetime_safe_iter<'b>(s: SafeIter<'b>) -> SafeIter<'static> {
-    std::mem::transmute::<SafeIter<'b>, SafeIter<'static>>(s)
+pub trait IterLocker {
+    fn lock(&self) -> &Slot<SafeIter<'static>>;
 }
 
-unsafe fn unextend_lifetime_safe_iter_rwlock_mut<'b>(
-    r: &mut RwLock<SafeIter<'static>>,
-) -> &'b mut RwLock<SafeIter<'b>> {
-    std::mem::transmute::<&mut RwLock<SafeIter<'static>>, &mut RwLock<SafeIter<'b>>>(r)
+unsafe fn extend_lifetime_rocks_iter<'b>(it: RocksIter<'b>) -> RocksIter<'static> {
+    std::mem::transmute::<RocksIter<'b>, RocksIter<'static>>(it)
 }
 
 pub struct OwnedResourceIter<T>
 where
     T: SafeIteration,
 {
-    it: RwLock<SafeIter<'static>>,
+    // ORDER MATTERS. Fields are dropped in declaration order
+    // so the iter is dropped before the resource it borrows from.
+    it: Arc<Slot<SafeIter<'static>>>,
     _res: T,
 }
 
-impl<T> Drop for OwnedResourceIter<T>
-where
-    T: SafeIteration,
-{
-    fn drop(&mut self) {
-        // ORDER MATTERS.
-        let mut it = unsafe { unextend_lifetime_safe_iter_rwlock_mut(&mut self.it) };
-        std::mem::drop(&mut it);
-        std::mem::drop(&mut self._res);
-    }
-}
-
 impl<T> OwnedResourceIter<T>
 where
     T: SafeIteration,
 {
-    fn new(res: T) -> OwnedResourceIter<T> {
-        let it_unlocked = unsafe { extend_lifetime_safe_iter(res.safe_iter()) };
-        let it = RwLock::new(it_unlocked);
-        OwnedResourceIter { _res: res, it }
+    fn new(res: T) -> Result<OwnedResourceIter<T>, Error> {
+        let db = res.iter_db();
+        let rdb = db.rocks_db_ref()?;
+        let raw = unsafe { res.raw_iter(&rdb)? };
+        let it = Slot::new(SafeIter::new_unseeked(raw));
+        match res.cf_name() {
+            Some(name) => db.register_cf(name, &it),
+            None => db.register(&it),
+        }
+        if let Some(ss) = res.iter_snapshot() {
+            ss.register(&it);
+            // the snapshot may have been released after the iter was created
+            ss.rocks_ss_ref()?;
+        }
+        drop(rdb);
+        Ok(OwnedResourceIter { it, _res: res })
     }
 }
 
@@ -206,7 +256,7 @@ impl<T> IterLocker for OwnedResourceIter<T>
 where
     T: SafeIteration,
 {
-    fn lock(&self) -> &RwLock<SafeIter<'static>> {
+    fn lock(&self) -> &Slot<SafeIter<'static>> {
         &self.it
     }
 }
@@ -219,33 +269,41 @@ pub enum IterResource {
 }
 
 impl IterResource {
-    pub fn from_db(db: SoyDb) -> SoyIter {
-        let res = OwnedResourceIter::new(db);
+    pub fn from_db(db: SoyDb) -> Result<SoyIter, Error> {
+        let res = OwnedResourceIter::new(db)?;
         let it = IterResource::Db(res);
-        ResourceArc::new(it)
+        Ok(ResourceArc::new(it))
     }
 
-    pub fn from_db_cf(db_cf: SoyDbColFam) -> SoyIter {
-        let res = OwnedResourceIter::new(db_cf);
+    pub fn from_db_cf(db_cf: SoyDbColFam) -> Result<SoyIter, Error> {
+        let res = OwnedResourceIter::new(db_cf)?;
         let it = IterResource::DbCf(res);
-        ResourceArc::new(it)
+        Ok(ResourceArc::new(it))
     }
 
-    pub fn from_ss(ss: SoySnapshot) -> SoyIter {
-        let res = OwnedResourceIter::new(ss);
+    pub fn from_ss(ss: SoySnapshot) -> Result<SoyIter, Error> {
+        let res = OwnedResourceIter::new(ss)?;
         let it = IterResource::Ss(res);
-        ResourceArc::new(it)
+        Ok(ResourceArc::new(it))
     }
 
-    pub fn from_ss_cf(ss_cf: SoySsColFam) -> SoyIter {
-        let res = OwnedResourceIter::new(ss_cf);
+    pub fn from_ss_cf(ss_cf: SoySsColFam) -> Result<SoyIter, Error> {
+        let res = OwnedResourceIter::new(ss_cf)?;
         let it = IterResource::SsCf(res);
-        ResourceArc::new(it)
+        Ok(ResourceArc::new(it))
+    }
+}
+
+impl IterResource {
+    /// Frees the RocksDB iterator without waiting for garbage collection.
+    /// Later use returns `Error::Released`.
+    pub fn close(&self) {
+        self.lock().release(&Error::Released);
     }
 }
 
 impl IterLocker for IterResource {
-    fn lock(&self) -> &RwLock<SafeIter<'static>> {
+    fn lock(&self) -> &Slot<SafeIter<'static>> {
         match self {
             IterResource::Ss(res) => res.lock(),
             IterResource::Db(res) => res.lock(),
@@ -255,79 +313,6 @@ impl IterLocker for IterResource {
     }
 }
 
-pub struct WalIterator {
-    _db: SoyDb,
-    it: RwLock<DBWALIterator>,
-}
-
-unsafe impl Send for WalIterator {}
-unsafe impl Sync for WalIterator {}
-
-impl WalIterator {
-    pub fn new(db: SoyDb, since: u64) -> Result<WalIterator, Error> {
-        let it = db
-            .rocks_db_ref()
-            .get_updates_since(since)
-            .map_err(|e| Error::WalIteratorCreationError(format!("{}", e)))?;
-        Ok(WalIterator {
-            _db: db,
-            it: RwLock::new(it),
-        })
-    }
-
-    pub fn next(&self) -> Option<(u64, Vec<WalRow>)> {
-        let mut it = self.it.write().unwrap();
-        if it.valid() {
-            match it.next() {
-                None => None,
-                Some((seq_number, write_batch)) => {
-                    let mut list = WalBatchList { items: Vec::new() };
-                    write_batch.iterate(&mut list);
-                    Some((seq_number, list.items))
-                }
-            }
-        } else {
-            None
-        }
-    }
-}
-
-pub enum WalRow {
-    Put { key: Box<[u8]>, value: Box<[u8]> },
-    Delete { key: Box<[u8]> },
-}
-
-impl Encoder for WalRow {
-    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
-        match self {
-            WalRow::Put {
-                key: ref k,
-                value: ref v,
-            } => (
-                atoms::put(),
-                new_binary(&k[..], env),
-                new_binary(&v[..], env),
-            )
-                .encode(env),
-            WalRow::Delete { key: ref k } => (atoms::put(), new_binary(&k[..], env)).encode(env),
-        }
-    }
-}
-
-pub struct WalBatchList {
-    items: Vec<WalRow>,
-}
-
-impl WriteBatchIterator for WalBatchList {
-    fn put(&mut self, key: Box<[u8]>, value: Box<[u8]>) {
-        self.items.push(WalRow::Put { key, value });
-    }
-
-    fn delete(&mut self, key: Box<[u8]>) {
-        self.items.push(WalRow::Delete { key });
-    }
-}
-
 // #[derive(NifRecord)]
 // #[tag = "prefix"]
 // pub struct IterPrefix(Bin);
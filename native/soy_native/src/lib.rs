@@ -1,17 +1,22 @@
 use rocksdb::checkpoint::Checkpoint;
 use rocksdb::properties as props;
-use rocksdb::{ColumnFamilyRef, Options, WriteBatch, DB as RocksDb};
+use rocksdb::{
+    AsColumnFamilyRef, ColumnFamilyDescriptor, ColumnFamilyRef, Options, ReadOptions, WriteBatch,
+    DB as RocksDb, DEFAULT_COLUMN_FAMILY_NAME,
+};
 use rustler::{
-    Atom, Binary, Env, Error as NifError, NifRecord, NifResult, NifUnitEnum, NifUntaggedEnum,
-    ResourceArc, Term,
+    Atom, Binary, Env, Error as NifError, LocalPid, NifRecord, NifResult, NifUnitEnum,
+    NifUntaggedEnum, ResourceArc, Term,
 };
+use std::collections::HashMap;
 use std::path::Path;
+use std::time::Duration;
 
 mod iteration;
-use iteration::{IterLocker, IterResource, SafeIter, WalIterator, WalRow};
+use iteration::{IterLocker, IterResource, SafeIter};
 
 mod bin;
-use bin::{new_binary, Bin, BinStr};
+use bin::{new_binary, vec_into_binary, Bin, BinStr, VecResource};
 
 mod batching;
 use batching::{BatchOp, CfOp, DbOp};
@@ -45,6 +50,55 @@ use snapshot::SnapshotResource;
 mod soy_db;
 use soy_db::{DbResource, SoyDb};
 
+mod release;
+
+mod sst_writer;
+use sst_writer::{SoySstFileInfo, SoySstWriter, SstOp, SstWriterResource};
+
+mod ingest_opts;
+use ingest_opts::SoyIngestOpts;
+
+mod export;
+use export::SoyCfExport;
+
+mod statistics;
+use statistics::SoyStatistics;
+
+mod property_map;
+use property_map::PropValue;
+
+mod events;
+
+mod log_tail;
+
+mod approximate;
+use approximate::RangeSize;
+
+mod cf_metadata;
+use cf_metadata::SoyCfMetadata;
+
+mod mutable_opts;
+
+mod options_file;
+use options_file::PersistedOptions;
+
+mod wal;
+use wal::{DefaultCfBatch, WalBatch, WalIterator};
+
+mod crc;
+
+mod dump;
+use dump::{LoadMode, SoyDumpInfo};
+
+mod checkpoint_stream;
+use checkpoint_stream::{CheckpointStream, StreamItem};
+
+mod changes;
+use changes::{Change, Subscriptions};
+
+mod diff;
+use diff::{DiffEntry, DiffSide, SnapshotDiff};
+
 type SoySnapshot = ResourceArc<SnapshotResource>;
 
 macro_rules! ok_or_err {
@@ -63,42 +117,136 @@ mod atoms {
         ok,
         error,
         put,
+        merge,
+        delete,
+        single_delete,
+        delete_range,
+        closed,
+        released,
+        column_family_dropped,
+        out_of_order,
+        column_family_mismatch,
+        soy_event,
+        soy_log,
+        soy_wal,
+        soy_change,
+        added,
+        removed,
+        changed,
+        chunk,
+        done,
+        unknown_option,
+        immutable_option,
+        invalid_option_value,
+        not_a_cf_option,
+        flush_begin,
+        flush_completed,
+        compaction_begin,
+        compaction_completed,
+        stall_conditions_changed,
+        table_file_created,
+        table_file_deleted,
+        background_error,
     }
 }
 
 #[rustler::nif]
-fn path_open_db(path: BinStr, open_opts: SoyOpenOpts) -> SoyDb {
+fn path_open_db(path: BinStr, mut open_opts: SoyOpenOpts) -> NifResult<SoyDb> {
+    let listener = open_opts.take_event_listener();
+    let log_listener = open_opts.take_log_listener();
+    let mut cf_opts = open_opts.take_column_families();
     let opts = open_opts.into();
-    match RocksDb::list_cf(&opts, &path[..]) {
+    let db = match RocksDb::list_cf(&opts, &path[..]) {
         Ok(cfs) => {
-            let rdb = RocksDb::open_cf(&opts, &path[..], cfs).unwrap();
-            DbResource::new(rdb)
+            // the default column family gets the db opts like on a fresh open
+            let descriptors = cfs.into_iter().map(|name| {
+                let options = match cf_opts.remove(&name) {
+                    Some(cf_opts) => cf_opts.into(),
+                    None if name == DEFAULT_COLUMN_FAMILY_NAME => opts.clone(),
+                    None => Options::default(),
+                };
+                ColumnFamilyDescriptor::new(name, options)
+            });
+            let rdb = RocksDb::open_cf_descriptors(&opts, &path[..], descriptors).unwrap();
+            DbResource::new(rdb, opts)
         }
         Err(_) => {
             let rdb = RocksDb::open(&opts, &path[..]).unwrap();
-            DbResource::new(rdb)
+            DbResource::new(rdb, opts)
         }
+    };
+    if let Some(polling) = listener {
+        db.listen(polling)?;
     }
+    if let Some((listener, db_log_dir)) = log_listener {
+        db.tail_log(listener, db_log_dir.as_deref())?;
+    }
+    Ok(db)
 }
 
-#[rustler::nif]
-fn db_path(env: Env, db: SoyDb) -> Binary {
-    new_binary(db.rocks_db_ref().path().to_str().unwrap().as_bytes(), env)
+#[rustler::nif(schedule = "DirtyIo")]
+fn path_load_latest_options(path: BinStr) -> Result<PersistedOptions, Error> {
+    options_file::load_latest(&path[..])
 }
 
 #[rustler::nif]
-fn db_checkpoint(db: SoyDb, checkpoint_path: BinStr) -> Atom {
+fn db_path(env: Env, db: SoyDb) -> NifResult<Binary> {
+    let rdb = db.rocks_db_ref()?;
+    Ok(new_binary(rdb.path().to_str().unwrap().as_bytes(), env))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_close(db: SoyDb) -> NifResult<Atom> {
+    db.close()?;
+    Ok(atoms::ok())
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_checkpoint(db: SoyDb, checkpoint_path: BinStr) -> NifResult<Atom> {
     let cp_path = Path::new(&checkpoint_path[..]);
-    let rdb = db.rocks_db_ref();
+    let rdb = db.rocks_db_ref()?;
     if rdb.path() == cp_path {
         panic!(
             "checkpoint path cannot be the same as the db path - got: {}",
             cp_path.to_str().unwrap()
         );
     }
-    let checkpoint = Checkpoint::new(rdb).unwrap();
+    let checkpoint = Checkpoint::new(&rdb).unwrap();
     checkpoint.create_checkpoint(cp_path).unwrap();
-    atoms::ok()
+    Ok(atoms::ok())
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_stream_checkpoint(
+    db: SoyDb,
+    id: u64,
+    chunk_size: usize,
+) -> NifResult<ResourceArc<CheckpointStream>> {
+    let stream = CheckpointStream::new(&db, id, chunk_size)?;
+    Ok(ResourceArc::new(stream))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn checkpoint_stream_next(stream: ResourceArc<CheckpointStream>) -> NifResult<Option<StreamItem>> {
+    Ok(stream.next()?)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn path_write_checkpoint_chunk(
+    dir: BinStr,
+    name: BinStr,
+    offset: u64,
+    crc: u32,
+    data: Binary,
+) -> NifResult<Atom> {
+    checkpoint_stream::write_chunk(&dir[..], &name[..], offset, crc, &data[..])?;
+    Ok(atoms::ok())
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn path_finish_checkpoint(dir: BinStr, files: Vec<(String, u64, u32)>) -> NifResult<Atom> {
+    checkpoint_stream::finish(&dir[..], &files)?;
+    Ok(atoms::ok())
 }
 
 #[rustler::nif(schedule = "DirtyIo")]
@@ -112,63 +260,96 @@ fn path_repair(path: BinStr) -> NifResult<Atom> {
 }
 
 #[rustler::nif]
-fn db_put(db: SoyDb, key: Binary, val: Binary) -> NifResult<Atom> {
-    ok_or_err!(db.rocks_db_ref().put(&key[..], &val[..]))
+fn db_put(env: Env, db: SoyDb, key: Binary, val: Binary) -> NifResult<Atom> {
+    let res = db.rocks_db_ref()?.put(&key[..], &val[..]);
+    if res.is_ok() {
+        let change = Change::put(DEFAULT_COLUMN_FAMILY_NAME, &key[..], &val[..]);
+        db.changes().notify(env, &[change]);
+    }
+    ok_or_err!(res)
 }
 
 #[rustler::nif]
-fn db_fetch<'a>(db: SoyDb, key: Binary) -> NifResult<(Atom, Bin)> {
-    match db.rocks_db_ref().get(&key[..]) {
-        Ok(Some(v)) => Ok((atoms::ok(), Bin::from_vec(v))),
+fn db_fetch<'a>(env: Env<'a>, db: SoyDb, key: Binary) -> NifResult<(Atom, Binary<'a>)> {
+    match db.rocks_db_ref()?.get_pinned(&key[..]) {
+        Ok(Some(v)) => Ok((atoms::ok(), new_binary(&v[..], env))),
         Ok(None) => Err(NifError::Atom("error")),
         Err(e) => panic!("{}", e),
     }
 }
 
 #[rustler::nif]
-fn db_delete(db: SoyDb, key: Binary) -> NifResult<Atom> {
-    ok_or_err!(db.rocks_db_ref().delete(&key[..]))
+fn db_delete(env: Env, db: SoyDb, key: Binary) -> NifResult<Atom> {
+    let res = db.rocks_db_ref()?.delete(&key[..]);
+    if res.is_ok() {
+        let change = Change::delete(DEFAULT_COLUMN_FAMILY_NAME, &key[..]);
+        db.changes().notify(env, &[change]);
+    }
+    ok_or_err!(res)
 }
 
 #[rustler::nif]
-fn db_merge(db: SoyDb, key: Binary, val: Binary) -> NifResult<Atom> {
-    ok_or_err!(db.rocks_db_ref().merge(&key[..], &val[..]))
+fn db_merge(env: Env, db: SoyDb, key: Binary, val: Binary) -> NifResult<Atom> {
+    let res = db.rocks_db_ref()?.merge(&key[..], &val[..]);
+    if res.is_ok() {
+        let change = Change::merge(DEFAULT_COLUMN_FAMILY_NAME, &key[..], &val[..]);
+        db.changes().notify(env, &[change]);
+    }
+    ok_or_err!(res)
 }
 
 #[rustler::nif]
-fn db_merge_cf(db: SoyDb, cf_name: BinStr, key: Binary, val: Binary) -> NifResult<Atom> {
-    let rdb = db.rocks_db_ref();
-    let cf_handle = get_cf_handle(rdb, &cf_name[..]).unwrap();
-    ok_or_err!(rdb.merge_cf(&cf_handle, &key[..], &val[..]))
+fn db_merge_cf(env: Env, db: SoyDb, cf_name: BinStr, key: Binary, val: Binary) -> NifResult<Atom> {
+    let rdb = db.rocks_db_ref()?;
+    let cf_handle = get_cf_handle(&rdb, &cf_name[..]).unwrap();
+    let res = rdb.merge_cf(&cf_handle, &key[..], &val[..]);
+    if res.is_ok() {
+        let change = Change::merge(&cf_name[..], &key[..], &val[..]);
+        db.changes().notify(env, &[change]);
+    }
+    ok_or_err!(res)
 }
 
 #[rustler::nif(schedule = "DirtyIo")]
 fn db_flush(db: SoyDb) -> NifResult<Atom> {
-    ok_or_err!(db.rocks_db_ref().flush())
+    ok_or_err!(db.rocks_db_ref()?.flush())
 }
 
 #[rustler::nif(schedule = "DirtyIo")]
 fn db_cf_flush(db_cf: SoyDbColFam) -> NifResult<Atom> {
-    let handle = db_cf.handle();
-    ok_or_err!(db_cf.rocks_db_ref().flush_cf(handle))
+    let (rdb, cf) = db_cf.lock()?;
+    ok_or_err!(rdb.flush_cf(&cf))
 }
 
 #[rustler::nif(schedule = "DirtyIo")]
 fn db_flush_wal(db: SoyDb, sync: bool) -> NifResult<Atom> {
-    ok_or_err!(db.rocks_db_ref().flush_wal(sync))
+    ok_or_err!(db.rocks_db_ref()?.flush_wal(sync))
 }
 
 #[rustler::nif]
-fn db_latest_sequence_number(db: SoyDb) -> u64 {
-    db.rocks_db_ref().latest_sequence_number()
+fn db_latest_sequence_number(db: SoyDb) -> NifResult<u64> {
+    Ok(db.rocks_db_ref()?.latest_sequence_number())
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_oldest_available_sequence_number(db: SoyDb) -> NifResult<u64> {
+    Ok(wal::oldest_sequence_number(&db.rocks_db_ref()?)?)
+}
+
+#[derive(NifUntaggedEnum)]
+pub enum WalStart {
+    Seq(u64),
+    Snapshot(SoySnapshot),
 }
 
 #[rustler::nif]
-fn db_get_updates_since(
-    db: SoyDb,
-    sequence_number: u64,
-) -> Result<ResourceArc<WalIterator>, Error> {
-    let it = WalIterator::new(db, sequence_number)?;
+fn db_get_updates_since(db: SoyDb, since: WalStart) -> NifResult<ResourceArc<WalIterator>> {
+    let since = match since {
+        WalStart::Seq(seq) => seq,
+        // the snapshot already contains its own sequence number
+        WalStart::Snapshot(ss) => ss.sequence_number() + 1,
+    };
+    let it = WalIterator::new(db, since)?;
     Ok(ResourceArc::new(it))
 }
 
@@ -179,14 +360,66 @@ pub enum Prop {
 }
 
 #[rustler::nif]
-fn db_get_property(db: SoyDb, prop: &str) -> Option<Prop> {
-    do_get_property(&db.rocks_db_ref(), prop)
+fn db_set_options(db: SoyDb, opts: HashMap<String, Term>) -> NifResult<Atom> {
+    mutable_opts::set_cf_options(&db.rocks_db_ref()?, None, opts)?;
+    Ok(atoms::ok())
 }
 
 #[rustler::nif]
-fn db_list_properties(db: SoyDb) -> Vec<(String, Option<Prop>)> {
-    let rdb = db.rocks_db_ref();
-    vec![
+fn db_cf_set_options(db_cf: SoyDbColFam, opts: HashMap<String, Term>) -> NifResult<Atom> {
+    let (rdb, cf) = db_cf.lock()?;
+    mutable_opts::set_cf_options(&rdb, Some(&cf), opts)?;
+    Ok(atoms::ok())
+}
+
+#[rustler::nif]
+fn db_get_property(db: SoyDb, prop: &str) -> NifResult<Option<Prop>> {
+    Ok(do_get_property(&db.rocks_db_ref()?, prop))
+}
+
+#[rustler::nif]
+fn db_cf_get_property(db_cf: SoyDbColFam, prop: &str) -> NifResult<Option<Prop>> {
+    let (rdb, cf) = db_cf.lock()?;
+    Ok(do_get_property_cf(&rdb, &cf, prop))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_get_property_map(db: SoyDb, prop: &str) -> NifResult<Option<HashMap<String, PropValue>>> {
+    let rdb = db.rocks_db_ref()?;
+    let text = rdb.property_value(prop).map_err(Error::from)?;
+    Ok(text.map(|t| property_map::parse(&t)))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_cf_get_property_map(
+    db_cf: SoyDbColFam,
+    prop: &str,
+) -> NifResult<Option<HashMap<String, PropValue>>> {
+    let (rdb, cf) = db_cf.lock()?;
+    let text = rdb.property_value_cf(&cf, prop).map_err(Error::from)?;
+    Ok(text.map(|t| property_map::parse(&t)))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_get_approximate_sizes(db: SoyDb, ranges: Vec<(Binary, Binary)>) -> NifResult<Vec<RangeSize>> {
+    let rdb = db.rocks_db_ref()?;
+    Ok(approximate::range_sizes(&rdb, None, &ranges)?)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_cf_get_approximate_sizes(
+    db_cf: SoyDbColFam,
+    ranges: Vec<(Binary, Binary)>,
+) -> NifResult<Vec<RangeSize>> {
+    let (rdb, cf) = db_cf.lock()?;
+    Ok(approximate::range_sizes(&rdb, Some(&cf), &ranges)?)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_list_properties(db: SoyDb) -> NifResult<Vec<(String, Option<Prop>)>> {
+    let rdb = db.rocks_db_ref()?;
+    let rdb = &*rdb;
+    Ok(vec![
         prop_kv(rdb, props::ACTUAL_DELAYED_WRITE_RATE),
         prop_kv(rdb, props::AGGREGATED_TABLE_PROPERTIES),
         prop_kv(rdb, props::AGGREGATED_TABLE_PROPERTIES_AT_LEVEL),
@@ -234,7 +467,18 @@ fn db_list_properties(db: SoyDb) -> Vec<(String, Option<Prop>)> {
         prop_kv(rdb, props::SSTABLES),
         prop_kv(rdb, props::STATS),
         prop_kv(rdb, props::TOTAL_SST_FILES_SIZE),
-    ]
+    ])
+}
+
+#[rustler::nif]
+fn db_statistics(db: SoyDb) -> NifResult<Option<SoyStatistics>> {
+    Ok(db.statistics()?)
+}
+
+#[rustler::nif]
+fn db_reset_statistics(db: SoyDb) -> NifResult<Atom> {
+    db.reset_statistics()?;
+    Ok(atoms::ok())
 }
 
 fn prop_kv(rdb: &RocksDb, prop: &str) -> (String, Option<Prop>) {
@@ -253,30 +497,112 @@ fn do_get_property(db: &RocksDb, prop: &str) -> Option<Prop> {
     }
 }
 
+fn do_get_property_cf(db: &RocksDb, cf: &impl AsColumnFamilyRef, prop: &str) -> Option<Prop> {
+    match db.property_int_value_cf(cf, prop) {
+        Ok(Some(int)) => Some(Prop::Int(int)),
+        Ok(None) => None,
+        Err(_) => match db.property_value_cf(cf, prop) {
+            Ok(Some(val)) => Some(Prop::String(val)),
+            Ok(None) => None,
+            Err(e) => panic!("{}", e),
+        },
+    }
+}
+
+#[rustler::nif]
+fn wal_iter_next(w: ResourceArc<WalIterator>) -> NifResult<Option<DefaultCfBatch>> {
+    Ok(w.next()?.map(DefaultCfBatch))
+}
+
 #[rustler::nif]
-fn wal_iter_next(w: ResourceArc<WalIterator>) -> Option<(u64, Vec<WalRow>)> {
-    w.next()
+fn wal_iter_next_batch(w: ResourceArc<WalIterator>) -> NifResult<Option<WalBatch>> {
+    Ok(w.next()?)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn wal_iter_take(w: ResourceArc<WalIterator>, n: usize) -> NifResult<Vec<WalBatch>> {
+    Ok(w.take(n)?)
+}
+
+#[rustler::nif]
+fn wal_iter_subscribe(
+    w: ResourceArc<WalIterator>,
+    pid: LocalPid,
+    id: u64,
+    interval_ms: u64,
+) -> NifResult<Atom> {
+    w.subscribe(pid, id, Duration::from_millis(interval_ms))?;
+    Ok(atoms::ok())
 }
 
 #[rustler::nif]
-fn db_multi_get<'a>(db: SoyDb, keys: Vec<Binary>) -> Vec<Option<Bin>> {
-    let keys_it = keys.iter().map(|k| (&k[..]).to_vec());
-    db.rocks_db_ref()
+fn wal_iter_next_binary<'a>(
+    env: Env<'a>,
+    w: ResourceArc<WalIterator>,
+) -> NifResult<Option<(u64, Binary<'a>)>> {
+    Ok(w.next_raw()?
+        .map(|(seq, rep)| (seq, vec_into_binary(rep, env))))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_apply_batch_binary(
+    db: SoyDb,
+    batch: Binary,
+    primary_cf_ids: HashMap<String, u32>,
+) -> NifResult<u64> {
+    Ok(db.apply_batch(&batch[..], &primary_cf_ids)?)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_wal_cf_ids(db: SoyDb) -> NifResult<HashMap<String, u32>> {
+    let names = db.cf_names(&db.rocks_db_ref()?)?;
+    Ok(names.iter().map(|(id, name)| (name.clone(), *id)).collect())
+}
+
+fn do_db_multi_get<'a>(
+    env: Env<'a>,
+    db: SoyDb,
+    keys: Vec<Binary>,
+) -> NifResult<Vec<Option<Binary<'a>>>> {
+    let keys_it = keys.iter().map(|k| &k[..]);
+    Ok(db
+        .rocks_db_ref()?
         .multi_get(keys_it)
         .into_iter()
-        .map(|v| match v.unwrap() {
-            Some(data) => Some(Bin::from_vec(data)),
-            None => None,
-        })
-        .collect()
+        .map(|v| v.unwrap().map(|data| vec_into_binary(data, env)))
+        .collect())
 }
 
 #[rustler::nif]
-fn db_cf_multi_get<'a>(pairs: Vec<(SoyDbColFam, Binary)>) -> Vec<Option<Bin>> {
+fn db_multi_get<'a>(
+    env: Env<'a>,
+    db: SoyDb,
+    keys: Vec<Binary>,
+) -> NifResult<Vec<Option<Binary<'a>>>> {
+    do_db_multi_get(env, db, keys)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_multi_get_dirty<'a>(
+    env: Env<'a>,
+    db: SoyDb,
+    keys: Vec<Binary>,
+) -> NifResult<Vec<Option<Binary<'a>>>> {
+    do_db_multi_get(env, db, keys)
+}
+
+fn do_db_cf_multi_get<'a>(
+    env: Env<'a>,
+    pairs: Vec<(SoyDbColFam, Binary)>,
+) -> NifResult<Vec<Option<Binary<'a>>>> {
     if pairs.len() == 0 {
-        return vec![];
+        return Ok(vec![]);
     }
-    let db_cf = pairs.first().unwrap().clone().0;
+    let rdb = pairs.first().unwrap().0.rocks_db_ref()?;
+    let handles = pairs
+        .iter()
+        .map(|(h, _)| h.handle())
+        .collect::<Result<Vec<_>, Error>>()?;
 
     // let cf_handle_keys: Vec<(Arc<rocksdb::BoundColumnFamily<'_>>, Binary)> = cf_and_keys
     //     .into_iter()
@@ -285,36 +611,51 @@ fn db_cf_multi_get<'a>(pairs: Vec<(SoyDbColFam, Binary)>) -> Vec<Option<Bin>> {
     //         (cf_handle, key)
     //     })
     //     .collect();
-    let keys_it = pairs.iter().map(|(h, k)| (h.handle(), &k[..]));
-    db_cf
-        .rocks_db_ref()
+    let keys_it = handles
+        .iter()
+        .zip(pairs.iter())
+        .map(|(h, (_, k))| (h, &k[..]));
+    Ok(rdb
         .multi_get_cf(keys_it)
         .into_iter()
-        .map(|v| match v.unwrap() {
-            Some(data) => Some(Bin::from_vec(data)),
-            None => None,
-        })
-        .collect()
+        .map(|v| v.unwrap().map(|data| vec_into_binary(data, env)))
+        .collect())
 }
 
 #[rustler::nif]
-fn db_live_files(db: SoyDb) -> Vec<SoyLiveFile> {
-    db.rocks_db_ref()
+fn db_cf_multi_get<'a>(
+    env: Env<'a>,
+    pairs: Vec<(SoyDbColFam, Binary)>,
+) -> NifResult<Vec<Option<Binary<'a>>>> {
+    do_db_cf_multi_get(env, pairs)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_cf_multi_get_dirty<'a>(
+    env: Env<'a>,
+    pairs: Vec<(SoyDbColFam, Binary)>,
+) -> NifResult<Vec<Option<Binary<'a>>>> {
+    do_db_cf_multi_get(env, pairs)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_live_files(db: SoyDb) -> NifResult<Vec<SoyLiveFile>> {
+    Ok(db
+        .rocks_db_ref()?
         .live_files()
         .unwrap()
         .into_iter()
         .map(|item| SoyLiveFile::from(item))
-        .collect()
+        .collect())
 }
 
-#[rustler::nif]
-fn db_batch<'a>(db: SoyDb, ops: Vec<BatchOp>) -> NifResult<usize> {
+fn do_db_batch(env: Env, db: SoyDb, ops: Vec<BatchOp>) -> NifResult<usize> {
     if ops.len() == 0 {
         return Ok(0);
     }
-    let rdb = db.rocks_db_ref();
+    let rdb = db.rocks_db_ref()?;
     let mut batch = WriteBatch::default();
-    for op in ops {
+    for op in ops.iter() {
         match op {
             BatchOp::Db(db_op) => match db_op {
                 DbOp::Put(p) => batch.put(p.key(), p.val()),
@@ -334,19 +675,92 @@ fn db_batch<'a>(db: SoyDb, ops: Vec<BatchOp>) -> NifResult<usize> {
     }
     let count = batch.len();
     match rdb.write(batch) {
-        Ok(_) => Ok(count),
+        Ok(_) => {
+            if !db.changes().is_empty() {
+                db.changes().notify(env, &batch_changes(&ops));
+            }
+            Ok(count)
+        }
         Err(e) => Err(NifError::Term(Box::new(format!("{}", e)))),
     }
 }
 
+fn batch_changes(ops: &[BatchOp]) -> Vec<Change> {
+    ops.iter()
+        .map(|op| match op {
+            BatchOp::Db(DbOp::Put(p)) => Change::put(DEFAULT_COLUMN_FAMILY_NAME, p.key(), p.val()),
+            BatchOp::Db(DbOp::Delete(d)) => Change::delete(DEFAULT_COLUMN_FAMILY_NAME, d.key()),
+            BatchOp::Cf(CfOp::Put(p)) => Change::put(p.name(), p.key(), p.val()),
+            BatchOp::Cf(CfOp::Delete(d)) => Change::delete(d.name(), d.key()),
+        })
+        .collect()
+}
+
+#[rustler::nif]
+fn db_batch<'a>(env: Env<'a>, db: SoyDb, ops: Vec<BatchOp>) -> NifResult<usize> {
+    do_db_batch(env, db, ops)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_batch_dirty<'a>(env: Env<'a>, db: SoyDb, ops: Vec<BatchOp>) -> NifResult<usize> {
+    do_db_batch(env, db, ops)
+}
+
+#[rustler::nif]
+fn db_subscribe_changes(
+    db: SoyDb,
+    pid: LocalPid,
+    id: u64,
+    cf_name: BinStr,
+    prefix: Binary,
+) -> NifResult<Atom> {
+    if db.rocks_db_ref()?.cf_handle(&cf_name[..]).is_none() {
+        return Err(Error::ColumnFamilyDoesNotExist(cf_name[..].to_string()).into());
+    }
+    db.changes().subscribe(id, pid, &cf_name[..], &prefix[..]);
+    Ok(atoms::ok())
+}
+
+#[rustler::nif]
+fn changes_unsubscribe(id: u64) -> Atom {
+    Subscriptions::unsubscribe(id);
+    atoms::ok()
+}
+
+#[rustler::nif]
+fn db_count_change_subscriptions(db: SoyDb) -> usize {
+    db.changes().len()
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_ingest_external_file(
+    db: SoyDb,
+    paths: Vec<String>,
+    ingest_opts: SoyIngestOpts,
+) -> NifResult<Atom> {
+    let opts = ingest_opts.into();
+    ok_or_err!(db.rocks_db_ref()?.ingest_external_file_opts(&opts, paths))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_cf_ingest_external_file(
+    db_cf: SoyDbColFam,
+    paths: Vec<String>,
+    ingest_opts: SoyIngestOpts,
+) -> NifResult<Atom> {
+    let opts = ingest_opts.into();
+    let (rdb, cf) = db_cf.lock()?;
+    ok_or_err!(rdb.ingest_external_file_cf_opts(&cf, &opts, paths))
+}
+
 #[rustler::nif]
-fn db_iter<'a>(db: SoyDb) -> SoyIter {
-    IterResource::from_db(db)
+fn db_iter<'a>(db: SoyDb) -> NifResult<SoyIter> {
+    Ok(IterResource::from_db(db)?)
 }
 
 #[rustler::nif]
-fn db_cf_iter<'a>(db_cf: SoyDbColFam) -> SoyIter {
-    IterResource::from_db_cf(db_cf)
+fn db_cf_iter<'a>(db_cf: SoyDbColFam) -> NifResult<SoyIter> {
+    Ok(IterResource::from_db_cf(db_cf)?)
 }
 
 #[derive(Debug, NifUnitEnum)]
@@ -376,9 +790,7 @@ enum Seek {
 #[tag = "next"]
 pub struct SeekNextBin(Bin);
 
-#[rustler::nif]
-fn iter_seek<'a>(env: Env<'a>, soy_iter: SoyIter, seek: Seek) -> Option<(Binary<'a>, Binary<'a>)> {
-    let mut it = soy_iter.lock().write().unwrap();
+fn do_seek(it: &mut SafeIter, seek: Seek) {
     match seek {
         Seek::Atom(SeekAtom::Next) => it.next(),
         Seek::Atom(SeekAtom::Prev) => it.prev(),
@@ -387,33 +799,55 @@ fn iter_seek<'a>(env: Env<'a>, soy_iter: SoyIter, seek: Seek) -> Option<(Binary<
         Seek::Next(SeekNext(key)) => it.seek(key.as_bytes()),
         Seek::Prev(SeekPrev(key)) => it.seek_for_prev(key.as_bytes()),
     }
-    do_iter_key_value(env, &it)
 }
 
 #[rustler::nif]
-fn iter_key(env: Env, soy_iter: SoyIter) -> Option<Binary> {
-    soy_iter
-        .lock()
-        .read()
-        .unwrap()
-        .key()
-        .map(|k| new_binary(&k[..], env))
+fn iter_seek<'a>(
+    env: Env<'a>,
+    soy_iter: SoyIter,
+    seek: Seek,
+) -> NifResult<Option<(Binary<'a>, Binary<'a>)>> {
+    let mut it = soy_iter.lock().write()?;
+    do_seek(&mut it, seek);
+    Ok(do_iter_key_value(env, &it))
 }
 
 #[rustler::nif]
-fn iter_value(env: Env, soy_iter: SoyIter) -> Option<Binary> {
-    soy_iter
-        .lock()
-        .read()
-        .unwrap()
-        .value()
-        .map(|k| new_binary(&k[..], env))
+fn iter_seek_key<'a>(env: Env<'a>, soy_iter: SoyIter, seek: Seek) -> NifResult<Option<Binary<'a>>> {
+    let mut it = soy_iter.lock().write()?;
+    do_seek(&mut it, seek);
+    Ok(it.key().map(|k| new_binary(&k[..], env)))
+}
+
+#[rustler::nif]
+fn iter_seek_key_size<'a>(
+    env: Env<'a>,
+    soy_iter: SoyIter,
+    seek: Seek,
+) -> NifResult<Option<(Binary<'a>, usize)>> {
+    let mut it = soy_iter.lock().write()?;
+    do_seek(&mut it, seek);
+    Ok(it
+        .key_value()
+        .map(|(k, v)| (new_binary(&k[..], env), v.len())))
+}
+
+#[rustler::nif]
+fn iter_key(env: Env, soy_iter: SoyIter) -> NifResult<Option<Binary>> {
+    let it = soy_iter.lock().read()?;
+    Ok(it.key().map(|k| new_binary(&k[..], env)))
 }
 
 #[rustler::nif]
-fn iter_key_value(env: Env, soy_iter: SoyIter) -> Option<(Binary, Binary)> {
-    let it = soy_iter.lock().read().unwrap();
-    do_iter_key_value(env, &it)
+fn iter_value(env: Env, soy_iter: SoyIter) -> NifResult<Option<Binary>> {
+    let it = soy_iter.lock().read()?;
+    Ok(it.value().map(|k| new_binary(&k[..], env)))
+}
+
+#[rustler::nif]
+fn iter_key_value(env: Env, soy_iter: SoyIter) -> NifResult<Option<(Binary, Binary)>> {
+    let it = soy_iter.lock().read()?;
+    Ok(do_iter_key_value(env, &it))
 }
 
 fn do_iter_key_value<'a>(env: Env<'a>, it: &SafeIter<'a>) -> Option<(Binary<'a>, Binary<'a>)> {
@@ -422,19 +856,68 @@ fn do_iter_key_value<'a>(env: Env<'a>, it: &SafeIter<'a>) -> Option<(Binary<'a>,
 }
 
 #[rustler::nif]
-fn db_snapshot(db: SoyDb) -> SoySnapshot {
-    SnapshotResource::new(db)
+fn db_snapshot(db: SoyDb) -> NifResult<SoySnapshot> {
+    Ok(SnapshotResource::new(db)?)
 }
 
 #[rustler::nif]
-fn ss_fetch(ss: SoySnapshot, key: Binary) -> NifResult<(Atom, Bin)> {
-    match ss.rocks_ss_ref().get(&key[..]) {
-        Ok(Some(v)) => Ok((atoms::ok(), Bin::from_vec(v))),
+fn ss_fetch<'a>(env: Env<'a>, ss: SoySnapshot, key: Binary) -> NifResult<(Atom, Binary<'a>)> {
+    match ss.rocks_ss_ref()?.get(&key[..]) {
+        Ok(Some(v)) => Ok((atoms::ok(), vec_into_binary(v, env))),
         Ok(None) => Err(NifError::Atom("error")),
         Err(e) => panic!("{}", e),
     }
 }
 
+#[rustler::nif]
+fn ss_sequence_number(ss: SoySnapshot) -> u64 {
+    ss.sequence_number()
+}
+
+#[rustler::nif]
+fn ss_created_at(ss: SoySnapshot) -> u64 {
+    ss.created_at()
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn ss_dump(ss: SoySnapshot, path: BinStr, cfs: Option<Vec<String>>) -> Result<SoyDumpInfo, Error> {
+    dump::dump(&ss, &path[..], cfs)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_load_dump(
+    db: SoyDb,
+    path: BinStr,
+    mode: LoadMode,
+    open_opts: SoyOpenOpts,
+) -> Result<SoyDumpInfo, Error> {
+    let opts = open_opts.into();
+    dump::load(&db, &path[..], mode, &opts)
+}
+
+#[rustler::nif]
+fn ss_diff(
+    old: DiffSide,
+    new: DiffSide,
+    cf_name: Option<String>,
+    from: Option<Bin>,
+    to: Option<Bin>,
+) -> NifResult<ResourceArc<SnapshotDiff>> {
+    let diff = SnapshotDiff::new(old, new, cf_name.as_deref(), from, to)?;
+    Ok(ResourceArc::new(diff))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn diff_next(diff: ResourceArc<SnapshotDiff>, max: usize) -> NifResult<Option<Vec<DiffEntry>>> {
+    Ok(diff.next_chunk(max)?)
+}
+
+#[rustler::nif]
+fn ss_release(ss: SoySnapshot) -> Atom {
+    ss.release();
+    atoms::ok()
+}
+
 #[rustler::nif]
 fn ss_open_ss_cf(ss: SoySnapshot, name: BinStr) -> NifResult<SoySsColFam> {
     let ss_cf = SsColFamResource::new(&ss, &name[..])?;
@@ -442,14 +925,22 @@ fn ss_open_ss_cf(ss: SoySnapshot, name: BinStr) -> NifResult<SoySsColFam> {
 }
 
 #[rustler::nif]
-fn iter_valid<'a>(soy_iter: SoyIter) -> bool {
-    soy_iter.lock().read().unwrap().valid()
+fn iter_valid<'a>(soy_iter: SoyIter) -> NifResult<bool> {
+    Ok(soy_iter.lock().read()?.valid())
+}
+
+#[rustler::nif]
+fn iter_close(soy_iter: SoyIter) -> Atom {
+    soy_iter.close();
+    atoms::ok()
 }
 
 #[rustler::nif]
 fn db_create_new_cf(db: SoyDb, name: BinStr, open_opts: SoyOpenOpts) -> NifResult<SoyDbColFam> {
     let opts = open_opts.into();
-    match db.rocks_db_ref().create_cf(&name[..], &opts) {
+    // the db lock must be released before build_cf_db takes it again
+    let created = db.create_cf(&db.rocks_db_ref()?, &name[..], &opts);
+    match created {
         Ok(()) => build_cf_db(&db, &name[..]),
         Err(e) => Err(NifError::Term(Box::new(format!("{}", e)))),
     }
@@ -460,22 +951,41 @@ fn db_open_existing_cf(db: SoyDb, name: BinStr) -> NifResult<SoyDbColFam> {
     build_cf_db(&db, &name[..])
 }
 
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_import_cf(
+    db: SoyDb,
+    name: BinStr,
+    dir: BinStr,
+    open_opts: SoyOpenOpts,
+    ingest_opts: SoyIngestOpts,
+) -> NifResult<SoyDbColFam> {
+    let opts = open_opts.into();
+    let ingest_opts = ingest_opts.into();
+    export::import_cf(&db, &name[..], &dir[..], &opts, &ingest_opts)?;
+    build_cf_db(&db, &name[..])
+}
+
 fn build_cf_db(db: &SoyDb, name: &str) -> NifResult<SoyDbColFam> {
     let resource = DbColFamResource::new(db, name)?;
     Ok(ResourceArc::new(resource))
 }
 
 #[rustler::nif]
-fn db_cf_put(db_cf: SoyDbColFam, key: Binary, val: Binary) -> NifResult<Atom> {
-    let handle = db_cf.handle();
-    ok_or_err!(db_cf.rocks_db_ref().put_cf(handle, &key[..], &val[..]))
+fn db_cf_put(env: Env, db_cf: SoyDbColFam, key: Binary, val: Binary) -> NifResult<Atom> {
+    let (rdb, cf) = db_cf.lock()?;
+    let res = rdb.put_cf(&cf, &key[..], &val[..]);
+    if res.is_ok() {
+        let change = Change::put(db_cf.name(), &key[..], &val[..]);
+        db_cf.soy_db().changes().notify(env, &[change]);
+    }
+    ok_or_err!(res)
 }
 
 #[rustler::nif]
-fn db_cf_fetch(db_cf: SoyDbColFam, key: Binary) -> NifResult<(Atom, Bin)> {
-    let handle = db_cf.handle();
-    match db_cf.rocks_db_ref().get_cf(handle, &key[..]) {
-        Ok(Some(v)) => Ok((atoms::ok(), Bin::from_vec(v))),
+fn db_cf_fetch<'a>(env: Env<'a>, db_cf: SoyDbColFam, key: Binary) -> NifResult<(Atom, Binary<'a>)> {
+    let (rdb, cf) = db_cf.lock()?;
+    match rdb.get_pinned_cf(&cf, &key[..]) {
+        Ok(Some(v)) => Ok((atoms::ok(), new_binary(&v[..], env))),
         Ok(None) => Err(NifError::Atom("error")),
         Err(e) => panic!("error: {:?}", e),
     }
@@ -492,33 +1002,85 @@ fn db_cf_into_db(db_cf: SoyDbColFam) -> SoyDb {
 }
 
 #[rustler::nif]
-fn db_cf_delete(db_cf: SoyDbColFam, key: Binary) -> NifResult<Atom> {
-    let handle = db_cf.handle();
-    ok_or_err!(db_cf.rocks_db_ref().delete_cf(handle, &key[..]))
+fn db_cf_delete(env: Env, db_cf: SoyDbColFam, key: Binary) -> NifResult<Atom> {
+    let (rdb, cf) = db_cf.lock()?;
+    let res = rdb.delete_cf(&cf, &key[..]);
+    if res.is_ok() {
+        let change = Change::delete(db_cf.name(), &key[..]);
+        db_cf.soy_db().changes().notify(env, &[change]);
+    }
+    ok_or_err!(res)
 }
 
 #[rustler::nif]
-fn db_cf_merge(db_cf: SoyDbColFam, key: Binary, val: Binary) -> NifResult<Atom> {
-    let handle = db_cf.handle();
-    ok_or_err!(db_cf.rocks_db_ref().merge_cf(handle, &key[..], &val[..]))
+fn db_cf_merge(env: Env, db_cf: SoyDbColFam, key: Binary, val: Binary) -> NifResult<Atom> {
+    let (rdb, cf) = db_cf.lock()?;
+    let res = rdb.merge_cf(&cf, &key[..], &val[..]);
+    if res.is_ok() {
+        let change = Change::merge(db_cf.name(), &key[..], &val[..]);
+        db_cf.soy_db().changes().notify(env, &[change]);
+    }
+    ok_or_err!(res)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_cf_count_range(db_cf: SoyDbColFam, from: Option<Bin>, to: Option<Bin>) -> NifResult<usize> {
+    let mut opts = ReadOptions::default();
+    if let Some(to) = to {
+        opts.set_iterate_upper_bound(to.as_bytes().to_vec());
+    }
+    let (rdb, cf) = db_cf.lock()?;
+    let mut it = rdb.raw_iterator_cf_opt(&cf, opts);
+    match from {
+        Some(from) => it.seek(from.as_bytes()),
+        None => it.seek_to_first(),
+    }
+    let mut count = 0;
+    while it.valid() {
+        count += 1;
+        it.next();
+    }
+    Ok(count)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_cf_export(db_cf: SoyDbColFam, dir: BinStr) -> Result<SoyCfExport, Error> {
+    export::export_cf(&db_cf, &dir[..])
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_cf_metadata(db_cf: SoyDbColFam) -> NifResult<SoyCfMetadata> {
+    let (rdb, cf) = db_cf.lock()?;
+    Ok(cf_metadata::cf_metadata(&rdb, &cf, db_cf.name())?)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_delete_files_in_range(db: SoyDb, from: Binary, to: Binary) -> NifResult<Atom> {
+    ok_or_err!(db.rocks_db_ref()?.delete_file_in_range(&from[..], &to[..]))
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn db_cf_delete_files_in_range(db_cf: SoyDbColFam, from: Binary, to: Binary) -> NifResult<Atom> {
+    let (rdb, cf) = db_cf.lock()?;
+    ok_or_err!(rdb.delete_file_in_range_cf(&cf, &from[..], &to[..]))
 }
 
 #[rustler::nif]
-fn db_cf_key_may_exist(db_cf: SoyDbColFam, key: Binary) -> bool {
-    let handle = db_cf.handle();
-    db_cf.rocks_db_ref().key_may_exist_cf(handle, &key[..])
+fn db_cf_key_may_exist(db_cf: SoyDbColFam, key: Binary) -> NifResult<bool> {
+    let (rdb, cf) = db_cf.lock()?;
+    Ok(rdb.key_may_exist_cf(&cf, &key[..]))
 }
 
 #[rustler::nif]
-fn db_cf_has_key(db_cf: SoyDbColFam, key: Binary) -> bool {
-    let handle = db_cf.handle();
-    let may_exist = db_cf.rocks_db_ref().key_may_exist_cf(handle, &key[..]);
+fn db_cf_has_key(db_cf: SoyDbColFam, key: Binary) -> NifResult<bool> {
+    let (rdb, cf) = db_cf.lock()?;
+    let may_exist = rdb.key_may_exist_cf(&cf, &key[..]);
     if !may_exist {
-        return false;
+        return Ok(false);
     }
-    match db_cf.rocks_db_ref().get_cf(db_cf.handle(), &key[..]) {
-        Ok(Some(_)) => true,
-        Ok(None) => false,
+    match rdb.get_cf(&cf, &key[..]) {
+        Ok(Some(_)) => Ok(true),
+        Ok(None) => Ok(false),
         Err(e) => panic!("{}", e),
     }
 }
@@ -530,46 +1092,47 @@ fn path_list_cf(path: BinStr) -> Vec<String> {
 
 #[rustler::nif]
 fn db_drop_cf(db: SoyDb, name: BinStr) -> NifResult<Atom> {
-    ok_or_err!(db.rocks_db_ref().drop_cf(&name[..]))
+    db.drop_cf(&name[..])?;
+    Ok(atoms::ok())
 }
 
 #[rustler::nif]
-fn db_key_may_exist(db: SoyDb, key: Binary) -> bool {
-    db.rocks_db_ref().key_may_exist(&key[..])
+fn db_key_may_exist(db: SoyDb, key: Binary) -> NifResult<bool> {
+    Ok(db.rocks_db_ref()?.key_may_exist(&key[..]))
 }
 
 #[rustler::nif]
-fn db_has_key(db: SoyDb, key: Binary) -> bool {
-    let rdb = db.rocks_db_ref();
+fn db_has_key(db: SoyDb, key: Binary) -> NifResult<bool> {
+    let rdb = db.rocks_db_ref()?;
     let may_exist = rdb.key_may_exist(&key[..]);
     if !may_exist {
-        return false;
+        return Ok(false);
     }
     match rdb.get(&key[..]) {
-        Ok(Some(_)) => true,
-        Ok(None) => false,
+        Ok(Some(_)) => Ok(true),
+        Ok(None) => Ok(false),
         Err(e) => panic!("{}", e),
     }
 }
 
 #[rustler::nif]
-fn ss_iter<'a>(ss: SoySnapshot) -> SoyIter {
-    IterResource::from_ss(ss)
+fn ss_iter<'a>(ss: SoySnapshot) -> NifResult<SoyIter> {
+    Ok(IterResource::from_ss(ss)?)
 }
 
 #[rustler::nif]
-fn ss_cf_fetch(ss_cf: SoySsColFam, key: Binary) -> NifResult<(Atom, Bin)> {
-    let handle = ss_cf.handle();
-    match ss_cf.rocks_ss_ref().get_cf(handle, &key[..]) {
-        Ok(Some(v)) => Ok((atoms::ok(), Bin::from_vec(v))),
+fn ss_cf_fetch<'a>(env: Env<'a>, ss_cf: SoySsColFam, key: Binary) -> NifResult<(Atom, Binary<'a>)> {
+    let (_rdb, rss, cf) = ss_cf.lock()?;
+    match rss.get_cf(&cf, &key[..]) {
+        Ok(Some(v)) => Ok((atoms::ok(), vec_into_binary(v, env))),
         Ok(None) => Err(NifError::Atom("error")),
         Err(e) => panic!("error: {:?}", e),
     }
 }
 
 #[rustler::nif]
-fn ss_cf_iter<'a>(ss_cf: SoySsColFam) -> SoyIter {
-    IterResource::from_ss_cf(ss_cf)
+fn ss_cf_iter<'a>(ss_cf: SoySsColFam) -> NifResult<SoyIter> {
+    Ok(IterResource::from_ss_cf(ss_cf)?)
 }
 
 #[rustler::nif]
@@ -582,34 +1145,93 @@ fn ss_cf_into_ss(ss_cf: SoySsColFam) -> SoySnapshot {
     ss_cf.soy_snapshot().clone()
 }
 
-#[rustler::nif]
-fn ss_cf_multi_get<'a>(pairs: Vec<(SoySsColFam, Binary)>) -> Vec<Option<Bin>> {
+fn do_ss_cf_multi_get<'a>(
+    env: Env<'a>,
+    pairs: Vec<(SoySsColFam, Binary)>,
+) -> NifResult<Vec<Option<Binary<'a>>>> {
     if pairs.len() == 0 {
-        return vec![];
+        return Ok(vec![]);
     }
-    let ss_cf = pairs.first().unwrap().clone().0;
-    let rss = ss_cf.rocks_ss_ref();
-    let pairs_it = pairs.iter().map(|(h, k)| (h.handle(), &k[..]));
-    rss.multi_get_cf(pairs_it)
+    let first = &pairs.first().unwrap().0;
+    // the db lock keeps the column families from being dropped
+    let _rdb = first.soy_snapshot().soy_db().rocks_db_ref()?;
+    let rss = first.rocks_ss_ref()?;
+    let handles = pairs
+        .iter()
+        .map(|(h, _)| h.handle())
+        .collect::<Result<Vec<_>, Error>>()?;
+    let pairs_it = handles
+        .iter()
+        .zip(pairs.iter())
+        .map(|(h, (_, k))| (h, &k[..]));
+    Ok(rss
+        .multi_get_cf(pairs_it)
         .into_iter()
-        .map(|v| match v.unwrap() {
-            Some(data) => Some(Bin::from_vec(data)),
-            None => None,
-        })
-        .collect()
+        .map(|v| v.unwrap().map(|data| vec_into_binary(data, env)))
+        .collect())
 }
 
 #[rustler::nif]
-fn ss_multi_get<'a>(ss: SoySnapshot, keys: Vec<Binary>) -> Vec<Option<Bin>> {
-    let keys_it = keys.iter().map(|k| (&k[..]).to_vec());
-    ss.rocks_ss_ref()
+fn ss_cf_multi_get<'a>(
+    env: Env<'a>,
+    pairs: Vec<(SoySsColFam, Binary)>,
+) -> NifResult<Vec<Option<Binary<'a>>>> {
+    do_ss_cf_multi_get(env, pairs)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn ss_cf_multi_get_dirty<'a>(
+    env: Env<'a>,
+    pairs: Vec<(SoySsColFam, Binary)>,
+) -> NifResult<Vec<Option<Binary<'a>>>> {
+    do_ss_cf_multi_get(env, pairs)
+}
+
+fn do_ss_multi_get<'a>(
+    env: Env<'a>,
+    ss: SoySnapshot,
+    keys: Vec<Binary>,
+) -> NifResult<Vec<Option<Binary<'a>>>> {
+    let keys_it = keys.iter().map(|k| &k[..]);
+    Ok(ss
+        .rocks_ss_ref()?
         .multi_get(keys_it)
         .into_iter()
-        .map(|v| match v.unwrap() {
-            Some(data) => Some(Bin::from_vec(data)),
-            None => None,
-        })
-        .collect()
+        .map(|v| v.unwrap().map(|data| vec_into_binary(data, env)))
+        .collect())
+}
+
+#[rustler::nif]
+fn ss_multi_get<'a>(
+    env: Env<'a>,
+    ss: SoySnapshot,
+    keys: Vec<Binary>,
+) -> NifResult<Vec<Option<Binary<'a>>>> {
+    do_ss_multi_get(env, ss, keys)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn ss_multi_get_dirty<'a>(
+    env: Env<'a>,
+    ss: SoySnapshot,
+    keys: Vec<Binary>,
+) -> NifResult<Vec<Option<Binary<'a>>>> {
+    do_ss_multi_get(env, ss, keys)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn sst_writer_open(path: BinStr, open_opts: SoyOpenOpts) -> NifResult<SoySstWriter> {
+    Ok(SstWriterResource::open(&path[..], open_opts.into())?)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn sst_writer_add(writer: SoySstWriter, ops: Vec<SstOp>) -> NifResult<usize> {
+    Ok(writer.add(ops)?)
+}
+
+#[rustler::nif(schedule = "DirtyIo")]
+fn sst_writer_finish(writer: SoySstWriter) -> Result<SoySstFileInfo, Error> {
+    writer.finish()
 }
 
 #[rustler::nif]
@@ -661,6 +1283,10 @@ fn load(env: Env, _: Term) -> bool {
     rustler::resource!(IterResource, env);
     rustler::resource!(SnapshotResource, env);
     rustler::resource!(WalIterator, env);
+    rustler::resource!(SnapshotDiff, env);
+    rustler::resource!(VecResource, env);
+    rustler::resource!(SstWriterResource, env);
+    rustler::resource!(CheckpointStream, env);
     true
 }
 
@@ -672,22 +1298,37 @@ rustler::init!(
         path_repair,
         path_list_cf,
         path_open_db,
+        path_load_latest_options,
+        path_write_checkpoint_chunk,
+        path_finish_checkpoint,
         // db ops
         // backups
         db_checkpoint,
+        db_stream_checkpoint,
+        checkpoint_stream_next,
         db_path,
+        db_close,
         // write ops
         db_put,
         db_delete,
         db_batch,
+        db_batch_dirty,
+        db_subscribe_changes,
+        changes_unsubscribe,
+        db_count_change_subscriptions,
         db_merge,
+        // ingestion
+        db_ingest_external_file,
+        db_cf_ingest_external_file,
         // cf create/open/drop ops
         db_open_existing_cf,
         db_create_new_cf,
         db_drop_cf,
+        db_import_cf,
         // read ops
         db_fetch,
         db_multi_get,
+        db_multi_get_dirty,
         db_key_may_exist,
         db_has_key,
         // flushing/sync
@@ -699,14 +1340,40 @@ rustler::init!(
         db_snapshot,
         // db props/metadata/introspection
         db_get_property,
+        db_get_property_map,
+        db_get_approximate_sizes,
         db_list_properties,
         db_live_files,
+        db_delete_files_in_range,
+        // runtime options
+        db_set_options,
+        db_statistics,
+        db_reset_statistics,
         // snapshot ops
         ss_iter,
         // snapshot funcs
         ss_fetch,
         ss_multi_get,
+        ss_multi_get_dirty,
         ss_open_ss_cf,
+        ss_release,
+        ss_diff,
+        diff_next,
+        ss_sequence_number,
+        ss_created_at,
+        ss_dump,
+        db_load_dump,
+        // wal
+        db_latest_sequence_number,
+        db_oldest_available_sequence_number,
+        db_get_updates_since,
+        wal_iter_next,
+        wal_iter_next_batch,
+        wal_iter_take,
+        wal_iter_subscribe,
+        wal_iter_next_binary,
+        db_apply_batch_binary,
+        db_wal_cf_ids,
         // write_opts
         write_opts_default,
         // read_opts_default
@@ -716,7 +1383,10 @@ rustler::init!(
         iter_value,
         iter_key_value,
         iter_seek,
+        iter_seek_key,
+        iter_seek_key_size,
         iter_valid,
+        iter_close,
         // cf resource ops
         db_cf_put,
         db_cf_fetch,
@@ -727,14 +1397,28 @@ rustler::init!(
         db_cf_key_may_exist,
         db_cf_has_key,
         db_cf_multi_get,
+        db_cf_multi_get_dirty,
         db_cf_flush,
         db_cf_iter,
+        db_cf_count_range,
+        db_cf_export,
+        db_cf_get_property,
+        db_cf_get_property_map,
+        db_cf_get_approximate_sizes,
+        db_cf_metadata,
+        db_cf_delete_files_in_range,
+        db_cf_set_options,
         // cf_ss resource ops
         ss_cf_fetch,
         ss_cf_multi_get,
+        ss_cf_multi_get_dirty,
         ss_cf_iter,
         ss_cf_name,
         ss_cf_into_ss,
+        // sst writer
+        sst_writer_open,
+        sst_writer_add,
+        sst_writer_finish,
     ],
     load = load
 );
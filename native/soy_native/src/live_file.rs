@@ -1,7 +1,7 @@
 use rocksdb::LiveFile;
 use rustler::NifStruct;
 
-#[derive(Debug, NifStruct)]
+#[derive(Debug, Clone, NifStruct)]
 #[must_use] // Added to test Issue #152
 #[module = "Soy.LiveFile"]
 pub struct SoyLiveFile {
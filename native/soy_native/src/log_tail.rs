@@ -0,0 +1,203 @@
+use crate::atoms;
+use crate::events::Listener;
+use rocksdb::{LogLevel, DB as RocksDb};
+use rustler::env::OwnedEnv;
+use rustler::{Encoder, NifUnitEnum};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{RwLock, Weak};
+use std::thread;
+use std::time::Duration;
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, NifUnitEnum)]
+pub enum InfoLogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+    Fatal,
+    Header,
+}
+
+impl From<InfoLogLevel> for LogLevel {
+    fn from(level: InfoLogLevel) -> LogLevel {
+        match level {
+            InfoLogLevel::Debug => LogLevel::Debug,
+            InfoLogLevel::Info => LogLevel::Info,
+            InfoLogLevel::Warn => LogLevel::Warn,
+            InfoLogLevel::Error => LogLevel::Error,
+            InfoLogLevel::Fatal => LogLevel::Fatal,
+            InfoLogLevel::Header => LogLevel::Header,
+        }
+    }
+}
+
+/// The path of the info log RocksDB writes for the db at `db_path`.
+///
+/// Without a `db_log_dir` it is `LOG` inside the db dir. With one, the
+/// absolute db path is flattened into the file name the same way RocksDB's
+/// `InfoLogFileName` does it.
+pub fn info_log_path(db_path: &Path, db_log_dir: Option<&str>) -> PathBuf {
+    let log_dir = match db_log_dir {
+        Some(dir) if !dir.is_empty() => dir,
+        _ => return db_path.join("LOG"),
+    };
+    let abs_path = std::env::current_dir()
+        .map(|cwd| cwd.join(db_path))
+        .unwrap_or_else(|_| db_path.to_path_buf());
+    let mut prefix = String::new();
+    for (i, c) in abs_path.to_string_lossy().chars().enumerate() {
+        if c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_' {
+            prefix.push(c);
+        } else if i > 0 {
+            prefix.push('_');
+        }
+    }
+    Path::new(log_dir).join(format!("{}_LOG", prefix))
+}
+
+/// Splits a LOG line of the form `2022/01/01-00:00:00.000000 7f00 [WARN] msg`
+/// into its level and message. RocksDB does not tag info and header lines,
+/// so both are sent as info. Returns `None` for lines that continue the
+/// previous entry.
+fn parse_line(line: &str) -> Option<(InfoLogLevel, &str)> {
+    let mut parts = line.splitn(3, ' ');
+    let timestamp = parts.next()?;
+    let is_entry =
+        timestamp.len() > 11 && timestamp.as_bytes()[4] == b'/' && timestamp.as_bytes()[10] == b'-';
+    if !is_entry {
+        return None;
+    }
+    let _thread_id = parts.next()?;
+    let msg = parts.next().unwrap_or("");
+    let tags = [
+        ("[DEBUG] ", InfoLogLevel::Debug),
+        ("[WARN] ", InfoLogLevel::Warn),
+        ("[ERROR] ", InfoLogLevel::Error),
+        ("[FATAL] ", InfoLogLevel::Fatal),
+    ];
+    for (tag, level) in tags.iter() {
+        if let Some(rest) = msg.strip_prefix(tag) {
+            return Some((*level, rest));
+        }
+    }
+    Some((InfoLogLevel::Info, msg))
+}
+
+/// Identifies a LOG file so that a roll (rename and recreate) is noticed.
+#[cfg(unix)]
+fn file_id(meta: &fs::Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    meta.ino()
+}
+
+#[cfg(not(unix))]
+fn file_id(_meta: &fs::Metadata) -> u64 {
+    0
+}
+
+struct Tail {
+    path: PathBuf,
+    file: Option<(File, u64)>,
+    // bytes of an incomplete last line
+    pending: Vec<u8>,
+    entry: Option<(InfoLogLevel, String)>,
+}
+
+impl Tail {
+    /// Reads what was appended since the last call, following the file
+    /// across rolls, and returns the complete entries.
+    fn read(&mut self) -> Vec<(InfoLogLevel, String)> {
+        let meta = match fs::metadata(&self.path) {
+            Ok(meta) => meta,
+            Err(_) => return Vec::new(),
+        };
+        let rolled = match &self.file {
+            Some((_, id)) => *id != file_id(&meta),
+            None => true,
+        };
+        let mut entries = Vec::new();
+        if rolled {
+            // finish the old file before switching over
+            if let Some((mut file, _)) = self.file.take() {
+                let _ = file.read_to_end(&mut self.pending);
+                self.pending.push(b'\n');
+                self.split_lines(&mut entries);
+            }
+            self.pending.clear();
+            self.file = File::open(&self.path).ok().map(|f| (f, file_id(&meta)));
+        }
+        if let Some((file, _)) = self.file.as_mut() {
+            let _ = file.read_to_end(&mut self.pending);
+        }
+        self.split_lines(&mut entries);
+        entries.extend(self.entry.take());
+        entries
+    }
+
+    fn split_lines(&mut self, entries: &mut Vec<(InfoLogLevel, String)>) {
+        let end = match self.pending.iter().rposition(|b| *b == b'\n') {
+            Some(end) => end,
+            None => return,
+        };
+        let rest = self.pending.split_off(end + 1);
+        let complete = std::mem::replace(&mut self.pending, rest);
+        for line in String::from_utf8_lossy(&complete).lines() {
+            match (parse_line(line), self.entry.as_mut()) {
+                (Some((level, msg)), _) => {
+                    entries.extend(self.entry.replace((level, msg.to_string())));
+                }
+                (None, Some((_, msg))) => {
+                    msg.push('\n');
+                    msg.push_str(line);
+                }
+                (None, None) => {
+                    self.entry = Some((InfoLogLevel::Info, line.to_string()));
+                }
+            }
+        }
+    }
+}
+
+/// Tails the info log of the db and sends `{:soy_log, path, level, message}`
+/// messages to `listener` for every entry.
+///
+/// The RocksDB C API has no way to install a custom logger, so the LOG file
+/// is followed instead. RocksDB rolls the previous LOG away on open, so
+/// the entries start with the ones written while opening. The tail drains
+/// the file one last time and stops once the db is closed or garbage
+/// collected.
+pub fn spawn_tail(
+    rdb: Weak<RwLock<Option<RocksDb>>>,
+    path: String,
+    log_path: PathBuf,
+    listener: Listener,
+) {
+    thread::spawn(move || {
+        let mut env = OwnedEnv::new();
+        let mut tail = Tail {
+            path: log_path,
+            file: None,
+            pending: Vec::new(),
+            entry: None,
+        };
+        loop {
+            let open = match rdb.upgrade() {
+                Some(rdb) => rdb.read().unwrap().is_some(),
+                None => false,
+            };
+            for (level, msg) in tail.read() {
+                env.send_and_clear(&listener.0, |env| {
+                    (atoms::soy_log(), path.as_str(), level, msg.as_str()).encode(env)
+                });
+            }
+            if !open {
+                return;
+            }
+            thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
@@ -0,0 +1,178 @@
+use crate::db_col_fam::CfHandle;
+use crate::Error;
+use rocksdb::DB as RocksDb;
+use rustler::Term;
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Scope {
+    Cf,
+    Db,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Kind {
+    Bool,
+    Int,
+    // ints RocksDB keeps as `int`, where e.g. `max_open_files: -1` is valid
+    SignedInt,
+    Double,
+    Str,
+}
+
+/// The options RocksDB can change on an open db, as named in the OPTIONS
+/// file.
+const MUTABLE: &[(&str, Scope, Kind)] = &[
+    ("write_buffer_size", Scope::Cf, Kind::Int),
+    ("max_write_buffer_number", Scope::Cf, Kind::SignedInt),
+    ("arena_block_size", Scope::Cf, Kind::Int),
+    ("memtable_prefix_bloom_size_ratio", Scope::Cf, Kind::Double),
+    ("memtable_whole_key_filtering", Scope::Cf, Kind::Bool),
+    ("memtable_huge_page_size", Scope::Cf, Kind::Int),
+    ("max_successive_merges", Scope::Cf, Kind::Int),
+    ("inplace_update_num_locks", Scope::Cf, Kind::Int),
+    ("disable_auto_compactions", Scope::Cf, Kind::Bool),
+    ("soft_pending_compaction_bytes_limit", Scope::Cf, Kind::Int),
+    ("hard_pending_compaction_bytes_limit", Scope::Cf, Kind::Int),
+    (
+        "level0_file_num_compaction_trigger",
+        Scope::Cf,
+        Kind::SignedInt,
+    ),
+    ("level0_slowdown_writes_trigger", Scope::Cf, Kind::SignedInt),
+    ("level0_stop_writes_trigger", Scope::Cf, Kind::SignedInt),
+    ("max_compaction_bytes", Scope::Cf, Kind::Int),
+    ("target_file_size_base", Scope::Cf, Kind::Int),
+    ("target_file_size_multiplier", Scope::Cf, Kind::SignedInt),
+    ("max_bytes_for_level_base", Scope::Cf, Kind::Int),
+    ("max_bytes_for_level_multiplier", Scope::Cf, Kind::Double),
+    ("ttl", Scope::Cf, Kind::Int),
+    ("periodic_compaction_seconds", Scope::Cf, Kind::Int),
+    ("max_sequential_skip_in_iterations", Scope::Cf, Kind::Int),
+    ("paranoid_file_checks", Scope::Cf, Kind::Bool),
+    ("report_bg_io_stats", Scope::Cf, Kind::Bool),
+    ("compression", Scope::Cf, Kind::Str),
+    ("prefix_extractor", Scope::Cf, Kind::Str),
+    ("max_background_jobs", Scope::Db, Kind::SignedInt),
+    ("max_background_compactions", Scope::Db, Kind::SignedInt),
+    ("max_background_flushes", Scope::Db, Kind::SignedInt),
+    ("max_subcompactions", Scope::Db, Kind::Int),
+    ("avoid_flush_during_shutdown", Scope::Db, Kind::Bool),
+    ("writable_file_max_buffer_size", Scope::Db, Kind::Int),
+    ("delayed_write_rate", Scope::Db, Kind::Int),
+    ("max_total_wal_size", Scope::Db, Kind::Int),
+    ("delete_obsolete_files_period_micros", Scope::Db, Kind::Int),
+    ("stats_dump_period_sec", Scope::Db, Kind::Int),
+    ("stats_persist_period_sec", Scope::Db, Kind::Int),
+    ("stats_history_buffer_size", Scope::Db, Kind::Int),
+    ("max_open_files", Scope::Db, Kind::SignedInt),
+    ("bytes_per_sync", Scope::Db, Kind::Int),
+    ("wal_bytes_per_sync", Scope::Db, Kind::Int),
+    ("strict_bytes_per_sync", Scope::Db, Kind::Bool),
+    ("compaction_readahead_size", Scope::Db, Kind::Int),
+];
+
+/// Options that are only read when the db is opened.
+const IMMUTABLE: &[&str] = &[
+    "create_if_missing",
+    "create_missing_column_families",
+    "error_if_exists",
+    "paranoid_checks",
+    "use_fsync",
+    "db_log_dir",
+    "wal_dir",
+    "max_log_file_size",
+    "keep_log_file_num",
+    "log_file_time_to_roll",
+    "recycle_log_file_num",
+    "max_manifest_file_size",
+    "table_cache_numshardbits",
+    "wal_ttl_seconds",
+    "wal_size_limit_mb",
+    "manifest_preallocation_size",
+    "allow_mmap_reads",
+    "allow_mmap_writes",
+    "use_direct_reads",
+    "use_direct_io_for_flush_and_compaction",
+    "is_fd_close_on_exec",
+    "advise_random_on_open",
+    "db_write_buffer_size",
+    "use_adaptive_mutex",
+    "enable_pipelined_write",
+    "unordered_write",
+    "allow_concurrent_memtable_write",
+    "enable_write_thread_adaptive_yield",
+    "skip_stats_update_on_db_open",
+    "wal_recovery_mode",
+    "manual_wal_flush",
+    "atomic_flush",
+    "max_file_opening_threads",
+    "num_levels",
+    "compaction_style",
+    "comparator",
+    "merge_operator",
+    "compaction_filter",
+    "min_write_buffer_number_to_merge",
+    "max_write_buffer_number_to_maintain",
+    "max_write_buffer_size_to_maintain",
+    "inplace_update_support",
+    "level_compaction_dynamic_level_bytes",
+    "optimize_filters_for_hits",
+    "force_consistency_checks",
+    "table_factory",
+    "memtable_factory",
+];
+
+/// Checks `opts` against the column family options that can change at
+/// runtime and renders their values the way RocksDB parses them. The
+/// options are checked by name, so the error is the same for the same
+/// options.
+fn validate(opts: HashMap<String, Term>) -> Result<Vec<(String, String)>, Error> {
+    let mut opts: Vec<(String, Term)> = opts.into_iter().collect();
+    opts.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let mut validated = Vec::with_capacity(opts.len());
+    for (name, value) in opts {
+        let (opt_scope, kind) = match MUTABLE.iter().find(|(n, _, _)| *n == name) {
+            Some((_, opt_scope, kind)) => (*opt_scope, *kind),
+            None if IMMUTABLE.contains(&&name[..]) => return Err(Error::ImmutableOption(name)),
+            None => return Err(Error::UnknownOption(name)),
+        };
+        if opt_scope == Scope::Db {
+            return Err(Error::NotCfOption(name));
+        }
+        let rendered = match kind {
+            Kind::Bool => value.decode::<bool>().map(|b| b.to_string()),
+            Kind::Int => value.decode::<u64>().map(|i| i.to_string()),
+            Kind::SignedInt => value.decode::<i64>().map(|i| i.to_string()),
+            Kind::Double => value
+                .decode::<f64>()
+                .or_else(|_| value.decode::<u64>().map(|i| i as f64))
+                .map(|d| d.to_string()),
+            Kind::Str => value.decode::<String>(),
+        };
+        match rendered {
+            Ok(rendered) => validated.push((name, rendered)),
+            Err(_) => return Err(Error::InvalidOptionValue(name)),
+        }
+    }
+    Ok(validated)
+}
+
+fn as_strs(opts: &[(String, String)]) -> Vec<(&str, &str)> {
+    opts.iter().map(|(k, v)| (&k[..], &v[..])).collect()
+}
+
+/// Sets mutable column family options of `cf` (or the default column
+/// family). All options are applied at once or none are.
+pub fn set_cf_options(
+    db: &RocksDb,
+    cf: Option<&CfHandle>,
+    opts: HashMap<String, Term>,
+) -> Result<(), Error> {
+    let opts = validate(opts)?;
+    match cf {
+        Some(cf) => db.set_options_cf(cf, &as_strs(&opts))?,
+        None => db.set_options(&as_strs(&opts))?,
+    }
+    Ok(())
+}
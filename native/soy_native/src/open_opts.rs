@@ -1,7 +1,14 @@
-use rocksdb::{DBCompactionStyle, Options};
+use rocksdb::{
+    DBCompactionStyle, FifoCompactOptions, Options, UniversalCompactOptions,
+    UniversalCompactionStopStyle,
+};
 use rustler::{NifStruct, NifUnitEnum};
 
+use crate::events::{Listener, Polling, DEFAULT_POLL_INTERVAL_MS};
+use crate::log_tail::InfoLogLevel;
 use crate::merger;
+use std::collections::HashMap;
+use std::time::Duration;
 
 #[derive(Debug, NifStruct)]
 #[must_use] // Added to test Issue #152
@@ -24,6 +31,79 @@ pub struct SoyOpenOpts {
     set_compaction_style: Option<CompactionStyle>,
     set_merge_operator_associative: Option<(String, merger::MergeOperator)>,
     prefix_length: Option<usize>,
+    enable_statistics: Option<bool>,
+    event_listener: Option<Listener>,
+    event_poll_interval_ms: Option<u64>,
+    event_table_files: Option<bool>,
+    set_log_level: Option<InfoLogLevel>,
+    set_max_log_file_size: Option<usize>,
+    set_keep_log_file_num: Option<usize>,
+    set_db_log_dir: Option<String>,
+    log_listener: Option<Listener>,
+    column_families: Option<HashMap<String, SoyOpenOpts>>,
+    increase_parallelism: Option<i32>,
+    set_max_background_jobs: Option<i32>,
+    set_wal_dir: Option<String>,
+    set_wal_ttl_seconds: Option<u64>,
+    set_wal_size_limit_mb: Option<u64>,
+    set_max_total_wal_size: Option<u64>,
+    set_atomic_flush: Option<bool>,
+    set_paranoid_checks: Option<bool>,
+    set_ratelimiter: Option<(i64, i64, i32)>,
+    set_delete_obsolete_files_period_micros: Option<u64>,
+    set_level_compaction_dynamic_level_bytes: Option<bool>,
+    set_max_bytes_for_level_base: Option<u64>,
+    set_max_bytes_for_level_multiplier: Option<f64>,
+    set_universal_compaction_options: Option<SoyUniversalCompactOpts>,
+    set_fifo_compaction_options: Option<SoyFifoCompactOpts>,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Soy.UniversalCompactOpts"]
+pub struct SoyUniversalCompactOpts {
+    set_size_ratio: Option<i32>,
+    set_min_merge_width: Option<i32>,
+    set_max_merge_width: Option<i32>,
+    set_max_size_amplification_percent: Option<i32>,
+    set_compression_size_percent: Option<i32>,
+    set_stop_style: Option<StopStyle>,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Soy.FifoCompactOpts"]
+pub struct SoyFifoCompactOpts {
+    set_max_table_files_size: Option<u64>,
+}
+
+impl SoyOpenOpts {
+    /// Takes the event listener out of the opts as it is not a RocksDB
+    /// option.
+    pub fn take_event_listener(&mut self) -> Option<Polling> {
+        let interval = self
+            .event_poll_interval_ms
+            .unwrap_or(DEFAULT_POLL_INTERVAL_MS);
+        let table_files = self.event_table_files.unwrap_or(false);
+        self.event_listener.take().map(|listener| Polling {
+            listener,
+            interval: Duration::from_millis(interval),
+            table_files,
+        })
+    }
+
+    /// Takes the options of individual column families out of the opts.
+    /// Column families without an entry are opened with default options.
+    pub fn take_column_families(&mut self) -> HashMap<String, SoyOpenOpts> {
+        self.column_families.take().unwrap_or_default()
+    }
+
+    /// Takes the log listener out of the opts along with the `db_log_dir`
+    /// the LOG file is written to.
+    pub fn take_log_listener(&mut self) -> Option<(Listener, Option<String>)> {
+        let db_log_dir = self.set_db_log_dir.clone();
+        self.log_listener
+            .take()
+            .map(|listener| (listener, db_log_dir))
+    }
 }
 
 macro_rules! set_opt {
@@ -49,11 +129,47 @@ impl From<SoyOpenOpts> for Options {
         set_opt!(opts, oc, set_target_file_size_base);
         set_opt!(opts, oc, set_min_write_buffer_number_to_merge);
         set_opt!(opts, oc, set_level_zero_stop_writes_trigger);
-        set_opt!(opts, oc, set_level_zero_stop_writes_trigger);
+        set_opt!(opts, oc, set_level_zero_slowdown_writes_trigger);
         set_opt!(opts, oc, set_disable_auto_compactions);
+        // before max_background_jobs, which it also sets
+        set_opt!(opts, oc, increase_parallelism);
+        set_opt!(opts, oc, set_max_background_jobs);
+        set_opt!(opts, oc, set_wal_ttl_seconds);
+        set_opt!(opts, oc, set_wal_size_limit_mb);
+        set_opt!(opts, oc, set_max_total_wal_size);
+        set_opt!(opts, oc, set_atomic_flush);
+        set_opt!(opts, oc, set_paranoid_checks);
+        set_opt!(opts, oc, set_delete_obsolete_files_period_micros);
+        set_opt!(opts, oc, set_level_compaction_dynamic_level_bytes);
+        set_opt!(opts, oc, set_max_bytes_for_level_base);
+        set_opt!(opts, oc, set_max_bytes_for_level_multiplier);
+        if let Some(dir) = oc.set_wal_dir {
+            opts.set_wal_dir(dir)
+        }
+        if let Some((rate_bytes_per_sec, refill_period_us, fairness)) = oc.set_ratelimiter {
+            opts.set_ratelimiter(rate_bytes_per_sec, refill_period_us, fairness)
+        }
+        if let Some(uco) = oc.set_universal_compaction_options {
+            opts.set_universal_compaction_options(&uco.into())
+        }
+        if let Some(fco) = oc.set_fifo_compaction_options {
+            opts.set_fifo_compaction_options(&fco.into())
+        }
+        set_opt!(opts, oc, set_max_log_file_size);
+        set_opt!(opts, oc, set_keep_log_file_num);
+        if let Some(level) = oc.set_log_level {
+            opts.set_log_level(level.into())
+        }
+        if let Some(dir) = oc.set_db_log_dir {
+            opts.set_db_log_dir(dir)
+        }
         if let Some(style) = oc.set_compaction_style {
             opts.set_compaction_style(style.into())
         }
+        if let Some(true) = oc.enable_statistics {
+            // the C API cannot set the stats level, so the default one is used
+            opts.enable_statistics();
+        }
         if let Some(len) = oc.prefix_length {
             let prefix_extractor = rocksdb::SliceTransform::create_fixed_prefix(len);
             opts.set_prefix_extractor(prefix_extractor);
@@ -82,3 +198,41 @@ impl From<CompactionStyle> for DBCompactionStyle {
         }
     }
 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, NifUnitEnum)]
+enum StopStyle {
+    Similar,
+    Total,
+}
+
+impl From<StopStyle> for UniversalCompactionStopStyle {
+    fn from(s: StopStyle) -> UniversalCompactionStopStyle {
+        match s {
+            StopStyle::Similar => UniversalCompactionStopStyle::Similar,
+            StopStyle::Total => UniversalCompactionStopStyle::Total,
+        }
+    }
+}
+
+impl From<SoyUniversalCompactOpts> for UniversalCompactOptions {
+    fn from(uc: SoyUniversalCompactOpts) -> UniversalCompactOptions {
+        let mut opts = UniversalCompactOptions::default();
+        set_opt!(opts, uc, set_size_ratio);
+        set_opt!(opts, uc, set_min_merge_width);
+        set_opt!(opts, uc, set_max_merge_width);
+        set_opt!(opts, uc, set_max_size_amplification_percent);
+        set_opt!(opts, uc, set_compression_size_percent);
+        if let Some(style) = uc.set_stop_style {
+            opts.set_stop_style(style.into())
+        }
+        opts
+    }
+}
+
+impl From<SoyFifoCompactOpts> for FifoCompactOptions {
+    fn from(fc: SoyFifoCompactOpts) -> FifoCompactOptions {
+        let mut opts = FifoCompactOptions::default();
+        set_opt!(opts, fc, set_max_table_files_size);
+        opts
+    }
+}
@@ -0,0 +1,90 @@
+use crate::Error;
+use rustler::NifMap;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The sections of a persisted OPTIONS file with their raw values.
+#[derive(Debug, NifMap)]
+pub struct PersistedOptions {
+    file: String,
+    db: HashMap<String, String>,
+    column_families: HashMap<String, HashMap<String, String>>,
+}
+
+/// The `OPTIONS-NNNNNN` file with the highest number in the db dir.
+fn latest_options_file(path: &Path) -> Result<PathBuf, Error> {
+    let mut latest: Option<(u64, PathBuf)> = None;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let number = name
+            .to_str()
+            .and_then(|n| n.strip_prefix("OPTIONS-"))
+            .filter(|n| !n.ends_with(".dbtmp"))
+            .and_then(|n| n.parse::<u64>().ok());
+        if let Some(number) = number {
+            if latest.as_ref().map_or(true, |(max, _)| number > *max) {
+                latest = Some((number, entry.path()));
+            }
+        }
+    }
+    latest
+        .map(|(_, file)| file)
+        .ok_or_else(|| Error::OptionsFileNotFound(path.to_string_lossy().into_owned()))
+}
+
+/// Parses the INI-like OPTIONS file format:
+///
+/// ```text
+/// [DBOptions]
+///   max_open_files=-1
+/// [CFOptions "default"]
+///   write_buffer_size=67108864
+/// ```
+///
+/// Only the `DBOptions` and `CFOptions` sections are kept.
+fn parse(
+    text: &str,
+) -> (
+    HashMap<String, String>,
+    HashMap<String, HashMap<String, String>>,
+) {
+    let mut db = HashMap::new();
+    let mut cfs: HashMap<String, HashMap<String, String>> = HashMap::new();
+    let mut section: Option<&mut HashMap<String, String>> = None;
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line.starts_with('[') {
+            section = if line == "[DBOptions]" {
+                Some(&mut db)
+            } else if let Some(name) = line
+                .strip_prefix("[CFOptions \"")
+                .and_then(|rest| rest.strip_suffix("\"]"))
+            {
+                Some(cfs.entry(name.to_string()).or_default())
+            } else {
+                None
+            };
+            continue;
+        }
+        if let (Some(section), Some((key, value))) = (section.as_mut(), line.split_once('=')) {
+            section.insert(key.trim().to_string(), value.trim().to_string());
+        }
+    }
+    (db, cfs)
+}
+
+/// Loads the options RocksDB persisted last for the db at `path`.
+pub fn load_latest(path: &str) -> Result<PersistedOptions, Error> {
+    let file = latest_options_file(Path::new(path))?;
+    let (db, column_families) = parse(&fs::read_to_string(&file)?);
+    Ok(PersistedOptions {
+        file: file.to_string_lossy().into_owned(),
+        db,
+        column_families,
+    })
+}
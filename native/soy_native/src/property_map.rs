@@ -0,0 +1,94 @@
+use rustler::NifUntaggedEnum;
+use std::collections::HashMap;
+
+#[derive(Debug, NifUntaggedEnum)]
+pub enum PropValue {
+    Int(i64),
+    Float(f64),
+    String(String),
+    Map(HashMap<String, PropValue>),
+}
+
+impl PropValue {
+    fn parse(val: &str) -> PropValue {
+        let val = val.trim();
+        if let Ok(int) = val.parse::<i64>() {
+            return PropValue::Int(int);
+        }
+        if let Ok(float) = val.parse::<f64>() {
+            return PropValue::Float(float);
+        }
+        PropValue::String(val.to_string())
+    }
+}
+
+/// Parses the text of a map-valued property.
+///
+/// Table properties (e.g. `rocksdb.aggregated-table-properties`) are a
+/// single line of `key=value; ` pairs. Stats (e.g. `rocksdb.cfstats`) are
+/// made of `** Compaction Stats [cf] **` tables, parsed into a map of row
+/// (`"L0"`, `"Sum"`, ...) to column (`"Files"`, `"W-Amp"`, ...), and of
+/// `Key: value` lines which are kept as text.
+pub fn parse(text: &str) -> HashMap<String, PropValue> {
+    if !text.trim_end().contains('\n') && text.contains('=') {
+        return parse_pairs(text);
+    }
+    let mut map = HashMap::new();
+    let mut tables = HashMap::new();
+    let mut lines = text.lines();
+    while let Some(line) = lines.next() {
+        if line.starts_with("** ") {
+            if let Some(header) = lines.next() {
+                parse_table(header, &mut lines, &mut tables);
+            }
+            continue;
+        }
+        if let Some((key, val)) = line.split_once(": ") {
+            map.insert(key.trim().to_string(), PropValue::parse(val));
+        }
+    }
+    if !tables.is_empty() {
+        map.insert("compaction_stats".to_string(), PropValue::Map(tables));
+    }
+    map
+}
+
+fn parse_pairs(text: &str) -> HashMap<String, PropValue> {
+    text.split(';')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(key, val)| (key.trim().to_string(), PropValue::parse(val)))
+        .collect()
+}
+
+/// Reads table rows until a blank line. Sizes are printed as two tokens,
+/// e.g. `1.00 KB`, which are joined back into one column.
+fn parse_table<'a>(
+    header: &str,
+    lines: &mut impl Iterator<Item = &'a str>,
+    tables: &mut HashMap<String, PropValue>,
+) {
+    let columns: Vec<&str> = header.split_whitespace().skip(1).collect();
+    for line in lines {
+        if line.trim().is_empty() {
+            break;
+        }
+        if line.starts_with('-') {
+            continue;
+        }
+        let mut tokens: Vec<String> = line.split_whitespace().map(str::to_string).collect();
+        if tokens.len() == columns.len() + 2 {
+            let unit = tokens.remove(3);
+            tokens[2] = format!("{} {}", tokens[2], unit);
+        }
+        if tokens.len() != columns.len() + 1 {
+            continue;
+        }
+        let row = tokens.remove(0);
+        let cells = columns
+            .iter()
+            .zip(tokens.iter())
+            .map(|(col, val)| (col.to_string(), PropValue::parse(val)))
+            .collect();
+        tables.insert(row, PropValue::Map(cells));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::Error;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard, Weak};
+
+/// RocksDB state that can be freed before the BEAM garbage collects the
+/// resource that owns it.
+pub trait Release: Send + Sync {
+    fn release(&self, reason: &Error);
+}
+
+/// Holds a RocksDB object until it is released.
+///
+/// Once released the object is dropped and every later access returns
+/// the `Error` that was given as the reason for the release.
+pub struct Slot<T> {
+    inner: RwLock<Result<T, Error>>,
+}
+
+impl<T> Slot<T> {
+    pub fn new(val: T) -> Arc<Slot<T>> {
+        Arc::new(Slot {
+            inner: RwLock::new(Ok(val)),
+        })
+    }
+
+    pub fn read(&self) -> Result<SlotRef<T>, Error> {
+        let guard = self.inner.read().unwrap();
+        if let Err(e) = &*guard {
+            return Err(e.clone());
+        }
+        Ok(SlotRef(guard))
+    }
+
+    pub fn write(&self) -> Result<SlotMut<T>, Error> {
+        let guard = self.inner.write().unwrap();
+        if let Err(e) = &*guard {
+            return Err(e.clone());
+        }
+        Ok(SlotMut(guard))
+    }
+}
+
+impl<T: Send + Sync> Release for Slot<T> {
+    fn release(&self, reason: &Error) {
+        let mut guard = self.inner.write().unwrap();
+        if guard.is_ok() {
+            *guard = Err(reason.clone());
+        }
+    }
+}
+
+pub struct SlotRef<'a, T>(RwLockReadGuard<'a, Result<T, Error>>);
+
+impl<T> Deref for SlotRef<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match &*self.0 {
+            Ok(val) => val,
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+pub struct SlotMut<'a, T>(RwLockWriteGuard<'a, Result<T, Error>>);
+
+impl<T> Deref for SlotMut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        match &*self.0 {
+            Ok(val) => val,
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+impl<T> DerefMut for SlotMut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        match &mut *self.0 {
+            Ok(val) => val,
+            Err(_) => unreachable!(),
+        }
+    }
+}
+
+/// Weak references to the slots created from a resource so they can all
+/// be released together, e.g. when the db they borrow from is closed.
+#[derive(Default)]
+pub struct Children {
+    items: Mutex<Vec<Weak<dyn Release>>>,
+}
+
+impl Children {
+    pub fn register<T: Release + 'static>(&self, child: &Arc<T>) {
+        let weak: Weak<dyn Release> = Arc::downgrade(child);
+        let mut items = self.items.lock().unwrap();
+        items.retain(|item| item.strong_count() > 0);
+        items.push(weak);
+    }
+
+    pub fn release_all(&self, reason: &Error) {
+        let items = std::mem::take(&mut *self.items.lock().unwrap());
+        for item in items {
+            if let Some(child) = item.upgrade() {
+                child.release(reason);
+            }
+        }
+    }
+}
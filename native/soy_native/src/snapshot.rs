@@ -1,9 +1,22 @@
-use crate::SoyDb;
+use crate::release::{Children, Release, Slot, SlotRef};
+use crate::{Error, SoyDb};
 use rocksdb::Snapshot as RSnapshot;
 use rustler::ResourceArc;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// attempts at reading an exact sequence number under concurrent writes
+const SEQ_ATTEMPTS: usize = 8;
+
+pub type RocksSnapshot = RSnapshot<'static>;
 
 pub struct SnapshotResource {
-    rss: RSnapshot<'static>,
+    rss: Arc<Slot<RocksSnapshot>>,
+    // the iterators reading the snapshot
+    children: Children,
+    seq: u64,
+    // unix time in milliseconds
+    created_at: u64,
     _db: SoyDb,
 }
 
@@ -12,9 +25,34 @@ unsafe fn extend_lifetime_rss<'b>(r: RSnapshot<'b>) -> RSnapshot<'static> {
 }
 
 impl SnapshotResource {
-    pub fn new(db: SoyDb) -> ResourceArc<SnapshotResource> {
-        let rss = unsafe { extend_lifetime_rss(db.rocks_db_ref().snapshot()) };
-        ResourceArc::new(SnapshotResource { rss, _db: db })
+    pub fn new(db: SoyDb) -> Result<ResourceArc<SnapshotResource>, Error> {
+        let rdb = db.rocks_db_ref()?;
+        // The C API cannot read the sequence number of a snapshot. The
+        // latest one is the snapshot's if nothing was written while taking
+        // it. Otherwise the lower bound is kept, so reading the WAL after
+        // the snapshot can repeat writes but never miss any.
+        let mut attempt = 0;
+        let (ss, seq) = loop {
+            let seq = rdb.latest_sequence_number();
+            let ss = rdb.snapshot();
+            attempt += 1;
+            if rdb.latest_sequence_number() == seq || attempt == SEQ_ATTEMPTS {
+                break (ss, seq);
+            }
+        };
+        let rss = Slot::new(unsafe { extend_lifetime_rss(ss) });
+        db.register(&rss);
+        drop(rdb);
+        let created_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as u64);
+        Ok(ResourceArc::new(SnapshotResource {
+            rss,
+            children: Children::default(),
+            seq,
+            created_at,
+            _db: db,
+        }))
     }
 
     pub fn soy_db(&self) -> &SoyDb {
@@ -25,7 +63,33 @@ impl SnapshotResource {
     //     self.db.rocks_db_ref()
     // }
 
-    pub fn rocks_ss_ref(&self) -> &RSnapshot {
-        &self.rss
+    pub fn rocks_ss_ref(&self) -> Result<SlotRef<RocksSnapshot>, Error> {
+        self.rss.read()
+    }
+
+    /// The sequence number of the last write the snapshot sees.
+    pub fn sequence_number(&self) -> u64 {
+        self.seq
+    }
+
+    /// When the snapshot was taken, in milliseconds since the unix epoch.
+    pub fn created_at(&self) -> u64 {
+        self.created_at
+    }
+
+    /// Registers an iterator reading the snapshot so that it is released
+    /// with the snapshot.
+    ///
+    /// The snapshot may have been released while the iterator was created,
+    /// so callers must check `rocks_ss_ref` again after registering.
+    pub fn register<T: Release + 'static>(&self, child: &Arc<T>) {
+        self.children.register(child)
+    }
+
+    /// Releases the snapshot and its iterators without waiting for garbage
+    /// collection. Later use returns `Error::Released`.
+    pub fn release(&self) {
+        self.children.release_all(&Error::Released);
+        self.rss.release(&Error::Released);
     }
 }
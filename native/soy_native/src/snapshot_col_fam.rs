@@ -1,39 +1,50 @@
+use crate::db_col_fam::CfHandle;
+use crate::release::{Slot, SlotRef};
+use crate::snapshot::RocksSnapshot;
+use crate::soy_db::DbRef;
 use crate::{Error, SoySnapshot};
-use rocksdb::{ColumnFamilyRef, Snapshot as RSnapshot, DB as RocksDb};
 use rustler::ResourceArc;
+use std::sync::Arc;
 
 pub type SoySsColFam = ResourceArc<SsColFamResource>;
 
 pub struct SsColFamResource {
     ss: SoySnapshot,
     cf_name: String,
-    cf_ref: ColumnFamilyRef<'static>,
+    cf_ref: Arc<Slot<CfHandle>>,
 }
 
-unsafe impl Send for SsColFamResource {}
-unsafe impl Sync for SsColFamResource {}
-
 impl SsColFamResource {
     pub fn new(ss: &SoySnapshot, name: &str) -> Result<SsColFamResource, Error> {
-        let db_ref = ss.soy_db().rocks_db_ref();
-        let cf_ref = get_cf_handle(db_ref, name)?;
+        let db = ss.soy_db();
+        let rdb = db.rocks_db_ref()?;
+        let cf_ref = CfHandle::open(db, &rdb, name)?;
         Ok(SsColFamResource {
             ss: ss.clone(),
             cf_name: name.to_owned(),
-            cf_ref: unsafe { extend_lifetime_cf(cf_ref) },
+            cf_ref,
         })
     }
 
-    pub fn handle<'a>(&'a self) -> &'a ColumnFamilyRef<'a> {
-        unsafe { unextend_lifetime_cf(&self.cf_ref) }
+    pub fn handle(&self) -> Result<CfHandle, Error> {
+        self.cf_ref.read().map(|h| h.clone())
     }
 
     // pub fn rocks_db_ref(&self) -> &RocksDb {
     //     &self.ss.rocks_db_ref()
     // }
 
-    pub fn rocks_ss_ref(&self) -> &RSnapshot {
-        &self.ss.rocks_ss_ref()
+    pub fn rocks_ss_ref(&self) -> Result<SlotRef<RocksSnapshot>, Error> {
+        self.ss.rocks_ss_ref()
+    }
+
+    /// Read-locks the db and the snapshot and takes the cf handle. The db
+    /// lock keeps the column family from being dropped while it is used.
+    pub fn lock(&self) -> Result<(DbRef, SlotRef<RocksSnapshot>, CfHandle), Error> {
+        let rdb = self.ss.soy_db().rocks_db_ref()?;
+        let rss = self.rocks_ss_ref()?;
+        let handle = self.handle()?;
+        Ok((rdb, rss, handle))
     }
 
     pub fn soy_snapshot(&self) -> &SoySnapshot {
@@ -44,21 +55,3 @@ impl SsColFamResource {
         &self.cf_name[..]
     }
 }
-
-pub(crate) fn get_cf_handle<'a>(
-    rdb: &'a RocksDb,
-    name: &str,
-) -> Result<ColumnFamilyRef<'a>, Error> {
-    match rdb.cf_handle(name) {
-        Some(cf_handle) => Ok(cf_handle),
-        None => Err(Error::ColumnFamilyDoesNotExist(name.to_string())),
-    }
-}
-
-unsafe fn extend_lifetime_cf<'b>(s: ColumnFamilyRef<'b>) -> ColumnFamilyRef<'static> {
-    std::mem::transmute::<ColumnFamilyRef<'b>, ColumnFamilyRef<'static>>(s)
-}
-
-unsafe fn unextend_lifetime_cf<'b>(r: &ColumnFamilyRef<'static>) -> &'b ColumnFamilyRef<'b> {
-    std::mem::transmute::<&ColumnFamilyRef<'static>, &ColumnFamilyRef<'b>>(r)
-}
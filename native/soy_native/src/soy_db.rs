@@ -1,18 +1,232 @@
-use rocksdb::DB as RocksDb;
+use crate::changes::Subscriptions;
+use crate::events::{self, Listener, Polling};
+use crate::log_tail;
+use crate::release::{Children, Release};
+use crate::statistics::SoyStatistics;
+use crate::wal;
+use crate::Error;
+use rocksdb::{Options, DB as RocksDb, DEFAULT_COLUMN_FAMILY_NAME};
 use rustler::ResourceArc;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
 
 pub type SoyDb = ResourceArc<DbResource>;
 
 pub struct DbResource {
-    rdb: RocksDb,
+    rdb: Arc<RwLock<Option<RocksDb>>>,
+    children: Children,
+    // the children that borrow a column family, by name
+    cf_children: Mutex<HashMap<String, Children>>,
+    // the column family names by WAL id, read once the cfs changed
+    cf_names: Mutex<Option<Arc<HashMap<u32, String>>>>,
+    // the options the db was opened with; they own its statistics
+    opts: Options,
+    stats_baseline: Mutex<Option<SoyStatistics>>,
+    // serializes the ordering check and write of replicated batches
+    apply_lock: Mutex<()>,
+    changes: Arc<Subscriptions>,
+}
+
+/// A read lock on an open db. While any `DbRef` is alive the db cannot be
+/// closed.
+pub struct DbRef<'a>(RwLockReadGuard<'a, Option<RocksDb>>);
+
+impl Deref for DbRef<'_> {
+    type Target = RocksDb;
+
+    fn deref(&self) -> &RocksDb {
+        self.0.as_ref().unwrap()
+    }
 }
 
 impl DbResource {
-    pub fn new(rdb: RocksDb) -> SoyDb {
-        ResourceArc::new(DbResource { rdb })
+    pub fn new(rdb: RocksDb, opts: Options) -> SoyDb {
+        ResourceArc::new(DbResource {
+            rdb: Arc::new(RwLock::new(Some(rdb))),
+            children: Children::default(),
+            cf_children: Mutex::new(HashMap::new()),
+            cf_names: Mutex::new(None),
+            opts,
+            stats_baseline: Mutex::new(None),
+            apply_lock: Mutex::new(()),
+            changes: Arc::default(),
+        })
+    }
+
+    pub fn rocks_db_ref(&self) -> Result<DbRef, Error> {
+        let guard = self.rdb.read().unwrap();
+        if guard.is_none() {
+            return Err(Error::Closed);
+        }
+        Ok(DbRef(guard))
+    }
+
+    /// Registers RocksDB state borrowed from this db (iterators, snapshots,
+    /// cf handles, ...) so that it is released before the db is closed.
+    ///
+    /// Must be called while holding the `DbRef` the state was created from,
+    /// otherwise the db could be closed in between.
+    pub fn register<T: Release + 'static>(&self, child: &Arc<T>) {
+        self.children.register(child)
+    }
+
+    /// Registers state that borrows the column family `name` so that it is
+    /// also released when the column family is dropped. The same rules as
+    /// for `register` apply.
+    pub fn register_cf<T: Release + 'static>(&self, name: &str, child: &Arc<T>) {
+        self.register(child);
+        self.cf_children
+            .lock()
+            .unwrap()
+            .entry(name.to_string())
+            .or_default()
+            .register(child)
+    }
+
+    /// The names of the column families by their WAL ids. They are read
+    /// from the MANIFEST once and again after the column families changed.
+    pub fn cf_names(&self, rdb: &RocksDb) -> Result<Arc<HashMap<u32, String>>, Error> {
+        let mut cached = self.cf_names.lock().unwrap();
+        if let Some(names) = cached.as_ref() {
+            return Ok(names.clone());
+        }
+        let names = Arc::new(wal::cf_names(rdb)?);
+        *cached = Some(names.clone());
+        Ok(names)
+    }
+
+    /// Creates or drops column families of `rdb`, which must be this db,
+    /// through `f` so that `cf_names` reads them again afterwards.
+    pub fn update_cfs<T>(&self, f: impl FnOnce() -> Result<T, Error>) -> Result<T, Error> {
+        // held throughout, so no stale names can be cached in between
+        let mut cached = self.cf_names.lock().unwrap();
+        let updated = f();
+        *cached = None;
+        updated
+    }
+
+    /// Creates the column family `name` in `rdb`, which must be this db.
+    pub fn create_cf(&self, rdb: &RocksDb, name: &str, opts: &Options) -> Result<(), Error> {
+        self.update_cfs(|| Ok(rdb.create_cf(name, opts)?))
+    }
+
+    /// Releases everything borrowed from the column family `name` and drops
+    /// it. Later use of its handles and iterators returns
+    /// `Error::ColumnFamilyDropped`.
+    ///
+    /// Cf handles are only used while the db is locked, so taking the write
+    /// lock guarantees none is in use when RocksDB frees them.
+    pub fn drop_cf(&self, name: &str) -> Result<(), Error> {
+        let guard = self.rdb.write().unwrap();
+        let rdb = guard.as_ref().ok_or(Error::Closed)?;
+        // RocksDB refuses to drop the default column family
+        if name != DEFAULT_COLUMN_FAMILY_NAME && rdb.cf_handle(name).is_some() {
+            let children = self.cf_children.lock().unwrap().remove(name);
+            if let Some(children) = children {
+                children.release_all(&Error::ColumnFamilyDropped(name.to_string()));
+            }
+        }
+        self.update_cfs(|| Ok(rdb.drop_cf(name)?))
+    }
+
+    /// Writes a raw `WriteBatch` read from the WAL of a primary db and
+    /// returns the latest sequence number afterwards.
+    ///
+    /// The batch must start right after the latest sequence number of this
+    /// db, which holds for a follower that began as a checkpoint of the
+    /// primary, or as an empty db, and is written to by nothing else.
+    /// Otherwise it returns `Error::OutOfOrderBatch` with the sequence
+    /// number the next batch has to start at.
+    ///
+    /// The WAL names column families by id, so the batch is only written
+    /// if the column families it writes to have the same ids here as in
+    /// `primary_cf_ids`. Otherwise it returns `Error::ColumnFamilyMismatch`.
+    pub fn apply_batch(
+        &self,
+        rep: &[u8],
+        primary_cf_ids: &HashMap<String, u32>,
+    ) -> Result<u64, Error> {
+        if wal::rep_count(rep) == 0 {
+            return Err(Error::RocksDbError("malformed WriteBatch".to_string()));
+        }
+        let rows = wal::decode_rep(rep)?;
+        let rdb = self.rocks_db_ref()?;
+        wal::check_cf_ids(&self.cf_names(&rdb)?, &rows, primary_cf_ids)?;
+        let _apply = self.apply_lock.lock().unwrap();
+        let expected = rdb.latest_sequence_number() + 1;
+        if wal::rep_sequence(rep) != expected {
+            return Err(Error::OutOfOrderBatch(expected));
+        }
+        wal::write_rep(&rdb, rep)?;
+        Ok(rdb.latest_sequence_number())
+    }
+
+    /// The pids subscribed to writes made through this db.
+    pub fn changes(&self) -> &Arc<Subscriptions> {
+        &self.changes
+    }
+
+    /// Starts sending the events of the db to `listener`.
+    pub fn listen(&self, polling: Polling) -> Result<(), Error> {
+        let path = self.rocks_db_ref()?.path().to_string_lossy().into_owned();
+        events::spawn_poller(Arc::downgrade(&self.rdb), path, polling);
+        Ok(())
+    }
+
+    /// Starts sending the info log entries of the db to `listener`.
+    pub fn tail_log(&self, listener: Listener, db_log_dir: Option<&str>) -> Result<(), Error> {
+        let db_path = self.rocks_db_ref()?.path().to_path_buf();
+        let log_path = log_tail::info_log_path(&db_path, db_log_dir);
+        let path = db_path.to_string_lossy().into_owned();
+        log_tail::spawn_tail(Arc::downgrade(&self.rdb), path, log_path, listener);
+        Ok(())
+    }
+
+    /// The statistics collected since the db was opened or since the last
+    /// `reset_statistics`. `None` unless the db was opened with
+    /// `enable_statistics`.
+    pub fn statistics(&self) -> Result<Option<SoyStatistics>, Error> {
+        let _rdb = self.rocks_db_ref()?;
+        let stats = match self.opts.get_statistics() {
+            Some(text) => SoyStatistics::parse(&text),
+            None => return Ok(None),
+        };
+        match &*self.stats_baseline.lock().unwrap() {
+            Some(baseline) => Ok(Some(stats.since(baseline))),
+            None => Ok(Some(stats)),
+        }
+    }
+
+    /// RocksDB cannot reset its statistics through the C API so the
+    /// current reading is kept and subtracted from later ones instead.
+    pub fn reset_statistics(&self) -> Result<(), Error> {
+        let _rdb = self.rocks_db_ref()?;
+        if let Some(text) = self.opts.get_statistics() {
+            *self.stats_baseline.lock().unwrap() = Some(SoyStatistics::parse(&text));
+        }
+        Ok(())
     }
 
-    pub fn rocks_db_ref(&self) -> &RocksDb {
-        &self.rdb
+    /// Releases everything borrowed from the db, flushes the memtables,
+    /// waits for background work to stop and closes the db, which also
+    /// releases its lock file.
+    ///
+    /// Waits for in-flight operations on the db to finish first.
+    pub fn close(&self) -> Result<(), Error> {
+        let mut guard = self.rdb.write().unwrap();
+        if guard.is_none() {
+            return Err(Error::Closed);
+        }
+        // snapshots and iterators point into the `Option`, so they are
+        // released before the db is moved out of it
+        self.children.release_all(&Error::Closed);
+        self.cf_children.lock().unwrap().clear();
+        self.changes.clear();
+        let rdb = guard.take().unwrap();
+        let flushed = rdb.flush();
+        rdb.cancel_all_background_work(true);
+        drop(rdb);
+        flushed.map_err(Error::from)
     }
 }
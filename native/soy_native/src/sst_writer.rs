@@ -0,0 +1,155 @@
+use crate::batching::{DeleteOp, MergeOp, PutOp};
+use crate::{Bin, Error};
+use rocksdb::{Options, SstFileWriter};
+use rustler::{NifStruct, NifUntaggedEnum, ResourceArc};
+use std::sync::Mutex;
+
+pub type SoySstWriter = ResourceArc<SstWriterResource>;
+
+#[derive(NifUntaggedEnum)]
+pub enum SstOp {
+    Put(PutOp),
+    Merge(MergeOp),
+    Delete(DeleteOp),
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Soy.SstFileInfo"]
+pub struct SoySstFileInfo {
+    pub path: String,
+    pub file_size: u64,
+    pub num_entries: u64,
+    pub smallest_key: Option<Bin>,
+    pub largest_key: Option<Bin>,
+}
+
+/// An sst file that is being written. It owns the writer together with the
+/// options the writer borrows.
+pub struct SstFile {
+    // ORDER MATTERS. Fields are dropped in declaration order
+    // so the writer is dropped before the options it borrows.
+    // The options are boxed so that moving the `SstFile` does not move
+    // them out from under the writer.
+    writer: SstFileWriter<'static>,
+    _opts: Box<Options>,
+    path: String,
+    num_entries: u64,
+    smallest_key: Option<Vec<u8>>,
+    largest_key: Vec<u8>,
+}
+
+impl SstFile {
+    /// Creates the sst file at `path`. The `opts` should match the options
+    /// of the db or cf the file will be ingested into; at least the
+    /// comparator and the merge operator must be the same.
+    pub fn open(path: &str, opts: Options) -> Result<SstFile, Error> {
+        let opts = Box::new(opts);
+        let writer = SstFileWriter::create(&opts);
+        open_writer(&writer, path)?;
+        Ok(SstFile {
+            writer: unsafe { extend_lifetime_writer(writer) },
+            _opts: opts,
+            path: path.to_owned(),
+            num_entries: 0,
+            smallest_key: None,
+            largest_key: Vec::new(),
+        })
+    }
+
+    /// Adds an op to the file. Keys must be strictly ascending, as
+    /// RocksDB enforces.
+    pub fn add(&mut self, op: &SstOp) -> Result<(), Error> {
+        match op {
+            SstOp::Put(p) => self.put(p.key(), p.val()),
+            SstOp::Merge(m) => {
+                self.writer.merge(m.key(), m.val())?;
+                self.track(m.key());
+                Ok(())
+            }
+            SstOp::Delete(d) => {
+                self.writer.delete(d.key())?;
+                self.track(d.key());
+                Ok(())
+            }
+        }
+    }
+
+    pub fn put(&mut self, key: &[u8], val: &[u8]) -> Result<(), Error> {
+        self.writer.put(key, val)?;
+        self.track(key);
+        Ok(())
+    }
+
+    fn track(&mut self, key: &[u8]) {
+        if self.smallest_key.is_none() {
+            self.smallest_key = Some(key.to_vec());
+        }
+        self.largest_key.clear();
+        self.largest_key.extend_from_slice(key);
+        self.num_entries += 1;
+    }
+
+    pub fn file_size(&self) -> u64 {
+        self.writer.file_size()
+    }
+
+    /// Finishes and closes the file.
+    pub fn finish(mut self) -> Result<SoySstFileInfo, Error> {
+        self.writer.finish()?;
+        let file_size = self.writer.file_size();
+        let largest_key = match self.smallest_key {
+            Some(_) => Some(Bin::from(self.largest_key)),
+            None => None,
+        };
+        Ok(SoySstFileInfo {
+            path: self.path,
+            file_size,
+            num_entries: self.num_entries,
+            smallest_key: self.smallest_key.map(Bin::from),
+            largest_key,
+        })
+    }
+}
+
+/// `open` borrows the writer for as long as the options it was created
+/// with, but does not hold on to the borrow, so the borrow is shortened to
+/// this call.
+fn open_writer<'a>(writer: &'a SstFileWriter<'a>, path: &str) -> Result<(), Error> {
+    writer.open(path)?;
+    Ok(())
+}
+
+/// The writer must be stored next to the boxed options it was created
+/// from and dropped before them, as `SstFile` does.
+unsafe fn extend_lifetime_writer<'b>(writer: SstFileWriter<'b>) -> SstFileWriter<'static> {
+    std::mem::transmute::<SstFileWriter<'b>, SstFileWriter<'static>>(writer)
+}
+
+pub struct SstWriterResource {
+    file: Mutex<Option<SstFile>>,
+}
+
+impl SstWriterResource {
+    pub fn open(path: &str, opts: Options) -> Result<SoySstWriter, Error> {
+        let file = SstFile::open(path, opts)?;
+        Ok(ResourceArc::new(SstWriterResource {
+            file: Mutex::new(Some(file)),
+        }))
+    }
+
+    pub fn add(&self, ops: Vec<SstOp>) -> Result<usize, Error> {
+        let mut guard = self.file.lock().unwrap();
+        let file = guard.as_mut().ok_or(Error::SstWriterFinished)?;
+        for op in ops.iter() {
+            file.add(op)?;
+        }
+        Ok(ops.len())
+    }
+
+    /// Finishes the file. The writer cannot be used afterwards.
+    pub fn finish(&self) -> Result<SoySstFileInfo, Error> {
+        let mut guard = self.file.lock().unwrap();
+        let file = guard.take().ok_or(Error::SstWriterFinished)?;
+        file.finish()
+    }
+}
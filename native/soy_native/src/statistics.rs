@@ -0,0 +1,91 @@
+use rustler::{NifMap, NifStruct};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, NifStruct)]
+#[module = "Soy.Statistics"]
+pub struct SoyStatistics {
+    pub tickers: HashMap<String, u64>,
+    pub histograms: HashMap<String, SoyHistogram>,
+}
+
+#[derive(Debug, Clone, NifMap)]
+pub struct SoyHistogram {
+    pub p50: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub p100: f64,
+    pub count: u64,
+    pub sum: u64,
+}
+
+impl SoyStatistics {
+    /// Parses the output of `Options::get_statistics`, which has a line
+    /// per ticker:
+    ///
+    /// `rocksdb.block.cache.miss COUNT : 3`
+    ///
+    /// and a line per histogram:
+    ///
+    /// `rocksdb.db.get.micros P50 : 1.5 P95 : 4.0 P99 : 8.1 P100 : 12.0 COUNT : 20 SUM : 40`
+    pub fn parse(text: &str) -> SoyStatistics {
+        let mut tickers = HashMap::new();
+        let mut histograms = HashMap::new();
+        for line in text.lines() {
+            let mut parts = line.split_whitespace();
+            let name = match parts.next() {
+                Some(name) => name.to_string(),
+                None => continue,
+            };
+            let fields = parse_fields(parts);
+            let float =
+                |key: &str| -> f64 { fields.get(key).and_then(|v| v.parse().ok()).unwrap_or(0.0) };
+            let int =
+                |key: &str| -> u64 { fields.get(key).and_then(|v| v.parse().ok()).unwrap_or(0) };
+            if fields.len() == 1 {
+                tickers.insert(name, int("COUNT"));
+                continue;
+            }
+            histograms.insert(
+                name,
+                SoyHistogram {
+                    p50: float("P50"),
+                    p95: float("P95"),
+                    p99: float("P99"),
+                    p100: float("P100"),
+                    count: int("COUNT"),
+                    sum: int("SUM"),
+                },
+            );
+        }
+        SoyStatistics {
+            tickers,
+            histograms,
+        }
+    }
+
+    /// Subtracts the tickers and the histogram counts and sums of an earlier
+    /// reading. Percentiles cannot be subtracted and are left as they are.
+    pub fn since(mut self, baseline: &SoyStatistics) -> SoyStatistics {
+        for (name, count) in self.tickers.iter_mut() {
+            if let Some(base) = baseline.tickers.get(name) {
+                *count = count.saturating_sub(*base);
+            }
+        }
+        for (name, hist) in self.histograms.iter_mut() {
+            if let Some(base) = baseline.histograms.get(name) {
+                hist.count = hist.count.saturating_sub(base.count);
+                hist.sum = hist.sum.saturating_sub(base.sum);
+            }
+        }
+        self
+    }
+}
+
+/// Parses `KEY : value` pairs.
+fn parse_fields<'a>(mut parts: impl Iterator<Item = &'a str>) -> HashMap<&'a str, &'a str> {
+    let mut fields = HashMap::new();
+    while let (Some(key), Some(":"), Some(val)) = (parts.next(), parts.next(), parts.next()) {
+        fields.insert(key, val);
+    }
+    fields
+}
@@ -0,0 +1,556 @@
+use crate::release::Slot;
+use crate::{atoms, new_binary, Error, SoyDb};
+use librocksdb_sys as ffi;
+use rocksdb::{AsColumnFamilyRef, DBAccess, Options, WriteBatch, DB as RocksDb};
+use rustler::env::OwnedEnv;
+use rustler::{Encoder, Env, LocalPid, Term};
+use std::collections::HashMap;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+// sequence number (fixed64) and count (fixed32)
+const HEADER_SIZE: usize = 12;
+
+unsafe fn take_err(err: *mut c_char) -> Result<(), Error> {
+    if err.is_null() {
+        return Ok(());
+    }
+    let msg = CStr::from_ptr(err).to_string_lossy().into_owned();
+    ffi::rocksdb_free(err as *mut c_void);
+    Err(Error::RocksDbError(msg))
+}
+
+/// The WAL iterator of the C API.
+///
+/// `DBWALIterator` of the rocksdb crate steps before it reads, which skips
+/// the batch the iterator starts at, so the C API is used directly.
+struct RawWalIter(*mut ffi::rocksdb_wal_iterator_t);
+
+unsafe impl Send for RawWalIter {}
+unsafe impl Sync for RawWalIter {}
+
+impl RawWalIter {
+    /// Starts at the batch containing `since`.
+    fn new(rdb: &RocksDb, since: u64) -> Result<RawWalIter, Error> {
+        let mut err: *mut c_char = ptr::null_mut();
+        unsafe {
+            let it = ffi::rocksdb_get_updates_since(rdb.inner(), since, ptr::null(), &mut err);
+            take_err(err).map_err(|e| Error::WalIteratorCreationError(e.to_string()))?;
+            Ok(RawWalIter(it))
+        }
+    }
+
+    fn valid(&self) -> bool {
+        unsafe { ffi::rocksdb_wal_iter_valid(self.0) != 0 }
+    }
+
+    fn status(&self) -> Result<(), Error> {
+        let mut err: *mut c_char = ptr::null_mut();
+        unsafe {
+            ffi::rocksdb_wal_iter_status(self.0, &mut err);
+            take_err(err)
+        }
+    }
+
+    fn next(&mut self) {
+        unsafe { ffi::rocksdb_wal_iter_next(self.0) }
+    }
+
+    /// The sequence number and raw `WriteBatch` of the current batch.
+    fn batch(&self) -> (u64, Vec<u8>) {
+        let mut seq = 0;
+        let mut len = 0;
+        unsafe {
+            let batch = ffi::rocksdb_wal_iter_get_batch(self.0, &mut seq);
+            let data = ffi::rocksdb_writebatch_data(batch, &mut len);
+            let rep = std::slice::from_raw_parts(data as *const u8, len).to_vec();
+            ffi::rocksdb_writebatch_destroy(batch);
+            (seq, rep)
+        }
+    }
+}
+
+impl Drop for RawWalIter {
+    fn drop(&mut self) {
+        unsafe { ffi::rocksdb_wal_iter_destroy(self.0) }
+    }
+}
+
+/// The sequence number of the oldest batch the WAL still holds, which is
+/// the earliest start of a `WalIterator` that misses no write. Once all
+/// WAL files were deleted that is the next sequence number.
+///
+/// RocksDB deletes WAL files as soon as their writes are flushed unless
+/// `WAL_ttl_seconds` or `WAL_size_limit_MB` keep them archived.
+pub fn oldest_sequence_number(rdb: &RocksDb) -> Result<u64, Error> {
+    let raw = RawWalIter::new(rdb, 0)?;
+    if raw.valid() {
+        return Ok(raw.batch().0);
+    }
+    raw.status()?;
+    Ok(rdb.latest_sequence_number() + 1)
+}
+
+/// A write of a WAL batch.
+pub enum WalRow {
+    Put {
+        cf: u32,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Merge {
+        cf: u32,
+        key: Vec<u8>,
+        value: Vec<u8>,
+    },
+    Delete {
+        cf: u32,
+        key: Vec<u8>,
+    },
+    SingleDelete {
+        cf: u32,
+        key: Vec<u8>,
+    },
+    DeleteRange {
+        cf: u32,
+        from: Vec<u8>,
+        to: Vec<u8>,
+    },
+}
+
+impl WalRow {
+    fn cf(&self) -> u32 {
+        match self {
+            WalRow::Put { cf, .. }
+            | WalRow::Merge { cf, .. }
+            | WalRow::Delete { cf, .. }
+            | WalRow::SingleDelete { cf, .. }
+            | WalRow::DeleteRange { cf, .. } => *cf,
+        }
+    }
+}
+
+struct RepReader<'a>(&'a [u8]);
+
+impl<'a> RepReader<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let (b, rest) = self.0.split_first()?;
+        self.0 = rest;
+        Some(*b)
+    }
+
+    fn varint32(&mut self) -> Option<u32> {
+        let mut result = 0u32;
+        for shift in (0..35).step_by(7) {
+            let b = self.byte()?;
+            result |= ((b & 0x7f) as u32) << shift;
+            if b & 0x80 == 0 {
+                return Some(result);
+            }
+        }
+        None
+    }
+
+    fn slice(&mut self) -> Option<&'a [u8]> {
+        let len = self.varint32()? as usize;
+        if self.0.len() < len {
+            return None;
+        }
+        let (s, rest) = self.0.split_at(len);
+        self.0 = rest;
+        Some(s)
+    }
+}
+
+/// The number of sequence numbers a raw `WriteBatch` uses.
+pub fn rep_count(rep: &[u8]) -> u64 {
+    match rep.get(8..HEADER_SIZE) {
+        Some(count) => u32::from_le_bytes([count[0], count[1], count[2], count[3]]) as u64,
+        None => 0,
+    }
+}
+
+/// The sequence number of the first write of a raw `WriteBatch`.
+pub fn rep_sequence(rep: &[u8]) -> u64 {
+    match rep.get(..8) {
+        Some(seq) => {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(seq);
+            u64::from_le_bytes(bytes)
+        }
+        None => 0,
+    }
+}
+
+/// Writes a raw `WriteBatch` atomically. RocksDB assigns it the next
+/// sequence numbers of `rdb`, whatever its header says.
+pub fn write_rep(rdb: &RocksDb, rep: &[u8]) -> Result<(), Error> {
+    let mut err: *mut c_char = ptr::null_mut();
+    unsafe {
+        let batch = ffi::rocksdb_writebatch_create_from(rep.as_ptr() as *const c_char, rep.len());
+        let opts = ffi::rocksdb_writeoptions_create();
+        ffi::rocksdb_write(rdb.inner(), opts, batch, &mut err);
+        ffi::rocksdb_writeoptions_destroy(opts);
+        ffi::rocksdb_writebatch_destroy(batch);
+        take_err(err)
+    }
+}
+
+/// Decodes the writes of a raw `WriteBatch`, following the record format
+/// of RocksDB's `ReadRecordFromWriteBatch`. Transaction markers, log data
+/// and blob indexes carry no user writes and are skipped.
+pub fn decode_rep(rep: &[u8]) -> Result<Vec<WalRow>, Error> {
+    let corrupt = || Error::RocksDbError("malformed WriteBatch".to_string());
+    if rep.len() < HEADER_SIZE {
+        return Err(corrupt());
+    }
+    let mut reader = RepReader(&rep[HEADER_SIZE..]);
+    let mut rows = Vec::with_capacity(rep_count(rep) as usize);
+    while let Some(tag) = reader.byte() {
+        let row = (|| {
+            let cf = match tag {
+                0x4 | 0x5 | 0x6 | 0x8 | 0xE | 0x10 => reader.varint32()?,
+                _ => 0,
+            };
+            let row = match tag {
+                0x1 | 0x5 => Some(WalRow::Put {
+                    cf,
+                    key: reader.slice()?.to_vec(),
+                    value: reader.slice()?.to_vec(),
+                }),
+                0x2 | 0x6 => Some(WalRow::Merge {
+                    cf,
+                    key: reader.slice()?.to_vec(),
+                    value: reader.slice()?.to_vec(),
+                }),
+                0x0 | 0x4 => Some(WalRow::Delete {
+                    cf,
+                    key: reader.slice()?.to_vec(),
+                }),
+                0x7 | 0x8 => Some(WalRow::SingleDelete {
+                    cf,
+                    key: reader.slice()?.to_vec(),
+                }),
+                0xE | 0xF => Some(WalRow::DeleteRange {
+                    cf,
+                    from: reader.slice()?.to_vec(),
+                    to: reader.slice()?.to_vec(),
+                }),
+                0x10 | 0x11 => {
+                    reader.slice()?;
+                    reader.slice()?;
+                    None
+                }
+                0x3 | 0xA | 0xB | 0xC => {
+                    reader.slice()?;
+                    None
+                }
+                0x15 => {
+                    reader.slice()?;
+                    reader.slice()?;
+                    None
+                }
+                0x9 | 0xD | 0x12 | 0x13 => None,
+                _ => return Some(Err(())),
+            };
+            Some(Ok(row))
+        })();
+        match row {
+            Some(Ok(Some(row))) => rows.push(row),
+            Some(Ok(None)) => (),
+            _ => return Err(corrupt()),
+        }
+    }
+    Ok(rows)
+}
+
+/// The id RocksDB gives the column family in the WAL.
+///
+/// The C API has no getter for it, so it is read back from a `WriteBatch`
+/// with a single write to the column family.
+pub fn cf_id(cf: &impl AsColumnFamilyRef) -> u32 {
+    let mut batch = WriteBatch::default();
+    batch.put_cf(cf, b"", b"");
+    match decode_rep(batch.data()) {
+        Ok(rows) => rows.first().map_or(0, WalRow::cf),
+        Err(_) => 0,
+    }
+}
+
+/// The names of the column families of `rdb` by their WAL ids, read from
+/// its MANIFEST. `DbResource::cf_names` caches them.
+pub fn cf_names(rdb: &RocksDb) -> Result<HashMap<u32, String>, Error> {
+    let names = RocksDb::list_cf(&Options::default(), rdb.path())?;
+    Ok(names
+        .into_iter()
+        .filter_map(|name| {
+            let handle = rdb.cf_handle(&name)?;
+            Some((cf_id(&handle), name))
+        })
+        .collect())
+}
+
+/// Checks that the column families `rows` write to have the same WAL ids
+/// in a follower with the column family `names` as in the primary with the
+/// ids `primary`, so that the rows of a primary's batch land in the column
+/// families of the same names.
+pub fn check_cf_ids(
+    names: &HashMap<u32, String>,
+    rows: &[WalRow],
+    primary: &HashMap<String, u32>,
+) -> Result<(), Error> {
+    for row in rows {
+        let id = row.cf();
+        // the default column family always has the id 0
+        if id == 0 {
+            continue;
+        }
+        match names.get(&id) {
+            Some(name) if primary.get(name) == Some(&id) => (),
+            _ => return Err(Error::ColumnFamilyMismatch(id)),
+        }
+    }
+    Ok(())
+}
+
+/// A batch of writes read from the WAL.
+pub struct WalBatch {
+    seq: u64,
+    rows: Vec<(Option<String>, WalRow)>,
+}
+
+impl Encoder for WalBatch {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let rows: Vec<Term<'a>> = self
+            .rows
+            .iter()
+            .map(|(cf, row)| encode_row(env, cf, row))
+            .collect();
+        (self.seq, rows).encode(env)
+    }
+}
+
+fn encode_row<'a>(env: Env<'a>, cf: &Option<String>, row: &WalRow) -> Term<'a> {
+    let bin = |b: &[u8]| new_binary(b, env);
+    match row {
+        WalRow::Put { key, value, .. } => (atoms::put(), cf, bin(key), bin(value)).encode(env),
+        WalRow::Merge { key, value, .. } => (atoms::merge(), cf, bin(key), bin(value)).encode(env),
+        WalRow::Delete { key, .. } => (atoms::delete(), cf, bin(key)).encode(env),
+        WalRow::SingleDelete { key, .. } => (atoms::single_delete(), cf, bin(key)).encode(env),
+        WalRow::DeleteRange { from, to, .. } => {
+            (atoms::delete_range(), cf, bin(from), bin(to)).encode(env)
+        }
+    }
+}
+
+/// A batch in the shape `wal_iter_next` has always returned: `{seq, rows}`
+/// with `{:put, key, value}` and `{:delete, key}` rows. Only the puts and
+/// deletes of the default column family have a row in that shape; the other
+/// writes of the batch are left out.
+pub struct DefaultCfBatch(pub WalBatch);
+
+impl Encoder for DefaultCfBatch {
+    fn encode<'a>(&self, env: Env<'a>) -> Term<'a> {
+        let bin = |b: &[u8]| new_binary(b, env);
+        let rows: Vec<Term<'a>> = self
+            .0
+            .rows
+            .iter()
+            .filter_map(|(_, row)| match row {
+                WalRow::Put { cf: 0, key, value } => {
+                    Some((atoms::put(), bin(key), bin(value)).encode(env))
+                }
+                WalRow::Delete { cf: 0, key } => Some((atoms::delete(), bin(key)).encode(env)),
+                _ => None,
+            })
+            .collect();
+        (self.0.seq, rows).encode(env)
+    }
+}
+
+struct Subscriber {
+    id: u64,
+    // set once the iterator caught up, cleared when the pid is notified
+    armed: bool,
+}
+
+struct WalState {
+    // the first sequence number that was not returned yet
+    since: u64,
+    // positioned at the next batch to return
+    raw: Option<RawWalIter>,
+    subscriber: Option<Subscriber>,
+}
+
+impl WalState {
+    fn arm(&mut self) {
+        if let Some(sub) = self.subscriber.as_mut() {
+            sub.armed = true;
+        }
+    }
+}
+
+/// Reads the batches written since a sequence number.
+///
+/// Once caught up, the iterator returns `None` until more is written; it
+/// then continues after the last batch it returned. A start in the future
+/// is allowed and waits the same way.
+pub struct WalIterator {
+    it: Arc<Slot<WalState>>,
+    _db: SoyDb,
+}
+
+impl WalIterator {
+    pub fn new(db: SoyDb, since: u64) -> Result<WalIterator, Error> {
+        let rdb = db.rocks_db_ref()?;
+        // RocksDB refuses to start after the latest sequence number
+        let raw = match since > rdb.latest_sequence_number() {
+            true => None,
+            false => Some(RawWalIter::new(&rdb, since)?),
+        };
+        let it = Slot::new(WalState {
+            since,
+            raw,
+            subscriber: None,
+        });
+        db.register(&it);
+        drop(rdb);
+        Ok(WalIterator { it, _db: db })
+    }
+
+    pub fn next(&self) -> Result<Option<WalBatch>, Error> {
+        let rdb = self._db.rocks_db_ref()?;
+        match self.next_rep(&rdb)? {
+            Some((seq, rep)) => {
+                let rows = decode_rep(&rep)?;
+                Ok(Some(WalBatch {
+                    seq,
+                    rows: self.named_rows(&rdb, rows)?,
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Returns up to `n` batches, fewer once caught up.
+    pub fn take(&self, n: usize) -> Result<Vec<WalBatch>, Error> {
+        let mut batches = Vec::new();
+        while batches.len() < n {
+            match self.next()? {
+                Some(batch) => batches.push(batch),
+                None => break,
+            }
+        }
+        Ok(batches)
+    }
+
+    /// Like `next`, but returns the batch as the raw `WriteBatch` RocksDB
+    /// logged, which a follower can write with `DbResource::apply_batch`.
+    pub fn next_raw(&self) -> Result<Option<(u64, Vec<u8>)>, Error> {
+        let rdb = self._db.rocks_db_ref()?;
+        self.next_rep(&rdb)
+    }
+
+    fn next_rep(&self, rdb: &RocksDb) -> Result<Option<(u64, Vec<u8>)>, Error> {
+        let mut guard = self.it.write()?;
+        let state = &mut *guard;
+        loop {
+            let (seq, rep) = match state.raw.as_mut() {
+                Some(raw) if raw.valid() => {
+                    let batch = raw.batch();
+                    raw.next();
+                    batch
+                }
+                Some(raw) => {
+                    raw.status()?;
+                    // caught up; restart from `since` once more is written
+                    state.raw = None;
+                    state.arm();
+                    return Ok(None);
+                }
+                None if state.since > rdb.latest_sequence_number() => {
+                    state.arm();
+                    return Ok(None);
+                }
+                None => {
+                    let since = state.since;
+                    state.raw = Some(RawWalIter::new(rdb, since)?);
+                    continue;
+                }
+            };
+            let count = rep_count(&rep);
+            // a restarted iterator begins at the batch containing `since`
+            if count > 0 && seq + count <= state.since {
+                continue;
+            }
+            state.since = state.since.max(seq + count);
+            return Ok(Some((seq, rep)));
+        }
+    }
+
+    /// Sends `{:soy_wal, id, latest_seq}` to `pid` when a write follows the
+    /// last batch returned, so the pid can wait instead of polling `next`.
+    ///
+    /// The pid is notified once, and again only after `next` returned
+    /// `None`. A thread checks the latest sequence number every `interval`
+    /// until the iterator is released or another pid subscribes.
+    pub fn subscribe(&self, pid: LocalPid, id: u64, interval: Duration) -> Result<(), Error> {
+        self.it.write()?.subscriber = Some(Subscriber { id, armed: true });
+        let it = Arc::downgrade(&self.it);
+        let db = self._db.clone();
+        thread::spawn(move || {
+            let mut env = OwnedEnv::new();
+            loop {
+                let latest = {
+                    let it = match it.upgrade() {
+                        Some(it) => it,
+                        None => return,
+                    };
+                    let rdb = match db.rocks_db_ref() {
+                        Ok(rdb) => rdb,
+                        Err(_) => return,
+                    };
+                    let mut guard = match it.write() {
+                        Ok(guard) => guard,
+                        Err(_) => return,
+                    };
+                    let state = &mut *guard;
+                    let latest = rdb.latest_sequence_number();
+                    match state.subscriber.as_mut() {
+                        Some(sub) if sub.id == id => {
+                            if sub.armed && state.since <= latest {
+                                sub.armed = false;
+                                Some(latest)
+                            } else {
+                                None
+                            }
+                        }
+                        _ => return,
+                    }
+                };
+                if let Some(latest) = latest {
+                    env.send_and_clear(&pid, |env| (atoms::soy_wal(), id, latest).encode(env));
+                }
+                thread::sleep(interval);
+            }
+        });
+        Ok(())
+    }
+
+    /// Names the column families of `rows`; those dropped since have none.
+    fn named_rows(
+        &self,
+        rdb: &RocksDb,
+        rows: Vec<WalRow>,
+    ) -> Result<Vec<(Option<String>, WalRow)>, Error> {
+        let names = self._db.cf_names(rdb)?;
+        Ok(rows
+            .into_iter()
+            .map(|row| (names.get(&row.cf()).cloned(), row))
+            .collect())
+    }
+}
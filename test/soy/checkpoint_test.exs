@@ -0,0 +1,110 @@
+defmodule Soy.CheckpointTest do
+  use ExUnit.Case
+  import Soy.TestHelpers
+  alias Soy.{Checkpoint, DBCol}
+
+  doctest Soy.Checkpoint
+
+  setup do
+    db = Soy.open(tmp_dir())
+    {:ok, cf} = DBCol.create_new(db, "feet")
+    for i <- 1..100, do: :ok = Soy.put(db, "key#{i}", "val#{i}")
+    :ok = DBCol.put(cf, "a", "1")
+    {:ok, %{db: db}}
+  end
+
+  test "streams the db in chunks", %{db: db} do
+    assert {:ok, id} = Checkpoint.stream(db, self(), chunk_size: 100)
+    {chunks, files} = collect(id, [])
+    assert length(chunks) > length(files)
+
+    for {:chunk, _name, offset, crc, data} <- chunks do
+      assert rem(offset, 100) == 0
+      assert byte_size(data) <= 100
+      assert :erlang.crc32(data) == crc
+    end
+
+    for {name, size, _crc} <- files do
+      sent = for {:chunk, ^name, _, _, data} <- chunks, do: byte_size(data)
+      assert Enum.sum(sent) == size
+    end
+  end
+
+  test "reassembles an openable db", %{db: db} do
+    assert {:ok, id} = Checkpoint.stream(db, self(), chunk_size: 1000)
+    dir = Path.join(tmp_dir(), "replica")
+    assert {:ok, ^dir} = Checkpoint.receive_into(dir, id)
+
+    replica = Soy.open(dir)
+    assert Soy.get(replica, "key42") == "val42"
+    assert {:ok, cf} = DBCol.open(replica, "feet")
+    assert DBCol.get(cf, "a") == "1"
+    assert Path.wildcard(Soy.DB.path(db) <> ".checkpoint-*") == []
+  end
+
+  test "rejects corrupt chunks" do
+    dir = tmp_dir()
+    send(self(), {:soy_checkpoint, self(), {:chunk, "CURRENT", 0, 0, "data"}})
+    assert {:error, "invalid checkpoint: checksum mismatch in CURRENT at 0"} =
+             Checkpoint.receive_into(dir, self())
+  end
+
+  test "rejects file names outside the dir" do
+    dir = tmp_dir()
+    send(self(), {:soy_checkpoint, self(), {:chunk, "../x", 0, :erlang.crc32("x"), "x"}})
+    assert {:error, "invalid checkpoint: bad file name \"../x\""} =
+             Checkpoint.receive_into(dir, self())
+  end
+
+  test "detects missing chunks" do
+    dir = tmp_dir()
+    send(self(), {:soy_checkpoint, self(), {:done, [{"CURRENT", 4, :erlang.crc32("data")}]}})
+    assert {:error, "invalid checkpoint: CURRENT is incomplete"} =
+             Checkpoint.receive_into(dir, self())
+  end
+
+  test "sends no more than a window of chunks ahead of the acks", %{db: db} do
+    assert {:ok, id} = Checkpoint.stream(db, self(), chunk_size: 10, window: 3)
+    for _ <- 1..3, do: assert_receive({:soy_checkpoint, ^id, {:chunk, _, _, _, _}})
+    refute_receive {:soy_checkpoint, ^id, _}, 50
+
+    send(id, {:soy_checkpoint_ack, id})
+    assert_receive {:soy_checkpoint, ^id, {:chunk, _, _, _, _}}
+    refute_receive {:soy_checkpoint, ^id, _}, 50
+  end
+
+  test "stops and removes the checkpoint when the receiver exits", %{db: db} do
+    receiver = spawn(fn -> receive do: (:stop -> :ok) end)
+    assert {:ok, id} = Checkpoint.stream(db, receiver, chunk_size: 10, window: 1)
+    assert [_] = Path.wildcard(Soy.DB.path(db) <> ".checkpoint-*")
+
+    ref = Process.monitor(id)
+    send(receiver, :stop)
+    assert_receive {:DOWN, ^ref, :process, ^id, _}
+    assert removed?(Soy.DB.path(db) <> ".checkpoint-*", 20)
+  end
+
+  # the stream resource is freed right after its process exits
+  defp removed?(pattern, tries) do
+    cond do
+      Path.wildcard(pattern) == [] -> true
+      tries == 0 -> false
+      true ->
+        Process.sleep(10)
+        removed?(pattern, tries - 1)
+    end
+  end
+
+  defp collect(id, chunks) do
+    receive do
+      {:soy_checkpoint, ^id, {:chunk, _, _, _, _} = chunk} ->
+        send(id, {:soy_checkpoint_ack, id})
+        collect(id, [chunk | chunks])
+
+      {:soy_checkpoint, ^id, {:done, files}} ->
+        {Enum.reverse(chunks), files}
+    after
+      5_000 -> flunk("checkpoint stream timed out")
+    end
+  end
+end
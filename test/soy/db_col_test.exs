@@ -31,6 +31,168 @@ defmodule Soy.DBColTest do
     end
   end
 
+  describe "export/2 and import/5" do
+    test "moves a column family to another db", %{cf: cf} do
+      for i <- 1..100, do: :ok = DBCol.put(cf, "key#{i}", "val#{i}")
+      dir = Path.join(tmp_dir(), "export")
+      assert {:ok, export} = DBCol.export(cf, dir)
+      assert export.num_entries == 100
+      assert [%Soy.SstFileInfo{num_entries: 100}] = export.files
+
+      other = Soy.open(tmp_dir())
+      assert {:ok, imported} = DBCol.import(other, "feet", dir, [], set_move_files: true)
+      assert DBCol.count_range(imported) == 100
+      assert DBCol.get(imported, "key42") == "val42"
+    end
+
+    test "exports an empty column family", %{db: db, cf: cf} do
+      dir = Path.join(tmp_dir(), "export")
+      assert {:ok, export} = DBCol.export(cf, dir)
+      assert export.files == []
+      assert {:ok, imported} = DBCol.import(db, "feet2", export)
+      assert DBCol.count_range(imported) == 0
+    end
+
+    test "does not export into an existing dir", %{cf: cf} do
+      dir = tmp_dir()
+      assert {:error, "export directory already exists: " <> _} = DBCol.export(cf, dir)
+    end
+
+    test "removes the dir of a failed export", %{db: db, cf: cf} do
+      dir = Path.join(tmp_dir(), "export")
+      :ok = Soy.DB.close(db)
+      assert DBCol.export(cf, dir) == {:error, :closed}
+      refute File.exists?(dir)
+    end
+
+    test "does not import over an existing column family", %{db: db, cf: cf} do
+      dir = Path.join(tmp_dir(), "export")
+      {:ok, export} = DBCol.export(cf, dir)
+      assert {:error, _} = DBCol.import(db, "feet", export)
+    end
+  end
+
+  describe "property_map/2" do
+    test "parses aggregated table properties", %{cf: cf} do
+      :ok = DBCol.put(cf, "a", "1")
+      :ok = DBCol.put(cf, "b", "2")
+      :ok = Soy.Native.db_cf_flush(DBCol.to_ref(cf))
+
+      props = DBCol.property_map(cf, "rocksdb.aggregated-table-properties")
+      assert props["# entries"] == 2
+    end
+
+    test "parses cfstats", %{cf: cf} do
+      :ok = DBCol.put(cf, "a", "1")
+      :ok = Soy.Native.db_cf_flush(DBCol.to_ref(cf))
+
+      stats = DBCol.property_map(cf, "rocksdb.cfstats")
+      assert %{"L0" => %{"Files" => "1/0", "W-Amp" => _}, "Sum" => _} = stats["compaction_stats"]
+      assert is_binary(stats["Uptime(secs)"])
+    end
+
+    test "is nil for an unknown property", %{cf: cf} do
+      assert DBCol.property_map(cf, "rocksdb.not-a-property") == nil
+    end
+  end
+
+  describe "approximate_sizes/2" do
+    test "measures flushed ranges", %{cf: cf} do
+      for i <- 1..100, do: :ok = DBCol.put(cf, "a#{i}", String.duplicate("x", 100))
+      :ok = DBCol.put(cf, "b", "1")
+      :ok = Soy.Native.db_cf_flush(DBCol.to_ref(cf))
+
+      assert [%{size: a_size, keys: a_keys}, %{size: 0, keys: 0}] =
+               DBCol.approximate_sizes(cf, [{"a", "b"}, {"c", "d"}])
+
+      assert a_size > 0
+      assert a_keys > 0 and a_keys <= 101
+    end
+
+    test "does not count unflushed writes", %{cf: cf} do
+      :ok = DBCol.put(cf, "a", "1")
+      assert DBCol.approximate_sizes(cf, [{"a", "b"}]) == [%{size: 0, keys: 0}]
+
+      :ok = Soy.Native.db_cf_flush(DBCol.to_ref(cf))
+      assert [%{size: size}] = DBCol.approximate_sizes(cf, [{"a", "b"}])
+      assert size > 0
+    end
+  end
+
+  describe "set_options/2" do
+    test "changes mutable options of the column family", %{db: db, cf: cf} do
+      assert :ok = DBCol.set_options(cf, level0_stop_writes_trigger: 40)
+      # RocksDB persists the new options in a fresh OPTIONS file
+      path = Soy.DB.path(db)
+      latest =
+        path
+        |> File.ls!()
+        |> Enum.filter(&String.starts_with?(&1, "OPTIONS-"))
+        |> Enum.max()
+
+      assert File.read!(Path.join(path, latest)) =~ "level0_stop_writes_trigger=40"
+    end
+
+    test "rejects unknown options without applying any", %{cf: cf} do
+      assert DBCol.set_options(cf, %{"write_buffer_size" => 1024, "nope" => 1}) ==
+               {:error, {:unknown_option, "nope"}}
+    end
+
+    test "reports the offending option that comes first by name", %{cf: cf} do
+      opts = %{"zzz" => 1, "write_buffer_size" => "big", "num_levels" => 4}
+      assert DBCol.set_options(cf, opts) == {:error, {:immutable_option, "num_levels"}}
+    end
+  end
+
+  describe "metadata/1" do
+    test "lists files per level with seqnos", %{cf: cf} do
+      :ok = DBCol.put(cf, "a", "1")
+      :ok = DBCol.put(cf, "b", "2")
+      :ok = Soy.Native.db_cf_flush(DBCol.to_ref(cf))
+
+      assert %Soy.CfMetadata{name: "feet", file_count: 1, size: size, levels: levels} =
+               DBCol.metadata(cf)
+
+      assert length(levels) > 1
+      assert [%{level: 0, size: ^size, files: [file]} | _] = levels
+
+      assert %Soy.SstFileMetadata{
+               smallest_key: "a",
+               largest_key: "b",
+               num_entries: 2,
+               smallest_seqno: smallest,
+               largest_seqno: largest
+             } = file
+
+      assert smallest < largest
+    end
+
+    test "is empty before a flush", %{cf: cf} do
+      :ok = DBCol.put(cf, "a", "1")
+      assert %Soy.CfMetadata{file_count: 0, size: 0} = DBCol.metadata(cf)
+    end
+  end
+
+  describe "delete_files_in_range/3" do
+    # level 0 files are never deleted, so the files are ingested to land
+    # in the bottommost level
+    test "deletes files entirely within the range", %{cf: cf} do
+      for key <- ["a", "x"] do
+        path = Path.join(tmp_dir(), "#{key}.sst")
+        {:ok, w} = Soy.SstWriter.open(path)
+        :ok = Soy.SstWriter.put(w, key, "1")
+        {:ok, _} = Soy.SstWriter.finish(w)
+        :ok = DBCol.ingest(cf, [path])
+      end
+
+      assert %Soy.CfMetadata{file_count: 2} = DBCol.metadata(cf)
+      assert :ok = DBCol.delete_files_in_range(cf, "a", "m")
+      assert DBCol.get(cf, "a") == nil
+      assert DBCol.get(cf, "x") == "1"
+      assert %Soy.CfMetadata{file_count: 1} = DBCol.metadata(cf)
+    end
+  end
+
   describe "name/1" do
     test "works", %{cf: cf} do
       assert DBCol.name(cf) == "feet"
@@ -49,6 +211,34 @@ defmodule Soy.DBColTest do
       assert :ok = DBCol.destroy(cf)
       assert DBCol.destroy(cf) == {:error, "Invalid column family: feet"}
     end
+
+    test "invalidates the handles of the column family", %{db: db, cf: cf} do
+      assert :ok = DBCol.put(cf, "a", "1")
+      assert {:ok, cf2} = DBCol.open(db, "feet")
+      assert :ok = DBCol.destroy(cf)
+      dropped = {:error, {:column_family_dropped, "feet"}}
+      assert DBCol.put(cf, "b", "2") == dropped
+      assert DBCol.fetch(cf2, "a") == dropped
+      assert DBCol.has_key?(cf2, "a") == dropped
+      assert DBCol.count_range(cf2) == dropped
+    end
+
+    test "makes iterators over the column family fail", %{cf: cf} do
+      assert :ok = DBCol.put(cf, "a", "1")
+      it = Soy.Iter.new(cf)
+      assert Soy.Iter.first(it) == {"a", "1"}
+      assert :ok = DBCol.destroy(cf)
+      assert Soy.Iter.next(it) == {:error, {:column_family_dropped, "feet"}}
+      assert Soy.Iter.new(cf) == {:error, {:column_family_dropped, "feet"}}
+    end
+
+    test "does not affect a recreated column family", %{db: db, cf: cf} do
+      assert :ok = DBCol.destroy(cf)
+      assert {:ok, cf2} = DBCol.create_new(db, "feet")
+      assert :ok = DBCol.put(cf2, "a", "1")
+      assert DBCol.get(cf2, "a") == "1"
+      assert DBCol.put(cf, "a", "1") == {:error, {:column_family_dropped, "feet"}}
+    end
   end
 
   describe "put/3" do
@@ -86,6 +276,17 @@ defmodule Soy.DBColTest do
     end
   end
 
+  describe "count_range/3" do
+    test "counts entries between the bounds", %{cf: cf} do
+      for i <- 1..9, do: :ok = DBCol.put(cf, "k#{i}", String.duplicate("v", i))
+      assert DBCol.count_range(cf) == 9
+      assert DBCol.count_range(cf, "k3", "k7") == 4
+      assert DBCol.count_range(cf, nil, "k3") == 2
+      assert DBCol.count_range(cf, "k8", nil) == 2
+      assert DBCol.count_range(cf, "x", nil) == 0
+    end
+  end
+
   describe "multi_get/1" do
     test "works" do
       db = Soy.open(tmp_dir())
@@ -4,4 +4,257 @@ defmodule Soy.DBTest do
   alias Soy.{DB, DBCol, OpenOpts}
 
   doctest Soy.DB
+
+  describe "statistics/1" do
+    test "has tickers and histograms" do
+      db = Soy.open(tmp_dir(), enable_statistics: true)
+      :ok = Soy.put(db, "a", "1")
+
+      assert %Soy.Statistics{tickers: tickers, histograms: histograms} = DB.statistics(db)
+      assert tickers["rocksdb.bytes.written"] > 0
+      assert tickers["rocksdb.stall.micros"] == 0
+      assert %{p50: _, p95: _, p99: _, p100: _, count: 1, sum: _} =
+               histograms["rocksdb.db.write.micros"]
+    end
+
+    test "returns an error after close" do
+      db = Soy.open(tmp_dir(), enable_statistics: true)
+      :ok = DB.close(db)
+      assert DB.statistics(db) == {:error, :closed}
+    end
+  end
+
+  describe "event_listener open option" do
+    test "sends table file events" do
+      path = tmp_dir()
+      opts = [event_listener: self(), event_poll_interval_ms: 10, event_table_files: true]
+      db = Soy.open(path, opts)
+      :ok = Soy.put(db, "a", "1")
+      :ok = Soy.Native.db_flush(DB.to_ref(db))
+
+      assert_receive {:soy_event, ^path, :table_file_created,
+                      %Soy.LiveFile{level: 0, num_entries: 1}},
+                     1_000
+    end
+
+    test "sends no table file events unless asked for" do
+      db = Soy.open(tmp_dir(), event_listener: self(), event_poll_interval_ms: 10)
+      :ok = Soy.put(db, "a", "1")
+      :ok = Soy.Native.db_flush(DB.to_ref(db))
+      refute_receive {:soy_event, _, :table_file_created, _}, 100
+    end
+
+    test "stops sending events after close" do
+      db = Soy.open(tmp_dir(), event_listener: self(), event_poll_interval_ms: 10)
+      :ok = DB.close(db)
+      :ok = Soy.put(Soy.open(tmp_dir()), "a", "1")
+      refute_receive {:soy_event, _, _, _}, 100
+    end
+  end
+
+  describe "log_listener open option" do
+    test "sends the entries of the info log" do
+      path = tmp_dir()
+      _db = Soy.open(path, log_listener: self())
+
+      assert_receive {:soy_log, ^path, :info, "RocksDB version" <> _}, 1_000
+    end
+
+    test "follows the info log in db_log_dir" do
+      path = tmp_dir()
+      log_dir = tmp_dir()
+      _db = Soy.open(path, log_listener: self(), set_db_log_dir: log_dir)
+
+      assert_receive {:soy_log, ^path, :info, _}, 1_000
+      assert [log_file] = File.ls!(log_dir)
+      assert String.ends_with?(log_file, "_LOG")
+    end
+
+    test "filters entries below set_log_level" do
+      log = open_and_close_log([])
+      assert Enum.any?(log, &(&1 =~ "Recovering from manifest"))
+
+      log = open_and_close_log(set_log_level: :error)
+      refute Enum.any?(log, &(&1 =~ "Recovering from manifest"))
+    end
+  end
+
+  defp open_and_close_log(opts) do
+    path = tmp_dir()
+    db = Soy.open(path, [log_listener: self()] ++ opts)
+    :ok = DB.close(db)
+    Process.sleep(300)
+    collect_log(path, [])
+  end
+
+  defp collect_log(path, acc) do
+    receive do
+      {:soy_log, ^path, _level, message} -> collect_log(path, [message | acc])
+    after
+      0 -> Enum.reverse(acc)
+    end
+  end
+
+  describe "close/1" do
+    test "releases snapshots, column families and iterators of the db" do
+      db = Soy.open(tmp_dir())
+      :ok = Soy.put(db, "a", "1")
+      {:ok, cf} = DBCol.create_new(db, "fam")
+      ss = Soy.snapshot(db)
+      it = Soy.Iter.new(db)
+
+      assert :ok = DB.close(db)
+
+      assert Soy.fetch(db, "a") == {:error, :closed}
+      assert Soy.fetch(cf, "a") == {:error, :closed}
+      assert Soy.fetch(ss, "a") == {:error, :closed}
+      assert Soy.Iter.next(it) == {:error, :closed}
+      assert DB.snapshot(db) == {:error, :closed}
+      assert DBCol.open(db, "fam") == {:error, :closed}
+    end
+
+    test "makes get raise while fetch returns the error" do
+      db = Soy.open(tmp_dir())
+      {:ok, cf} = DBCol.create_new(db, "fam")
+      :ok = DB.close(db)
+
+      assert Soy.fetch(db, "a") == {:error, :closed}
+      assert_raise ArgumentError, ~s(cannot get "a": :closed), fn -> Soy.get(db, "a") end
+      assert_raise ArgumentError, fn -> DBCol.get(cf, "a") end
+    end
+
+    test "allows the path to be reopened" do
+      path = tmp_dir()
+      db = Soy.open(path, %OpenOpts{})
+      :ok = Soy.put(db, "a", "1")
+      :ok = DB.close(db)
+
+      db = Soy.open(path)
+      assert Soy.get(db, "a") == "1"
+    end
+  end
+
+  describe "dump/3 and load_dump/3" do
+    setup do
+      db = Soy.open(tmp_dir())
+      {:ok, cf} = DBCol.create_new(db, "feet")
+      value = String.duplicate("v", 10_000)
+      for i <- 1..300, do: :ok = Soy.put(db, "key#{i}", value)
+      for i <- 1..10, do: :ok = DBCol.put(cf, "cf#{i}", "#{i}")
+      {:ok, %{db: db, value: value}}
+    end
+
+    test "round trips in batch and ingest mode", %{db: db, value: value} do
+      path = Path.join(tmp_dir(), "db.dump")
+      assert {:ok, %Soy.DumpInfo{num_entries: 310}} = DB.dump(db, path)
+
+      for mode <- [:batch, :ingest] do
+        other = Soy.open(tmp_dir())
+        assert {:ok, info} = DB.load_dump(other, path, mode: mode)
+        assert info.num_entries == 310
+        assert info.column_families == ["default", "feet"]
+        assert Soy.get(other, "key150") == value
+        assert {:ok, cf} = DBCol.open(other, "feet")
+        assert DBCol.count_range(cf) == 10
+      end
+    end
+
+    test "only holds what the snapshot sees", %{db: db} do
+      ss = Soy.snapshot(db)
+      :ok = Soy.put(db, "later", "1")
+      path = Path.join(tmp_dir(), "db.dump")
+      assert {:ok, info} = Soy.Snapshot.dump(ss, path, column_families: ["default"])
+      assert info.sequence_number == Soy.Snapshot.sequence_number(ss)
+      assert info.column_families == ["default"]
+
+      other = Soy.open(tmp_dir())
+      assert {:ok, %{num_entries: 300}} = DB.load_dump(other, path)
+      assert Soy.get(other, "later") == nil
+      assert DB.list_columns(other) == ["default"]
+    end
+
+    test "rejects a corrupted dump", %{db: db} do
+      path = Path.join(tmp_dir(), "db.dump")
+      {:ok, _} = DB.dump(db, path)
+      bytes = File.read!(path)
+      at = div(byte_size(bytes), 2)
+      <<head::binary-size(at), byte, rest::binary>> = bytes
+      File.write!(path, <<head::binary, Bitwise.bxor(byte, 1), rest::binary>>)
+
+      other = Soy.open(tmp_dir())
+      assert DB.load_dump(other, path) == {:error, "invalid dump: checksum mismatch"}
+    end
+
+    test "does not overwrite a file", %{db: db} do
+      path = Path.join(tmp_dir(), "db.dump")
+      File.write!(path, "")
+      assert {:error, _} = DB.dump(db, path)
+      assert File.read!(path) == ""
+    end
+  end
+
+  describe "subscribe_changes/3" do
+    setup do
+      {:ok, %{db: Soy.open(tmp_dir())}}
+    end
+
+    test "reports batches and column family writes", %{db: db} do
+      {:ok, cf} = DBCol.create_new(db, "feet")
+      {:ok, _} = DB.subscribe_changes(db, "a", cf: "feet")
+      {:ok, _} = DB.subscribe_changes(db, "")
+
+      :ok = DBCol.put(cf, "a1", "1")
+      :ok = DBCol.put(cf, "b1", "2")
+      :ok = DBCol.delete(cf, "a1")
+      ops = [{:put, "x", "1"}, {:put_cf, DBCol.to_ref(cf), "a2", "3"}, {:delete_cf, "feet", "b1"}]
+      3 = Soy.batch(db, ops)
+
+      assert_receive {:soy_change, "feet", :put, "a1", "1"}
+      assert_receive {:soy_change, "feet", :delete, "a1", nil}
+      assert_receive {:soy_change, "default", :put, "x", "1"}
+      assert_receive {:soy_change, "feet", :put, "a2", "3"}
+      refute_receive {:soy_change, "feet", _, "b1", _}
+    end
+
+    test "ends with unsubscribe_changes/2", %{db: db} do
+      {:ok, id} = DB.subscribe_changes(db, "a")
+      assert DB.count_change_subscriptions(db) == 1
+      :ok = DB.unsubscribe_changes(db, id)
+      assert DB.count_change_subscriptions(db) == 0
+      :ok = Soy.put(db, "a", "1")
+      refute_receive {:soy_change, _, _, _, _}
+    end
+
+    test "ends when the subscriber exits", %{db: db} do
+      parent = self()
+
+      pid =
+        spawn(fn ->
+          receive do
+            :stop -> :ok
+            msg -> send(parent, {:got, msg})
+          end
+        end)
+
+      {:ok, _} = DB.subscribe_changes(db, "a", pid: pid)
+      {:ok, _} = DB.subscribe_changes(db, "a")
+      assert DB.count_change_subscriptions(db) == 2
+
+      ref = Process.monitor(pid)
+      send(pid, :stop)
+      assert_receive {:DOWN, ^ref, :process, _, _}
+      # the monitor handles the exit before this call returns
+      :sys.get_state(Soy.ChangeMonitor)
+      assert DB.count_change_subscriptions(db) == 1
+
+      :ok = Soy.put(db, "a", "1")
+      assert_receive {:soy_change, "default", :put, "a", "1"}
+      refute_receive {:got, _}
+    end
+
+    test "fails for an unknown column family", %{db: db} do
+      assert DB.subscribe_changes(db, "a", cf: "nope") ==
+               {:error, "column family does not exist: nope"}
+    end
+  end
 end
@@ -0,0 +1,76 @@
+defmodule Soy.DiffTest do
+  use ExUnit.Case
+  import Soy.TestHelpers
+  alias Soy.{DBCol, Diff, Snapshot}
+
+  doctest Soy.Diff
+
+  setup do
+    db = Soy.open(tmp_dir())
+    {:ok, %{db: db}}
+  end
+
+  test "compares two snapshots", %{db: db} do
+    for k <- ["a", "b", "c"], do: :ok = Soy.put(db, k, "1")
+    old = Soy.snapshot(db)
+    :ok = Soy.put(db, "b", "2")
+    :ok = Soy.delete(db, "c")
+    new = Soy.snapshot(db)
+    :ok = Soy.put(db, "d", "1")
+
+    assert Enum.to_list(Diff.stream(old, new)) == [
+             {:changed, "b", "1", "2"},
+             {:removed, "c", "1"}
+           ]
+
+    assert Enum.to_list(Diff.stream(new, old)) == [
+             {:changed, "b", "2", "1"},
+             {:added, "c", "1"}
+           ]
+
+    assert Enum.to_list(Diff.stream(old, old)) == []
+  end
+
+  test "limits the diff to a column family and key range", %{db: db} do
+    {:ok, cf} = DBCol.create_new(db, "feet")
+    old = Soy.snapshot(db)
+    for k <- ["a", "b", "c", "d"], do: :ok = DBCol.put(cf, k, k)
+    :ok = Soy.put(db, "b", "b")
+
+    assert Enum.to_list(Diff.stream(old, db, cf: "feet", from: "b", to: "d")) == [
+             {:added, "b", "b"},
+             {:added, "c", "c"}
+           ]
+
+    assert Enum.to_list(Diff.stream(old, db, to: "b")) == []
+  end
+
+  test "reads the db as of its creation", %{db: db} do
+    ss = Soy.snapshot(db)
+    diff = Diff.new(ss, db)
+    :ok = Soy.put(db, "a", "1")
+    assert Diff.next(diff) == nil
+  end
+
+  test "returns large diffs in chunks", %{db: db} do
+    ss = Soy.snapshot(db)
+    for i <- 1..2500, do: :ok = Soy.put(db, "key#{i}", "v")
+    diff = Diff.new(ss, db)
+    assert length(Diff.next(diff)) == 1000
+    assert length(Diff.next(diff)) == 1000
+    assert length(Diff.next(diff)) == 500
+    assert Diff.next(diff) == nil
+  end
+
+  test "fails for an unknown column family", %{db: db} do
+    ss = Soy.snapshot(db)
+    assert {:error, "column family does not exist: nope"} = Diff.new(ss, db, cf: "nope")
+  end
+
+  test "fails once a snapshot is released", %{db: db} do
+    ss = Soy.snapshot(db)
+    diff = Diff.new(ss, db)
+    :ok = Snapshot.release(ss)
+    assert Diff.next(diff) == {:error, :released}
+  end
+end
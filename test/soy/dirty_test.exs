@@ -0,0 +1,32 @@
+defmodule Soy.DirtyTest do
+  use ExUnit.Case
+  import Soy.TestHelpers
+  alias Soy.{DBCol, Dirty, Snapshot}
+
+  doctest Soy.Dirty
+
+  describe "dirty dispatch" do
+    test "large batches and multi_gets give the same results as small ones" do
+      db = Soy.open(tmp_dir())
+      {:ok, cf} = DBCol.create_new(db, "big")
+      n = Dirty.thresholds().batch + 1
+      keys = for i <- 1..n, do: "k#{i}"
+      assert Soy.batch(db, Enum.map(keys, fn k -> {:put, k, k} end)) == n
+      assert Soy.multi_get(db, keys) == keys
+      assert Snapshot.multi_get(Snapshot.new(db), keys) == keys
+      assert DBCol.multi_get(cf, keys) == List.duplicate(nil, n)
+    end
+
+    test "few ops with large values go dirty and give the same results" do
+      db = Soy.open(tmp_dir())
+      {:ok, cf} = DBCol.create_new(db, "big")
+      value = :binary.copy("v", Dirty.thresholds().bytes)
+      ops = [{:put, "a", value}, {:put_cf, DBCol.to_ref(cf), "b", value}]
+      assert Dirty.dirty?(:batch, ops)
+      refute Dirty.dirty?(:batch, length(ops))
+      assert Soy.batch(db, ops) == 2
+      assert Soy.get(db, "a") == value
+      assert DBCol.get(cf, "b") == value
+    end
+  end
+end
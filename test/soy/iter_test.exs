@@ -76,6 +76,32 @@ defmodule Soy.IterTest do
     end
   end
 
+  describe "seek/3" do
+    test "returns only keys in :key mode", %{db: db} do
+      it = Iter.new(db)
+      assert Iter.seek(it, :first, :key) == "a"
+      assert Iter.seek(it, :next, :key) == "k1"
+      assert Iter.seek(it, {:next, "k3"}, :key) == "k3"
+      assert Iter.seek(it, :last, :key) == "z"
+      assert Iter.seek(it, :next, :key) == nil
+    end
+
+    test "returns keys with value sizes in :key_size mode", %{db: db} do
+      it = Iter.new(db)
+      assert Iter.seek(it, :last, :key_size) == {"z", 4}
+      assert Iter.seek(it, :prev, :key_size) == {"k3", 2}
+      assert Iter.seek(it, {:prev, "b"}, :key_size) == {"a", 1}
+      assert Iter.seek(it, :prev, :key_size) == nil
+    end
+
+    test "works for a DBCol iter", %{cf: cf} do
+      it = Iter.new(cf)
+      assert Iter.seek(it, :next, :key) == "tk1"
+      assert Iter.seek(it, :next, :key_size) == {"tk2", 3}
+      assert Iter.seek(it, :next) == {"tk3", "tv3"}
+    end
+  end
+
   describe "last/1" do
     test "seeks the last entry in the db", %{db: db} do
       it = Iter.new(db)
@@ -195,6 +221,21 @@ defmodule Soy.IterTest do
     end
   end
 
+  describe "close/1" do
+    test "frees the iter but not the db", %{db: db, cf: cf} do
+      it = Iter.new(db)
+      cf_it = Iter.new(cf)
+      assert Iter.next(it) == {"a", "1"}
+      assert Iter.close(it) == :ok
+      assert Iter.close(it) == :ok
+      assert Iter.next(it) == {:error, :released}
+      assert Iter.key(it) == {:error, :released}
+      assert Iter.valid?(it) == {:error, :released}
+      assert Iter.next(cf_it) == {"tk1", "tv1"}
+      assert Soy.fetch(db, "a") == {:ok, "1"}
+    end
+  end
+
   # describe "new/" do
   # test "returns a cf iter for a snapshot", %{db: db} do
   #   ss = Snapshot.new(db)
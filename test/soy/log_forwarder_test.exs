@@ -0,0 +1,20 @@
+defmodule Soy.LogForwarderTest do
+  use ExUnit.Case
+  import ExUnit.CaptureLog
+  import Soy.TestHelpers
+
+  doctest Soy.LogForwarder
+
+  test "logs the info log of a db opened with it as log_listener" do
+    path = tmp_dir()
+
+    log =
+      capture_log(fn ->
+        db = Soy.open(path, log_listener: Soy.LogForwarder)
+        :ok = Soy.DB.close(db)
+        Process.sleep(300)
+      end)
+
+    assert log =~ "RocksDB version"
+  end
+end
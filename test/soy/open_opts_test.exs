@@ -0,0 +1,125 @@
+defmodule Soy.OpenOptsTest do
+  use ExUnit.Case
+  import Soy.TestHelpers
+  alias Soy.{DB, DBCol, OpenOpts}
+
+  doctest Soy.OpenOpts
+
+  describe "load_latest/1" do
+    test "loads the options of every column family" do
+      path = tmp_dir()
+      db = Soy.open(path, set_compaction_style: :level, prefix_length: 3)
+      {:ok, _} = DBCol.create_new(db, "fam")
+      :ok = DB.close(db)
+
+      assert {:ok, %{db: db_opts, column_families: cfs}} = OpenOpts.load_latest(path)
+      assert db_opts.create_if_missing == true
+      assert %{set_compaction_style: :level, prefix_length: 3} = cfs["default"]
+      assert Map.has_key?(cfs, "fam")
+    end
+
+    test "is an error without an OPTIONS file" do
+      assert {:error, "no OPTIONS file found in: " <> _} = OpenOpts.load_latest(tmp_dir())
+    end
+  end
+
+  describe "round trip" do
+    test "persists the options given at open" do
+      path = tmp_dir()
+
+      opts = [
+        set_level_zero_slowdown_writes_trigger: 17,
+        set_level_zero_stop_writes_trigger: 33,
+        set_max_background_jobs: 3,
+        set_wal_ttl_seconds: 60,
+        set_wal_size_limit_mb: 16,
+        set_max_total_wal_size: 1_048_576,
+        set_atomic_flush: true,
+        set_paranoid_checks: true,
+        set_delete_obsolete_files_period_micros: 1_000_000,
+        set_level_compaction_dynamic_level_bytes: true,
+        set_max_bytes_for_level_base: 1_048_576,
+        set_max_bytes_for_level_multiplier: 8,
+        set_universal_compaction_options: [set_size_ratio: 5, set_stop_style: :similar],
+        set_ratelimiter: {10_485_760, 100_000, 10}
+      ]
+
+      :ok = DB.close(Soy.open(path, opts))
+      {:ok, loaded} = OpenOpts.load_latest(path)
+
+      assert %{
+               set_max_background_jobs: 3,
+               set_wal_ttl_seconds: 60,
+               set_wal_size_limit_mb: 16,
+               set_max_total_wal_size: 1_048_576,
+               set_atomic_flush: true,
+               set_paranoid_checks: true,
+               set_delete_obsolete_files_period_micros: 1_000_000
+             } = loaded.db
+
+      refute Map.has_key?(loaded.db, :set_wal_dir)
+
+      assert %{
+               set_level_zero_slowdown_writes_trigger: 17,
+               set_level_zero_stop_writes_trigger: 33,
+               set_level_compaction_dynamic_level_bytes: true,
+               set_max_bytes_for_level_base: 1_048_576,
+               set_max_bytes_for_level_multiplier: 8.0,
+               set_universal_compaction_options: %Soy.UniversalCompactOpts{
+                 set_size_ratio: 5,
+                 set_stop_style: :similar,
+                 set_max_merge_width: nil
+               }
+             } = loaded.column_families["default"]
+
+      assert {:ok, _} = loaded |> OpenOpts.from_persisted() |> OpenOpts.validate()
+    end
+  end
+
+  describe "validate/1" do
+    test "validates column family options" do
+      opts = %OpenOpts{column_families: %{"fam" => [set_write_buffer_size: -1]}}
+      assert {:error, {:column_families, _}} = OpenOpts.validate(opts)
+    end
+
+    test "accepts column family options given as structs" do
+      cf_opts = %OpenOpts{set_write_buffer_size: 8_388_608}
+      opts = %OpenOpts{column_families: %{"fam" => cf_opts}}
+      assert {:ok, %OpenOpts{column_families: %{"fam" => ^cf_opts}}} = OpenOpts.validate(opts)
+    end
+
+    test "makes open raise for invalid options" do
+      assert_raise ArgumentError, ~r/set_ratelimiter/, fn ->
+        Soy.open(tmp_dir(), set_ratelimiter: 100)
+      end
+    end
+  end
+
+  describe "load_latest_options open option" do
+    test "reopens with the persisted options overridden by the given ones" do
+      path = tmp_dir()
+      db = Soy.open(path, set_max_open_files: 100, prefix_length: 3)
+      :ok = DB.close(db)
+
+      db = Soy.open(path, load_latest_options: true, set_max_open_files: 200)
+      :ok = DB.close(db)
+
+      assert {:ok, %{db: %{set_max_open_files: 200}, column_families: cfs}} =
+               OpenOpts.load_latest(path)
+
+      assert cfs["default"].prefix_length == 3
+    end
+
+    test "reopens a db with another column family" do
+      path = tmp_dir()
+      db = Soy.open(path)
+      {:ok, cf} = DBCol.create_new(db, "feet")
+      :ok = DBCol.put(cf, "a", "1")
+      :ok = DB.close(db)
+
+      db = Soy.open(path, load_latest_options: true)
+      {:ok, cf} = DBCol.open(db, "feet")
+      assert DBCol.get(cf, "a") == "1"
+    end
+  end
+end
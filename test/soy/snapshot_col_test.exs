@@ -113,4 +113,16 @@ defmodule Soy.SnapshotColTest do
              ]
     end
   end
+
+  describe "dropped column family" do
+    test "fails reads and iterators", %{cf: cf, ss: ss, ss_cf: ss_cf} do
+      it = SnapshotCol.iter(ss_cf)
+      assert :ok = DBCol.destroy(cf)
+      dropped = {:error, {:column_family_dropped, "feet"}}
+      assert SnapshotCol.fetch(ss_cf, "me") == dropped
+      assert SnapshotCol.multi_get(ss_cf, ["me"]) == dropped
+      assert Soy.Iter.next(it) == dropped
+      assert SnapshotCol.new(ss, "feet") == {:error, "column family does not exist: feet"}
+    end
+  end
 end
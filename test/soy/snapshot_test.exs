@@ -3,6 +3,8 @@ defmodule Soy.SnapshotTest do
   import Soy.TestHelpers
   alias Soy.{Iter, Snapshot}
 
+  doctest Soy.Snapshot
+
   describe "new/1" do
     test "returns a reference" do
       db = Soy.open(tmp_dir())
@@ -41,6 +43,29 @@ defmodule Soy.SnapshotTest do
     end
   end
 
+  describe "release/1" do
+    test "releases the snapshot, its column families and its iterators" do
+      db = Soy.open(tmp_dir())
+      :ok = Soy.put(db, "k1", "v1")
+      {:ok, cf} = Soy.DBCol.create_new(db, "feet")
+      ss = Snapshot.new(db)
+      {:ok, ss_cf} = Soy.SnapshotCol.new(ss, "feet")
+      it = Iter.new(ss)
+      cf_it = Iter.new(ss_cf)
+
+      assert Snapshot.release(ss) == :ok
+      assert Snapshot.release(ss) == :ok
+
+      assert Snapshot.fetch(ss, "k1") == {:error, :released}
+      assert Soy.SnapshotCol.fetch(ss_cf, "k1") == {:error, :released}
+      assert Iter.next(it) == {:error, :released}
+      assert Iter.next(cf_it) == {:error, :released}
+      assert Iter.new(ss) == {:error, :released}
+      assert Soy.fetch(db, "k1") == {:ok, "v1"}
+      assert Soy.DBCol.put(cf, "k2", "v2") == :ok
+    end
+  end
+
   describe "iter/1" do
     test "iterates through snapshotted entries" do
       db = Soy.open(tmp_dir())
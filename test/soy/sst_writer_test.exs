@@ -0,0 +1,91 @@
+defmodule Soy.SstWriterTest do
+  use ExUnit.Case
+  import Soy.TestHelpers
+  alias Soy.{DB, SstFileInfo, SstWriter}
+
+  doctest Soy.SstWriter
+
+  defp sst_path, do: Path.join(tmp_dir(), "test.sst")
+
+  describe "add/2" do
+    test "rejects keys that are not ascending" do
+      {:ok, w} = SstWriter.open(sst_path())
+      assert :ok = SstWriter.put(w, "b", "2")
+      assert {:error, reason} = SstWriter.put(w, "a", "1")
+      assert reason =~ "ascending"
+    end
+  end
+
+  describe "finish/1" do
+    test "returns the file info" do
+      path = sst_path()
+      {:ok, w} = SstWriter.open(path)
+      3 = SstWriter.add(w, [{:put, "a", "1"}, {:put, "b", "2"}, {:put, "c", "3"}])
+
+      assert {:ok, %SstFileInfo{} = info} = SstWriter.finish(w)
+      assert info.path == path
+      assert info.num_entries == 3
+      assert info.smallest_key == "a"
+      assert info.largest_key == "c"
+      assert info.file_size == File.stat!(path).size
+    end
+
+    test "fails for an empty file" do
+      {:ok, w} = SstWriter.open(sst_path())
+      assert {:error, _} = SstWriter.finish(w)
+    end
+
+    test "cannot be used after finishing" do
+      {:ok, w} = SstWriter.open(sst_path())
+      :ok = SstWriter.put(w, "a", "1")
+      {:ok, _} = SstWriter.finish(w)
+      assert SstWriter.finish(w) == {:error, "sst writer is already finished"}
+      assert SstWriter.put(w, "b", "2") == {:error, "sst writer is already finished"}
+    end
+  end
+
+  describe "DB.ingest/3" do
+    test "overwrites and deletes existing keys" do
+      db = Soy.open(tmp_dir())
+      :ok = Soy.put(db, "a", "old")
+      :ok = Soy.put(db, "b", "old")
+      path = sst_path()
+      {:ok, w} = SstWriter.open(path)
+      2 = SstWriter.add(w, [{:put, "a", "new"}, {:delete, "b"}])
+      {:ok, _} = SstWriter.finish(w)
+
+      assert :ok = DB.ingest(db, [path])
+      assert Soy.get(db, "a") == "new"
+      assert Soy.get(db, "b") == nil
+    end
+
+    test "keeps snapshots consistent" do
+      db = Soy.open(tmp_dir())
+      ss = Soy.snapshot(db)
+      path = sst_path()
+      {:ok, w} = SstWriter.open(path)
+      :ok = SstWriter.put(w, "a", "1")
+      {:ok, _} = SstWriter.finish(w)
+
+      assert :ok = DB.ingest(db, [path], set_snapshot_consistency: true)
+      assert Soy.get(db, "a") == "1"
+      assert Soy.fetch(ss, "a") == :error
+    end
+
+    test "moves the files when asked" do
+      db = Soy.open(tmp_dir())
+      path = sst_path()
+      {:ok, w} = SstWriter.open(path)
+      :ok = SstWriter.put(w, "a", "1")
+      {:ok, _} = SstWriter.finish(w)
+
+      assert :ok = DB.ingest(db, [path], set_move_files: true)
+      assert Soy.get(db, "a") == "1"
+    end
+
+    test "returns an error for a missing file" do
+      db = Soy.open(tmp_dir())
+      assert {:error, _} = DB.ingest(db, [sst_path()])
+    end
+  end
+end
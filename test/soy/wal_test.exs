@@ -1,15 +1,219 @@
 defmodule Soy.WalTest do
   use ExUnit.Case
-  # import Soy.TestHelpers
-
-  # doctest Soy.Iter
-
-  # setup do
-  #   db = Soy.open(tmp_dir())
-  #   :ok = Soy.put(db, "a", "1")
-  #   :ok = Soy.put(db, "k1", "v1")
-  #   :ok = Soy.put(db, "k3", "v3")
-  #   :ok = Soy.put(db, "k2", "v2")
-  #   :ok = Soy.put(db, "z", "1000")
-  # end
+  import Soy.TestHelpers
+  alias Soy.{DB, DBCol, Snapshot, Wal}
+
+  doctest Soy.Wal
+
+  setup do
+    db = Soy.open(tmp_dir())
+    {:ok, %{db: db}}
+  end
+
+  describe "updates_since/2" do
+    test "starts at the given sequence number", %{db: db} do
+      :ok = Soy.put(db, "a", "1")
+      :ok = Soy.put(db, "b", "2")
+      :ok = Soy.delete(db, "a")
+      wal = Wal.updates_since(db, 2)
+      assert Wal.next(wal) == {2, [{:put, "default", "b", "2"}]}
+      assert Wal.next(wal) == {3, [{:delete, "default", "a"}]}
+      assert Wal.next(wal) == nil
+    end
+
+    test "names the column families of the writes", %{db: db} do
+      {:ok, cf} = DBCol.create_new(db, "feet")
+      :ok = DBCol.put(cf, "a", "1")
+      :ok = Soy.put(db, "b", "2")
+      wal = Wal.updates_since(db, 1)
+      assert Wal.next(wal) == {1, [{:put, "feet", "a", "1"}]}
+      assert Wal.next(wal) == {2, [{:put, "default", "b", "2"}]}
+    end
+
+    test "continues after a snapshot without a gap", %{db: db} do
+      :ok = Soy.put(db, "a", "1")
+      ss = Soy.snapshot(db)
+      wal = Wal.updates_since(db, ss)
+      assert Wal.next(wal) == nil
+
+      :ok = Soy.put(db, "b", "2")
+      seq = Snapshot.sequence_number(ss)
+      assert Wal.next(wal) == {seq + 1, [{:put, "default", "b", "2"}]}
+      assert Wal.next(wal) == nil
+
+      :ok = Soy.put(db, "c", "3")
+      assert Wal.next(wal) == {seq + 2, [{:put, "default", "c", "3"}]}
+    end
+
+    test "returns batches as a whole", %{db: db} do
+      2 = Soy.batch(db, [{:put, "a", "1"}, {:delete, "b"}])
+      wal = Wal.updates_since(db, 1)
+      assert Wal.next(wal) == {1, [{:put, "default", "a", "1"}, {:delete, "default", "b"}]}
+      assert DB.latest_sequence_number(db) == 2
+    end
+
+    test "keeps the row shape of wal_iter_next", %{db: db} do
+      {:ok, cf} = DBCol.create_new(db, "feet")
+      :ok = DBCol.put(cf, "a", "1")
+      2 = Soy.batch(db, [{:put, "b", "2"}, {:delete, "c"}])
+      ref = Wal.to_ref(Wal.updates_since(db, 1))
+      assert Soy.Native.wal_iter_next(ref) == {1, []}
+      assert Soy.Native.wal_iter_next(ref) == {2, [{:put, "b", "2"}, {:delete, "c"}]}
+      assert Soy.Native.wal_iter_next(ref) == nil
+    end
+
+    test "fails after the db is closed", %{db: db} do
+      :ok = Soy.put(db, "a", "1")
+      wal = Wal.updates_since(db, 1)
+      :ok = DB.close(db)
+      assert Wal.next(wal) == {:error, :closed}
+      assert Wal.updates_since(db, 1) == {:error, :closed}
+    end
+  end
+
+  describe "take/2" do
+    test "continues after catching up", %{db: db} do
+      wal = Wal.updates_since(db, 1)
+      assert Wal.take(wal, 5) == []
+      :ok = Soy.put(db, "a", "1")
+      :ok = Soy.put(db, "b", "2")
+      assert Wal.take(wal, 5) == [
+               {1, [{:put, "default", "a", "1"}]},
+               {2, [{:put, "default", "b", "2"}]}
+             ]
+      assert Wal.take(wal, 0) == []
+    end
+
+    test "fails after the db is closed", %{db: db} do
+      wal = Wal.updates_since(db, 1)
+      :ok = DB.close(db)
+      assert Wal.take(wal, 1) == {:error, :closed}
+    end
+  end
+
+  describe "subscribe/2" do
+    test "notifies once per catch up", %{db: db} do
+      :ok = Soy.put(db, "a", "1")
+      wal = Wal.updates_since(db, 1)
+      assert {:ok, id} = Wal.subscribe(wal, interval: 5)
+      assert_receive {:soy_wal, ^id, 1}
+
+      :ok = Soy.put(db, "b", "2")
+      refute_receive {:soy_wal, ^id, _}, 50
+      assert length(Wal.take(wal, 10)) == 2
+
+      :ok = Soy.put(db, "c", "3")
+      assert_receive {:soy_wal, ^id, 3}
+    end
+
+    test "notifies another pid", %{db: db} do
+      parent = self()
+      pid = spawn(fn -> receive do: (msg -> send(parent, {:got, msg})) end)
+      wal = Wal.updates_since(db, 1)
+      assert {:ok, id} = Wal.subscribe(wal, pid: pid, interval: 5)
+      :ok = Soy.put(db, "a", "1")
+      assert_receive {:got, {:soy_wal, ^id, 1}}
+    end
+
+    test "replaces the earlier subscription", %{db: db} do
+      wal = Wal.updates_since(db, 1)
+      {:ok, old} = Wal.subscribe(wal, interval: 5)
+      {:ok, new} = Wal.subscribe(wal, interval: 5)
+      :ok = Soy.put(db, "a", "1")
+      assert_receive {:soy_wal, ^new, 1}
+      refute_receive {:soy_wal, ^old, _}, 50
+    end
+  end
+
+  describe "next_binary/1 and apply_batch/3" do
+    test "replicate writes to a follower", %{db: db} do
+      follower = Soy.open(tmp_dir())
+      {:ok, cf} = DBCol.create_new(db, "feet")
+      {:ok, follower_cf} = DBCol.create_new(follower, "feet")
+      :ok = Soy.put(db, "a", "1")
+      3 = Soy.batch(db, [{:put, "b", "2"}, {:delete, "a"}, {:put, "c", "3"}])
+      :ok = DBCol.put(cf, "d", "4")
+
+      wal = Wal.updates_since(db, 1)
+      assert {1, b1} = Wal.next_binary(wal)
+      assert {2, b2} = Wal.next_binary(wal)
+      assert {5, b3} = Wal.next_binary(wal)
+      assert Wal.next_binary(wal) == nil
+
+      cf_ids = Wal.cf_ids(db)
+      assert Wal.apply_batch(follower, b1, cf_ids) == 1
+      assert Wal.apply_batch(follower, b2, cf_ids) == 4
+      assert Wal.apply_batch(follower, b3, cf_ids) == 5
+
+      assert Soy.get(follower, "a") == nil
+      assert Soy.get(follower, "b") == "2"
+      assert Soy.get(follower, "c") == "3"
+      assert DBCol.get(follower_cf, "d") == "4"
+      assert DB.latest_sequence_number(follower) == DB.latest_sequence_number(db)
+    end
+
+    test "rejects batches out of order", %{db: db} do
+      follower = Soy.open(tmp_dir())
+      :ok = Soy.put(db, "a", "1")
+      :ok = Soy.put(db, "b", "2")
+      wal = Wal.updates_since(db, 1)
+      {1, b1} = Wal.next_binary(wal)
+      {2, b2} = Wal.next_binary(wal)
+
+      cf_ids = Wal.cf_ids(db)
+      assert Wal.apply_batch(follower, b2, cf_ids) == {:error, {:out_of_order, 1}}
+      assert Wal.apply_batch(follower, b1, cf_ids) == 1
+      assert Wal.apply_batch(follower, b1, cf_ids) == {:error, {:out_of_order, 2}}
+      assert Soy.get(follower, "b") == nil
+    end
+
+    test "rejects batches for column families of other ids", %{db: db} do
+      follower = Soy.open(tmp_dir())
+      {:ok, _} = DBCol.create_new(db, "feet")
+      {:ok, cf} = DBCol.create_new(db, "hands")
+      {:ok, _} = DBCol.create_new(follower, "hands")
+      {:ok, _} = DBCol.create_new(follower, "feet")
+      :ok = DBCol.put(cf, "a", "1")
+      {1, batch} = Wal.next_binary(Wal.updates_since(db, 1))
+
+      assert Wal.apply_batch(follower, batch, Wal.cf_ids(db)) ==
+               {:error, {:column_family_mismatch, 2}}
+
+      assert DB.latest_sequence_number(follower) == 0
+    end
+
+    test "rejects malformed batches", %{db: db} do
+      assert {:error, "malformed WriteBatch"} = Wal.apply_batch(db, "nope", %{})
+      assert DB.latest_sequence_number(db) == 0
+    end
+  end
+
+  describe "WAL retention" do
+    test "flushed WAL files are deleted by default" do
+      path = tmp_dir()
+      db = Soy.open(path)
+      :ok = Soy.put(db, "a", "1")
+      :ok = DB.close(db)
+
+      db = Soy.open(path)
+      assert DB.latest_sequence_number(db) == 1
+      assert DB.oldest_available_sequence_number(db) == 2
+      :ok = Soy.put(db, "b", "2")
+      assert DB.oldest_available_sequence_number(db) == 2
+    end
+
+    test "archived WAL files stay readable" do
+      path = tmp_dir()
+      db = Soy.open(path, set_wal_ttl_seconds: 3600)
+      :ok = Soy.put(db, "a", "1")
+      :ok = DB.close(db)
+
+      db = Soy.open(path, set_wal_ttl_seconds: 3600)
+      :ok = Soy.put(db, "b", "2")
+      assert DB.oldest_available_sequence_number(db) == 1
+      wal = Wal.updates_since(db, 1)
+      assert Wal.next(wal) == {1, [{:put, "default", "a", "1"}]}
+      assert Wal.next(wal) == {2, [{:put, "default", "b", "2"}]}
+    end
+  end
 end